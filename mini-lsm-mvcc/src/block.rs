@@ -13,45 +13,145 @@
 // limitations under the License.
 
 mod builder;
+mod hash_index;
 mod iterator;
 
-pub use builder::BlockBuilder;
+use anyhow::{Result, bail};
+pub use builder::{BlockBuilder, DEFAULT_RESTART_INTERVAL};
 use bytes::{Buf, BufMut, Bytes};
 pub use iterator::BlockIterator;
 
-pub(crate) const SIZEOF_U16: usize = std::mem::size_of::<u16>();
+use crate::varint::{get_varint, put_varint, varint_len};
+
+pub(crate) const SIZEOF_U32: usize = std::mem::size_of::<u32>();
+pub(crate) const SIZEOF_U64: usize = std::mem::size_of::<u64>();
+/// Set in the restart count at the end of the block if the block has a hash index.
+const HASH_INDEX_FLAG: u32 = 1 << 31;
+
+/// How entries are laid out in the data section of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlockLayout {
+    /// Each entry stores the length shared with the previous key and the length of the rest.
+    #[default]
+    Variable,
+    /// All keys have the given length and are stored in full without length prefixes.
+    FixedKey(usize),
+}
+
+impl BlockLayout {
+    /// Encode the layout into the SST meta.
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            BlockLayout::Variable => buf.put_u8(0),
+            BlockLayout::FixedKey(key_len) => {
+                buf.put_u8(1);
+                put_varint(buf, *key_len as u64);
+            }
+        }
+    }
+
+    pub(crate) fn encoded_len(&self) -> usize {
+        match self {
+            BlockLayout::Variable => 1,
+            BlockLayout::FixedKey(key_len) => 1 + varint_len(*key_len as u64),
+        }
+    }
+
+    pub(crate) fn decode(buf: &mut &[u8]) -> Result<Self> {
+        match buf.get_u8() {
+            0 => Ok(BlockLayout::Variable),
+            1 => Ok(BlockLayout::FixedKey(get_varint(buf) as usize)),
+            tag => bail!("unknown block layout {}", tag),
+        }
+    }
+}
 
 /// A block is the smallest unit of read and caching in LSM tree. It is a collection of sorted
 /// key-value pairs.
+///
+/// Each key is prefix-compressed against the previous key in the block. Every
+/// `restart_interval` entries a full key is stored, and the offsets of these entries are kept in
+/// `offsets` so that lookups can binary-search them. Timestamps are stored as varint deltas from
+/// `base_ts`, which is the timestamp of the first key and is kept in the block trailer. With
+/// `BlockLayout::FixedKey`, keys are neither prefix-compressed nor length-prefixed.
+///
+/// All sections are slices of the buffer the block was decoded from, so a block read from disk
+/// shares one allocation between the block cache and its iterators.
+#[derive(Default)]
 pub struct Block {
-    pub(crate) data: Vec<u8>,
-    pub(crate) offsets: Vec<u16>,
+    pub(crate) data: Bytes,
+    /// The encoded restart array, read with `restart`.
+    pub(crate) offsets: Bytes,
+    pub(crate) base_ts: u64,
+    /// The layout is not part of the encoded block, it is kept in the SST meta.
+    pub(crate) layout: BlockLayout,
+    /// Buckets of the optional hash index used by point lookups.
+    pub(crate) hash_index: Option<Bytes>,
 }
 
 impl Block {
     pub fn encode(&self) -> Bytes {
-        let mut buf = self.data.clone();
-        let offsets_len = self.offsets.len();
-        for offset in &self.offsets {
-            buf.put_u16(*offset);
+        let mut buf = self.data.to_vec();
+        let mut restarts_len = self.num_restarts() as u32;
+        buf.put_slice(&self.offsets);
+        if let Some(buckets) = &self.hash_index {
+            buf.put_slice(buckets);
+            buf.put_u32(buckets.len() as u32);
+            restarts_len |= HASH_INDEX_FLAG;
         }
-        // Adds number of elements at the end of the block
-        buf.put_u16(offsets_len as u16);
+        buf.put_u64(self.base_ts);
+        // Adds number of restart points at the end of the block
+        buf.put_u32(restarts_len);
         buf.into()
     }
 
+    /// Decode a block from a copy of `data`. Use `decode_from_bytes` to avoid the copy.
     pub fn decode(data: &[u8]) -> Self {
-        // get number of elements in the block
-        let entry_offsets_len = (&data[data.len() - SIZEOF_U16..]).get_u16() as usize;
-        let data_end = data.len() - SIZEOF_U16 - entry_offsets_len * SIZEOF_U16;
-        let offsets_raw = &data[data_end..data.len() - SIZEOF_U16];
-        // get offset array
-        let offsets = offsets_raw
-            .chunks(SIZEOF_U16)
-            .map(|mut x| x.get_u16())
-            .collect();
-        // retrieve data
-        let data = data[0..data_end].to_vec();
-        Self { data, offsets }
+        Self::decode_from_bytes(Bytes::copy_from_slice(data))
+    }
+
+    /// Decode a block that keeps referencing `data`.
+    pub fn decode_from_bytes(data: Bytes) -> Self {
+        Self::decode_with_layout(data, BlockLayout::Variable)
+    }
+
+    /// Decode a block built with the given layout.
+    pub fn decode_with_layout(data: Bytes, layout: BlockLayout) -> Self {
+        // get number of restart points in the block
+        let mut end = data.len() - SIZEOF_U32;
+        let restarts_len = (&data[end..]).get_u32();
+        end -= SIZEOF_U64;
+        let base_ts = (&data[end..]).get_u64();
+        let hash_index = if restarts_len & HASH_INDEX_FLAG != 0 {
+            let num_buckets = (&data[end - SIZEOF_U32..]).get_u32() as usize;
+            end -= SIZEOF_U32 + num_buckets;
+            Some(data.slice(end..end + num_buckets))
+        } else {
+            None
+        };
+        let restarts_len = (restarts_len & !HASH_INDEX_FLAG) as usize;
+        let data_end = end - restarts_len * SIZEOF_U32;
+        Self {
+            offsets: data.slice(data_end..end),
+            data: data.slice(..data_end),
+            base_ts,
+            layout,
+            hash_index,
+        }
+    }
+
+    /// Number of restart points in the block.
+    pub(crate) fn num_restarts(&self) -> usize {
+        self.offsets.len() / SIZEOF_U32
+    }
+
+    /// Offset of the idx-th restart point in `data`.
+    pub(crate) fn restart(&self, idx: usize) -> usize {
+        (&self.offsets[idx * SIZEOF_U32..]).get_u32() as usize
+    }
+
+    /// The encoded restart array, one big-endian `u32` offset per restart point.
+    pub(crate) fn restarts(&self) -> &Bytes {
+        &self.offsets
     }
 }
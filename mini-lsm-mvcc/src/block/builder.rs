@@ -15,28 +15,44 @@
 use bytes::BufMut;
 
 use crate::key::{KeySlice, KeyVec};
+use crate::varint::{put_varint, varint_len, zigzag_encode};
 
-use super::{Block, SIZEOF_U16};
+use super::{Block, BlockLayout, SIZEOF_U32, SIZEOF_U64, hash_index};
+
+/// The default number of entries between two restart points.
+pub const DEFAULT_RESTART_INTERVAL: usize = 16;
 
 /// Builds a block.
 pub struct BlockBuilder {
-    /// Offsets of each key-value entries.
-    offsets: Vec<u16>,
+    /// Offsets of the entries that store a full key.
+    restarts: Vec<u32>,
     /// All serialized key-value pairs in the block.
     data: Vec<u8>,
     /// The expected block size.
     block_size: usize,
-    /// The first key in the block
-    first_key: KeyVec,
+    /// Number of entries between two restart points.
+    restart_interval: usize,
+    /// Number of entries added since the last restart point.
+    counter: usize,
+    /// The last key added to the block
+    last_key: KeyVec,
+    /// The timestamp of the first key, which other timestamps are encoded against.
+    base_ts: u64,
+    /// How entries are laid out.
+    layout: BlockLayout,
+    /// Whether to append a hash index for point lookups.
+    hash_index: bool,
+    /// Hash and restart index of the first version of each user key.
+    key_hashes: Vec<(u32, u8)>,
 }
 
-fn compute_overlap(first_key: KeySlice, key: KeySlice) -> usize {
+fn compute_overlap(last_key: KeySlice, key: KeySlice) -> usize {
     let mut i = 0;
     loop {
-        if i >= first_key.key_len() || i >= key.key_len() {
+        if i >= last_key.key_len() || i >= key.key_len() {
             break;
         }
-        if first_key.key_ref()[i] != key.key_ref()[i] {
+        if last_key.key_ref()[i] != key.key_ref()[i] {
             break;
         }
         i += 1;
@@ -48,53 +64,125 @@ impl BlockBuilder {
     /// Creates a new block builder.
     pub fn new(block_size: usize) -> Self {
         Self {
-            offsets: Vec::new(),
+            restarts: Vec::new(),
             data: Vec::new(),
             block_size,
-            first_key: KeyVec::new(),
+            restart_interval: DEFAULT_RESTART_INTERVAL,
+            counter: 0,
+            last_key: KeyVec::new(),
+            base_ts: 0,
+            layout: BlockLayout::Variable,
+            hash_index: false,
+            key_hashes: Vec::new(),
         }
     }
 
+    /// Store a full key every `restart_interval` entries.
+    pub fn with_restart_interval(mut self, restart_interval: usize) -> Self {
+        assert!(restart_interval > 0, "restart interval must be positive");
+        self.restart_interval = restart_interval;
+        self
+    }
+
+    /// Lay out entries with the given layout. All keys must match the layout.
+    pub fn with_layout(mut self, layout: BlockLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Append a hash index to the block so that point lookups can skip the binary search.
+    pub fn with_hash_index(mut self, hash_index: bool) -> Self {
+        self.hash_index = hash_index;
+        self
+    }
+
     fn estimated_size(&self) -> usize {
-        SIZEOF_U16 /* number of key-value pairs in the block */ +  self.offsets.len() * SIZEOF_U16 /* offsets */ + self.data.len()
-        // key-value pairs
+        let hash_index_size = if self.hash_index {
+            hash_index::num_buckets(self.key_hashes.len() + 1) + SIZEOF_U32
+        } else {
+            0
+        };
+        SIZEOF_U32 /* number of restart points in the block */ + SIZEOF_U64 /* base ts */ + self.restarts.len() * SIZEOF_U32 /* restarts */ + self.data.len() /* key-value pairs */ + hash_index_size
     }
 
     /// Adds a key-value pair to the block. Returns false when the block is full.
     #[must_use]
     pub fn add(&mut self, key: KeySlice, value: &[u8]) -> bool {
         assert!(!key.is_empty(), "key must not be empty");
-        if self.estimated_size() + key.raw_len() + value.len() + SIZEOF_U16 * 3 /* key_len, value_len and offset */ > self.block_size
-            && !self.is_empty()
-        {
+        let restart = self.is_empty() || self.counter >= self.restart_interval;
+        // A restart point stores the full key, and so does every entry of a fixed-width layout.
+        let overlap = match self.layout {
+            BlockLayout::Variable if !restart => compute_overlap(self.last_key.as_key_slice(), key),
+            BlockLayout::Variable => 0,
+            BlockLayout::FixedKey(key_len) => {
+                assert_eq!(
+                    key.key_len(),
+                    key_len,
+                    "key does not match the block layout"
+                );
+                0
+            }
+        };
+        let unshared = key.key_len() - overlap;
+        let key_header_size = match self.layout {
+            BlockLayout::Variable => varint_len(overlap as u64) + varint_len(unshared as u64),
+            BlockLayout::FixedKey(_) => 0,
+        };
+        if self.is_empty() {
+            self.base_ts = key.ts();
+        }
+        let ts_delta = zigzag_encode(key.ts().wrapping_sub(self.base_ts) as i64);
+        let entry_size = key_header_size
+            + unshared
+            + varint_len(ts_delta)
+            + varint_len(value.len() as u64)
+            + value.len();
+        let restart_size = if restart { SIZEOF_U32 } else { 0 };
+        if self.estimated_size() + restart_size + entry_size > self.block_size && !self.is_empty() {
             return false;
         }
-        // Add the offset of the data into the offset array.
-        self.offsets.push(self.data.len() as u16);
-        let overlap = compute_overlap(self.first_key.as_key_slice(), key);
-        // Encode key overlap.
-        self.data.put_u16(overlap as u16);
-        // Encode key length.
-        self.data.put_u16((key.key_len() - overlap) as u16);
+        assert!(
+            self.data.len() + entry_size <= u32::MAX as usize,
+            "block is too large"
+        );
+        if restart {
+            self.restarts.push(self.data.len() as u32);
+            self.counter = 0;
+        }
+        if self.layout == BlockLayout::Variable {
+            // Encode the length shared with the previous key.
+            put_varint(&mut self.data, overlap as u64);
+            // Encode the length of the rest of the key.
+            put_varint(&mut self.data, unshared as u64);
+        }
         // Encode key content.
         self.data.put(&key.key_ref()[overlap..]);
-        // Encode key ts
-        self.data.put_u64(key.ts());
+        // Encode key ts as the difference from the base ts.
+        put_varint(&mut self.data, ts_delta);
         // Encode value length.
-        self.data.put_u16(value.len() as u16);
+        put_varint(&mut self.data, value.len() as u64);
         // Encode value content.
         self.data.put(value);
 
-        if self.first_key.is_empty() {
-            self.first_key = key.to_key_vec();
+        if self.hash_index
+            && (self.key_hashes.is_empty() || self.last_key.key_ref() != key.key_ref())
+        {
+            // Only the first version of each user key is indexed. The restart index is truncated
+            // for large blocks, which do not get an index anyway.
+            self.key_hashes.push((
+                hash_index::hash_key(key.key_ref()),
+                (self.restarts.len() - 1) as u8,
+            ));
         }
+        self.counter += 1;
+        self.last_key.set_from_slice(key);
 
         true
     }
 
     /// Check if there are no key-value pairs in the block.
     pub fn is_empty(&self) -> bool {
-        self.offsets.is_empty()
+        self.restarts.is_empty()
     }
 
     /// Finalize the block.
@@ -102,9 +190,22 @@ impl BlockBuilder {
         if self.is_empty() {
             panic!("block should not be empty");
         }
+        let hash_index =
+            if self.hash_index && self.restarts.len() <= hash_index::MAX_RESTARTS_FOR_HASH_INDEX {
+                Some(hash_index::build(&self.key_hashes))
+            } else {
+                None
+            };
+        let mut restarts = Vec::with_capacity(self.restarts.len() * SIZEOF_U32);
+        for restart in &self.restarts {
+            restarts.put_u32(*restart);
+        }
         Block {
-            data: self.data,
-            offsets: self.offsets,
+            data: self.data.into(),
+            offsets: restarts.into(),
+            base_ts: self.base_ts,
+            layout: self.layout,
+            hash_index: hash_index.map(Into::into),
         }
     }
 }
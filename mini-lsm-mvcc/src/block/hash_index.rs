@@ -0,0 +1,64 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A hash index that maps user keys to the restart interval holding their first version in a
+//! data block. Each bucket is one byte: the restart index, or one of the markers below.
+
+/// The bucket does not contain any key.
+const BUCKET_EMPTY: u8 = 255;
+/// More than one user key falls into the bucket.
+const BUCKET_COLLISION: u8 = 254;
+/// Blocks with more restart points than this do not get a hash index.
+pub(crate) const MAX_RESTARTS_FOR_HASH_INDEX: usize = BUCKET_COLLISION as usize;
+
+pub(crate) enum HashIndexLookup {
+    /// The key, if present, starts in this restart interval.
+    Restart(usize),
+    /// The key is not in the block.
+    NotFound,
+    /// The index cannot tell, fall back to binary search.
+    Unknown,
+}
+
+pub(crate) fn hash_key(key: &[u8]) -> u32 {
+    farmhash::fingerprint32(key)
+}
+
+/// Number of buckets for `num_keys` user keys, targeting a load factor of 0.75.
+pub(crate) fn num_buckets(num_keys: usize) -> usize {
+    num_keys * 4 / 3 + 1
+}
+
+/// Build the buckets from `(key hash, restart index)` pairs of distinct user keys.
+pub(crate) fn build(entries: &[(u32, u8)]) -> Vec<u8> {
+    let num_buckets = num_buckets(entries.len());
+    let mut buckets = vec![BUCKET_EMPTY; num_buckets];
+    for (hash, restart_idx) in entries {
+        let bucket = &mut buckets[*hash as usize % num_buckets];
+        if *bucket == BUCKET_EMPTY || *bucket == *restart_idx {
+            *bucket = *restart_idx;
+        } else {
+            *bucket = BUCKET_COLLISION;
+        }
+    }
+    buckets
+}
+
+pub(crate) fn lookup(buckets: &[u8], key: &[u8]) -> HashIndexLookup {
+    match buckets[hash_key(key) as usize % buckets.len()] {
+        BUCKET_EMPTY => HashIndexLookup::NotFound,
+        BUCKET_COLLISION => HashIndexLookup::Unknown,
+        restart_idx => HashIndexLookup::Restart(restart_idx as usize),
+    }
+}
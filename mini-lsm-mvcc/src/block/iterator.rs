@@ -17,11 +17,12 @@ use std::sync::Arc;
 use bytes::Buf;
 
 use crate::{
-    block::SIZEOF_U16,
     key::{KeySlice, KeyVec},
+    varint::{get_varint, zigzag_decode},
 };
 
-use super::Block;
+use super::hash_index::{self, HashIndexLookup};
+use super::{Block, BlockLayout};
 
 /// Iterates on a block.
 pub struct BlockIterator {
@@ -29,33 +30,22 @@ pub struct BlockIterator {
     block: Arc<Block>,
     /// the current key at the iterator position
     key: KeyVec,
+    /// the offset of the current entry in the block.data
+    offset: usize,
     /// the current value range in the block.data, corresponds to the current key
     value_range: (usize, usize),
-    /// the current index at the iterator position
-    idx: usize,
-    /// the first key in the block
-    first_key: KeyVec,
-}
-
-impl Block {
-    fn get_first_key(&self) -> KeyVec {
-        let mut buf = &self.data[..];
-        buf.get_u16();
-        let key_len = buf.get_u16() as usize;
-        let key = &buf[..key_len];
-        buf.advance(key_len);
-        KeyVec::from_vec_with_ts(key.to_vec(), buf.get_u64())
-    }
+    /// the restart interval that the current entry belongs to
+    restart_idx: usize,
 }
 
 impl BlockIterator {
     fn new(block: Arc<Block>) -> Self {
         Self {
-            first_key: block.get_first_key(),
             block,
             key: KeyVec::new(),
+            offset: 0,
             value_range: (0, 0),
-            idx: 0,
+            restart_idx: 0,
         }
     }
 
@@ -73,6 +63,29 @@ impl BlockIterator {
         iter
     }
 
+    /// Creates a block iterator and seek to the last entry.
+    pub fn create_and_seek_to_last(block: Arc<Block>) -> Self {
+        let mut iter = Self::new(block);
+        iter.seek_to_last();
+        iter
+    }
+
+    /// Creates a block iterator and seek to the last key that <= `key`.
+    pub fn create_and_seek_for_prev(block: Arc<Block>, key: KeySlice) -> Self {
+        let mut iter = Self::new(block);
+        iter.seek_for_prev(key);
+        iter
+    }
+
+    /// Creates a block iterator for a point lookup of `key`, using the hash index of the block if
+    /// there is one. Unlike `create_and_seek_to_key`, if the user key is not in the block, the
+    /// iterator is either invalid or positioned at some larger key.
+    pub fn create_and_seek_for_get(block: Arc<Block>, key: KeySlice) -> Self {
+        let mut iter = Self::new(block);
+        iter.seek_for_get(key);
+        iter
+    }
+
     /// Returns the key of the current entry.
     pub fn key(&self) -> KeySlice {
         debug_assert!(!self.key.is_empty(), "invalid iterator");
@@ -92,58 +105,127 @@ impl BlockIterator {
 
     /// Seeks to the first key in the block.
     pub fn seek_to_first(&mut self) {
-        self.seek_to(0);
+        self.seek_to_restart(0);
     }
 
-    /// Seeks to the idx-th key in the block.
-    fn seek_to(&mut self, idx: usize) {
-        if idx >= self.block.offsets.len() {
+    /// Seeks to the last key in the block.
+    pub fn seek_to_last(&mut self) {
+        self.seek_to_restart(self.block.num_restarts() - 1);
+        while self.value_range.1 < self.block.data.len() {
+            self.next();
+        }
+    }
+
+    /// Seeks to the first entry of the idx-th restart interval.
+    fn seek_to_restart(&mut self, idx: usize) {
+        if idx >= self.block.num_restarts() {
             self.key.clear();
             self.value_range = (0, 0);
             return;
         }
-        let offset = self.block.offsets[idx] as usize;
+        let offset = self.block.restart(idx);
+        // The entry at a restart point stores the full key.
+        self.key.clear();
         self.seek_to_offset(offset);
-        self.idx = idx;
+        self.restart_idx = idx;
     }
 
     /// Move to the next key in the block.
     pub fn next(&mut self) {
-        self.idx += 1;
-        self.seek_to(self.idx);
+        let offset = self.value_range.1;
+        if offset >= self.block.data.len() {
+            self.key.clear();
+            self.value_range = (0, 0);
+            return;
+        }
+        if self.restart_idx + 1 < self.block.num_restarts()
+            && self.block.restart(self.restart_idx + 1) == offset
+        {
+            self.restart_idx += 1;
+        }
+        self.seek_to_offset(offset);
     }
 
-    /// Seek to the specified position and update the current `key` and `value`
-    /// Index update will be handled by caller
+    /// Move to the previous key in the block. The iterator becomes invalid if it is at the first
+    /// key.
+    pub fn prev(&mut self) {
+        debug_assert!(self.is_valid(), "invalid iterator");
+        let offset = self.offset;
+        // Entries only store the suffix of the key, so scan forward from the restart point of the
+        // interval holding the previous entry.
+        let restart_idx = if self.block.restart(self.restart_idx) == offset {
+            if self.restart_idx == 0 {
+                self.key.clear();
+                self.value_range = (0, 0);
+                return;
+            }
+            self.restart_idx - 1
+        } else {
+            self.restart_idx
+        };
+        self.seek_to_restart(restart_idx);
+        while self.value_range.1 < offset {
+            self.next();
+        }
+    }
+
+    /// Seek to the specified position and update the current `key` and `value`. The key is
+    /// decoded against the current key, so the caller must either be positioned on the
+    /// previous entry or clear the key before seeking to a restart point.
     fn seek_to_offset(&mut self, offset: usize) {
+        self.offset = offset;
         let mut entry = &self.block.data[offset..];
-        // Since `get_u16()` will automatically move the ptr 2 bytes ahead here,
-        // we don't need to manually advance it
-        let overlap_len = entry.get_u16() as usize;
-        let key_len = entry.get_u16() as usize;
-        let key = &entry[..key_len];
-        self.key.clear();
-        self.key.append(&self.first_key.key_ref()[..overlap_len]);
-        self.key.append(key);
-        entry.advance(key_len);
-        let ts = entry.get_u64();
-        self.key.set_ts(ts);
-        let value_len = entry.get_u16() as usize;
-        // REMEMBER TO CHANGE THIS every time you change the encoding!
-        let value_offset_begin =
-            offset + SIZEOF_U16 + SIZEOF_U16 + std::mem::size_of::<u64>() + key_len + SIZEOF_U16;
+        // `get_varint()` advances the slice past what they read, so the value
+        // offset is derived from how much of the entry is left.
+        let (shared_len, unshared_len) = match self.block.layout {
+            BlockLayout::Variable => (
+                get_varint(&mut entry) as usize,
+                get_varint(&mut entry) as usize,
+            ),
+            BlockLayout::FixedKey(key_len) => (0, key_len),
+        };
+        self.key.truncate(shared_len);
+        self.key.append(&entry[..unshared_len]);
+        entry.advance(unshared_len);
+        let ts_delta = zigzag_decode(get_varint(&mut entry));
+        self.key
+            .set_ts(self.block.base_ts.wrapping_add(ts_delta as u64));
+        let value_len = get_varint(&mut entry) as usize;
+        let value_offset_begin = self.block.data.len() - entry.len();
         let value_offset_end = value_offset_begin + value_len;
         self.value_range = (value_offset_begin, value_offset_end);
-        entry.advance(value_len);
+    }
+
+    /// Seek to `key` for a point lookup. See `create_and_seek_for_get`.
+    pub fn seek_for_get(&mut self, key: KeySlice) {
+        let lookup = match &self.block.hash_index {
+            Some(buckets) => hash_index::lookup(buckets, key.key_ref()),
+            None => HashIndexLookup::Unknown,
+        };
+        match lookup {
+            HashIndexLookup::Restart(restart_idx) => {
+                self.seek_to_restart(restart_idx);
+                while self.is_valid() && self.key() < key {
+                    self.next();
+                }
+            }
+            HashIndexLookup::NotFound => {
+                self.key.clear();
+                self.value_range = (0, 0);
+            }
+            HashIndexLookup::Unknown => self.seek_to_key(key),
+        }
     }
 
     /// Seek to the first key that is >= `key`.
     pub fn seek_to_key(&mut self, key: KeySlice) {
+        // Find the first restart point whose key is >= `key`. The target is either in the
+        // previous restart interval or is that restart point itself.
         let mut low = 0;
-        let mut high = self.block.offsets.len();
+        let mut high = self.block.num_restarts();
         while low < high {
             let mid = low + (high - low) / 2;
-            self.seek_to(mid);
+            self.seek_to_restart(mid);
             assert!(self.is_valid());
             match self.key().cmp(&key) {
                 std::cmp::Ordering::Less => low = mid + 1,
@@ -151,6 +233,19 @@ impl BlockIterator {
                 std::cmp::Ordering::Equal => return,
             }
         }
-        self.seek_to(low);
+        self.seek_to_restart(low.saturating_sub(1));
+        while self.is_valid() && self.key() < key {
+            self.next();
+        }
+    }
+
+    /// Seek to the last key that is <= `key`. The iterator is invalid if all keys are larger.
+    pub fn seek_for_prev(&mut self, key: KeySlice) {
+        self.seek_to_key(key);
+        if !self.is_valid() {
+            self.seek_to_last();
+        } else if self.key() > key {
+            self.prev();
+        }
     }
 }
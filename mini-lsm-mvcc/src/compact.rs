@@ -35,9 +35,10 @@ use crate::iterators::two_merge_iterator::TwoMergeIterator;
 use crate::key::KeySlice;
 use crate::lsm_storage::{CompactionFilter, LsmStorageInner, LsmStorageState};
 use crate::manifest::ManifestRecord;
+use crate::range_tombstone::RangeTombstone;
 use crate::table::{SsTable, SsTableBuilder, SsTableIterator};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CompactionTask {
     Leveled(LeveledCompactionTask),
     Tiered(TieredCompactionTask),
@@ -57,6 +58,17 @@ impl CompactionTask {
             CompactionTask::Tiered(task) => task.bottom_tier_included,
         }
     }
+
+    /// The level the output SSTs are written to. Tiers are not numbered as levels, so tiered
+    /// compaction writes to level 0.
+    fn output_level(&self) -> usize {
+        match self {
+            CompactionTask::ForceFullCompaction { .. } => 1,
+            CompactionTask::Leveled(task) => task.lower_level,
+            CompactionTask::Simple(task) => task.lower_level,
+            CompactionTask::Tiered(_) => 0,
+        }
+    }
 }
 
 pub(crate) enum CompactionController {
@@ -105,6 +117,16 @@ impl CompactionController {
 }
 
 impl CompactionController {
+    /// Estimate the bytes the compactions triggered by the current state would rewrite.
+    pub fn estimate_pending_compaction_bytes(&self, snapshot: &LsmStorageState) -> u64 {
+        match self {
+            CompactionController::Leveled(ctrl) => ctrl.estimate_pending_compaction_bytes(snapshot),
+            CompactionController::Simple(ctrl) => ctrl.estimate_pending_compaction_bytes(snapshot),
+            CompactionController::Tiered(ctrl) => ctrl.estimate_pending_compaction_bytes(snapshot),
+            CompactionController::NoCompaction => 0,
+        }
+    }
+
     pub fn flush_to_l0(&self) -> bool {
         matches!(
             self,
@@ -126,32 +148,85 @@ pub enum CompactionOptions {
     NoCompaction,
 }
 
+/// Check whether the version at `key` is deleted by one of `tombstones`.
+fn is_range_deleted(tombstones: &[RangeTombstone], key: KeySlice) -> bool {
+    tombstones
+        .iter()
+        .any(|tombstone| tombstone.covers(key.key_ref(), key.ts()))
+}
+
+/// Add the part of `tombstones` in `[lower, upper)` to `builder`.
+fn add_clipped_range_tombstones(
+    builder: &mut SsTableBuilder,
+    tombstones: &[RangeTombstone],
+    lower: Option<&[u8]>,
+    upper: Option<&[u8]>,
+) {
+    for tombstone in tombstones {
+        if let Some(tombstone) = tombstone.clip(lower, upper) {
+            builder.add_range_tombstone(tombstone);
+        }
+    }
+}
+
 impl LsmStorageInner {
+    /// Collect the range tombstones of the given SSTs.
+    fn range_tombstones_of<'a>(
+        snapshot: &LsmStorageState,
+        sst_ids: impl IntoIterator<Item = &'a usize>,
+    ) -> Vec<RangeTombstone> {
+        sst_ids
+            .into_iter()
+            .flat_map(|id| snapshot.sstables[id].range_tombstones().iter().cloned())
+            .collect()
+    }
+
+    fn new_compaction_sst_builder(&self, task: &CompactionTask) -> SsTableBuilder {
+        self.new_sst_builder(task.output_level(), task.compact_to_bottom_level())
+            .with_compaction_task(task.clone())
+    }
+
     fn compact_generate_sst_from_iter(
         &self,
         mut iter: impl for<'a> StorageIterator<KeyType<'a> = KeySlice<'a>>,
-        compact_to_bottom_level: bool,
+        range_tombstones: Vec<RangeTombstone>,
+        task: &CompactionTask,
     ) -> Result<Vec<Arc<SsTable>>> {
+        let compact_to_bottom_level = task.compact_to_bottom_level();
         let mut builder = None;
         let mut new_sst = Vec::new();
         let watermark = self.mvcc().watermark();
         let mut last_key = Vec::<u8>::new();
         let mut first_key_below_watermark = false;
         let compaction_filters = self.compaction_filters.lock().clone();
-        'outer: while iter.is_valid() {
-            if builder.is_none() {
-                builder = Some(SsTableBuilder::new(self.options.block_size));
+        // Versions deleted by a tombstone below the watermark are invisible to all readers. The
+        // tombstones themselves are only dropped at the bottom level, where nothing older is left
+        // for them to delete.
+        let mut expired_tombstones = Vec::new();
+        let mut kept_tombstones = Vec::new();
+        for tombstone in range_tombstones {
+            if tombstone.ts <= watermark {
+                expired_tombstones.push(tombstone.clone());
+                if compact_to_bottom_level {
+                    continue;
+                }
             }
-
+            kept_tombstones.push(tombstone);
+        }
+        // Each output SST gets the part of the range tombstones between its first key and the
+        // first key of the next SST, so that the SSTs do not overlap.
+        let mut sst_lower_key = None::<Vec<u8>>;
+        'outer: while iter.is_valid() {
             let same_as_last_key = iter.key().key_ref() == last_key;
             if !same_as_last_key {
                 first_key_below_watermark = true;
             }
 
-            if compact_to_bottom_level
-                && !same_as_last_key
-                && iter.key().ts() <= watermark
-                && iter.value().is_empty()
+            if is_range_deleted(&expired_tombstones, iter.key())
+                || (compact_to_bottom_level
+                    && !same_as_last_key
+                    && iter.key().ts() <= watermark
+                    && iter.value().is_empty())
             {
                 last_key.clear();
                 last_key.extend(iter.key().key_ref());
@@ -182,18 +257,29 @@ impl LsmStorageInner {
                 }
             }
 
+            // Create the builder lazily, so that no SST is built if all keys are dropped.
+            if builder.is_none() {
+                builder = Some(self.new_compaction_sst_builder(task));
+            }
             let builder_inner = builder.as_mut().unwrap();
 
             if builder_inner.estimated_size() >= self.options.target_sst_size && !same_as_last_key {
                 let sst_id = self.next_sst_id();
-                let old_builder = builder.take().unwrap();
+                let mut old_builder = builder.take().unwrap();
+                add_clipped_range_tombstones(
+                    &mut old_builder,
+                    &kept_tombstones,
+                    sst_lower_key.as_deref(),
+                    Some(iter.key().key_ref()),
+                );
+                sst_lower_key = Some(iter.key().key_ref().to_vec());
                 let sst = Arc::new(old_builder.build(
                     sst_id,
                     Some(self.block_cache.clone()),
                     self.path_of_sst(sst_id),
                 )?);
                 new_sst.push(sst);
-                builder = Some(SsTableBuilder::new(self.options.block_size));
+                builder = Some(self.new_compaction_sst_builder(task));
             }
 
             let builder_inner = builder.as_mut().unwrap();
@@ -206,7 +292,16 @@ impl LsmStorageInner {
 
             iter.next()?;
         }
-        if let Some(builder) = builder {
+        if builder.is_none() && !kept_tombstones.is_empty() {
+            builder = Some(self.new_compaction_sst_builder(task));
+        }
+        if let Some(mut builder) = builder {
+            add_clipped_range_tombstones(
+                &mut builder,
+                &kept_tombstones,
+                sst_lower_key.as_deref(),
+                None,
+            );
             let sst_id = self.next_sst_id(); // lock dropped here
             let sst = Arc::new(builder.build(
                 sst_id,
@@ -242,7 +337,11 @@ impl LsmStorageInner {
                     MergeIterator::create(l0_iters),
                     SstConcatIterator::create_and_seek_to_first(l1_iters)?,
                 )?;
-                self.compact_generate_sst_from_iter(iter, task.compact_to_bottom_level())
+                self.compact_generate_sst_from_iter(
+                    iter,
+                    Self::range_tombstones_of(&snapshot, l0_sstables.iter().chain(l1_sstables)),
+                    task,
+                )
             }
             CompactionTask::Simple(SimpleLeveledCompactionTask {
                 upper_level,
@@ -271,7 +370,11 @@ impl LsmStorageInner {
                     let lower_iter = SstConcatIterator::create_and_seek_to_first(lower_ssts)?;
                     self.compact_generate_sst_from_iter(
                         TwoMergeIterator::create(upper_iter, lower_iter)?,
-                        task.compact_to_bottom_level(),
+                        Self::range_tombstones_of(
+                            &snapshot,
+                            upper_level_sst_ids.iter().chain(lower_level_sst_ids),
+                        ),
+                        task,
                     )
                 }
                 None => {
@@ -289,7 +392,11 @@ impl LsmStorageInner {
                     let lower_iter = SstConcatIterator::create_and_seek_to_first(lower_ssts)?;
                     self.compact_generate_sst_from_iter(
                         TwoMergeIterator::create(upper_iter, lower_iter)?,
-                        task.compact_to_bottom_level(),
+                        Self::range_tombstones_of(
+                            &snapshot,
+                            upper_level_sst_ids.iter().chain(lower_level_sst_ids),
+                        ),
+                        task,
                     )
                 }
             },
@@ -304,7 +411,11 @@ impl LsmStorageInner {
                 }
                 self.compact_generate_sst_from_iter(
                     MergeIterator::create(iters),
-                    task.compact_to_bottom_level(),
+                    Self::range_tombstones_of(
+                        &snapshot,
+                        tiers.iter().flat_map(|(_, tier_sst_ids)| tier_sst_ids),
+                    ),
+                    task,
                 )
             }
         }
@@ -314,6 +425,7 @@ impl LsmStorageInner {
         let CompactionOptions::NoCompaction = self.options.compaction_options else {
             panic!("full compaction can only be called with compaction is not enabled")
         };
+        let _compaction_lock = self.compaction_lock.lock();
 
         let snapshot = {
             let state = self.state.read();
@@ -331,6 +443,7 @@ impl LsmStorageInner {
 
         let sstables = self.compact(&compaction_task)?;
         let mut ids = Vec::with_capacity(sstables.len());
+        let mut ssts_to_remove = Vec::with_capacity(l0_sstables.len() + l1_sstables.len());
 
         {
             let state_lock = self.state_lock.lock();
@@ -338,6 +451,7 @@ impl LsmStorageInner {
             for sst in l0_sstables.iter().chain(l1_sstables.iter()) {
                 let result = state.sstables.remove(sst);
                 assert!(result.is_some());
+                ssts_to_remove.push(result.unwrap());
             }
             for new_sst in sstables {
                 ids.push(new_sst.sst_id());
@@ -355,14 +469,16 @@ impl LsmStorageInner {
                 .collect::<Vec<_>>();
             assert!(l0_sstables_map.is_empty());
             *self.state.write() = Arc::new(state);
+            self.notify_backlog_reduced();
             self.sync_dir()?;
             self.manifest.as_ref().unwrap().add_record(
                 &state_lock,
                 ManifestRecord::Compaction(compaction_task, ids.clone()),
             )?;
         }
-        for sst in l0_sstables.iter().chain(l1_sstables.iter()) {
-            std::fs::remove_file(self.path_of_sst(*sst))?;
+        for sst in ssts_to_remove {
+            sst.evict_cached_blocks();
+            std::fs::remove_file(self.path_of_sst(sst.sst_id()))?;
         }
 
         println!("force full compaction done, new SSTs: {:?}", ids);
@@ -371,6 +487,7 @@ impl LsmStorageInner {
     }
 
     fn trigger_compaction(&self) -> Result<()> {
+        let _compaction_lock = self.compaction_lock.lock();
         let snapshot = {
             let state = self.state.read();
             state.clone()
@@ -407,6 +524,7 @@ impl LsmStorageInner {
             let mut state = self.state.write();
             *state = Arc::new(snapshot);
             drop(state);
+            self.notify_backlog_reduced();
             self.sync_dir()?;
             self.manifest()
                 .add_record(&state_lock, ManifestRecord::Compaction(task, new_sst_ids))?;
@@ -419,6 +537,7 @@ impl LsmStorageInner {
             output
         );
         for sst in ssts_to_remove {
+            sst.evict_cached_blocks();
             std::fs::remove_file(self.path_of_sst(sst.sst_id()))?;
         }
         self.sync_dir()?;
@@ -452,10 +571,20 @@ impl LsmStorageInner {
     }
 
     fn trigger_flush(&self) -> Result<()> {
-        let res = {
-            let state = self.state.read();
-            state.imm_memtables.len() >= self.options.num_memtable_limit
-        };
+        let num_imm_memtables = self.state.read().imm_memtables.len();
+        // The manager reads the state of all storages, so it is not called under `self.state`.
+        // Writers held back by the immutable memtables wait for a flush.
+        let res = num_imm_memtables >= self.options.num_memtable_limit
+            || self
+                .options
+                .write_stall
+                .stalls_on_imm_memtables(num_imm_memtables)
+            || (num_imm_memtables > 0
+                && self
+                    .options
+                    .write_buffer_manager
+                    .as_ref()
+                    .is_some_and(|manager| manager.should_flush()));
         if res {
             self.force_flush_next_imm_memtable()?;
         }
@@ -25,7 +25,7 @@ pub struct SimpleLeveledCompactionOptions {
     pub max_levels: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SimpleLeveledCompactionTask {
     // if upper_level is `None`, then it is L0 compaction
     pub upper_level: Option<usize>,
@@ -44,6 +44,28 @@ impl SimpleLeveledCompactionController {
         Self { options }
     }
 
+    /// Estimate the bytes to compact: the upper and lower level of each pair of levels that
+    /// would trigger a compaction.
+    pub fn estimate_pending_compaction_bytes(&self, snapshot: &LsmStorageState) -> u64 {
+        let level_bytes = |ids: &[usize]| {
+            ids.iter()
+                .map(|x| snapshot.sstables[x].table_size())
+                .sum::<u64>()
+        };
+        let mut pending = 0;
+        if snapshot.l0_sstables.len() >= self.options.level0_file_num_compaction_trigger {
+            pending += level_bytes(&snapshot.l0_sstables) + level_bytes(&snapshot.levels[0].1);
+        }
+        for i in 1..self.options.max_levels {
+            let (upper, lower) = (&snapshot.levels[i - 1].1, &snapshot.levels[i].1);
+            let size_ratio = lower.len() as f64 / upper.len() as f64;
+            if size_ratio < self.options.size_ratio_percent as f64 / 100.0 {
+                pending += level_bytes(upper) + level_bytes(lower);
+            }
+        }
+        pending
+    }
+
     /// Generates a compaction task.
     ///
     /// Returns `None` if no compaction needs to be scheduled. The order of SSTs in the compaction task id vector matters.
@@ -18,7 +18,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::lsm_storage::LsmStorageState;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TieredCompactionTask {
     pub tiers: Vec<(usize, Vec<usize>)>,
     pub bottom_tier_included: bool,
@@ -42,6 +42,21 @@ impl TieredCompactionController {
         Self { options }
     }
 
+    /// Estimate the bytes to compact: the tiers a compaction reducing the number of sorted runs
+    /// would merge, once there are `num_tiers` tiers.
+    pub fn estimate_pending_compaction_bytes(&self, snapshot: &LsmStorageState) -> u64 {
+        if snapshot.levels.len() < self.options.num_tiers {
+            return 0;
+        }
+        snapshot
+            .levels
+            .iter()
+            .take(self.options.max_merge_width.unwrap_or(usize::MAX))
+            .flat_map(|(_, ids)| ids)
+            .map(|x| snapshot.sstables[x].table_size())
+            .sum()
+    }
+
     pub fn generate_compaction_task(
         &self,
         snapshot: &LsmStorageState,
@@ -0,0 +1,179 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::write_stall::WriteStallCause;
+
+/// A file that failed checksum verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorruptedFile {
+    Sst(usize),
+    Wal(usize),
+}
+
+/// The part of a file that failed checksum verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorruptedSection {
+    /// The data block with the given index.
+    DataBlock(usize),
+    BlockMeta,
+    /// The index partition with the given index.
+    IndexPartition(usize),
+    Bloom,
+    Footer,
+    WalBatch,
+    /// A write batch cut short at the end of the WAL. It has no checksum to compare, so both
+    /// checksums of the error are 0.
+    IncompleteWalBatch,
+}
+
+/// Returned, wrapped in `anyhow::Error`, when a checksum does not match. Use
+/// `err.downcast_ref::<CorruptionError>()` to inspect it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptionError {
+    pub file: CorruptedFile,
+    pub section: CorruptedSection,
+    /// Byte offset of the corrupted section in the file.
+    pub offset: u64,
+    pub expected_checksum: u32,
+    pub actual_checksum: u32,
+}
+
+impl fmt::Display for CorruptedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CorruptedFile::Sst(id) => write!(f, "SST {}", id),
+            CorruptedFile::Wal(id) => write!(f, "WAL {}", id),
+        }
+    }
+}
+
+impl fmt::Display for CorruptedSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CorruptedSection::DataBlock(idx) => write!(f, "data block {}", idx),
+            CorruptedSection::BlockMeta => write!(f, "block meta"),
+            CorruptedSection::IndexPartition(idx) => write!(f, "index partition {}", idx),
+            CorruptedSection::Bloom => write!(f, "bloom filter"),
+            CorruptedSection::Footer => write!(f, "footer"),
+            CorruptedSection::WalBatch => write!(f, "write batch"),
+            CorruptedSection::IncompleteWalBatch => write!(f, "incomplete write batch"),
+        }
+    }
+}
+
+impl fmt::Display for CorruptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.section == CorruptedSection::IncompleteWalBatch {
+            return write!(
+                f,
+                "corruption in {} {} at offset {}",
+                self.file, self.section, self.offset
+            );
+        }
+        write!(
+            f,
+            "corruption in {} {} at offset {}: expected checksum {:#010x}, got {:#010x}",
+            self.file, self.section, self.offset, self.expected_checksum, self.actual_checksum
+        )
+    }
+}
+
+impl std::error::Error for CorruptionError {}
+
+impl CorruptionError {
+    /// Check that `data` hashes to `expected_checksum`.
+    pub(crate) fn check(
+        data: &[u8],
+        expected_checksum: u32,
+        file: CorruptedFile,
+        section: CorruptedSection,
+        offset: u64,
+    ) -> Result<(), Self> {
+        let actual_checksum = crc32fast::hash(data);
+        if actual_checksum == expected_checksum {
+            Ok(())
+        } else {
+            Err(Self {
+                file,
+                section,
+                offset,
+                expected_checksum,
+                actual_checksum,
+            })
+        }
+    }
+
+    /// The write batch at `offset` in the WAL with the given id is incomplete.
+    pub(crate) fn incomplete_wal_batch(id: usize, offset: u64) -> Self {
+        Self {
+            file: CorruptedFile::Wal(id),
+            section: CorruptedSection::IncompleteWalBatch,
+            offset,
+            expected_checksum: 0,
+            actual_checksum: 0,
+        }
+    }
+}
+
+/// Returned, wrapped in `anyhow::Error`, when a write waited longer than
+/// `WriteStallOptions::stop_timeout` while writes were stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteStallError {
+    pub cause: WriteStallCause,
+    pub timeout: Duration,
+}
+
+impl fmt::Display for WriteStallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "writes stopped for {:?} because of {}",
+            self.timeout, self.cause
+        )
+    }
+}
+
+impl std::error::Error for WriteStallError {}
+
+/// The error that failed a group of writes committed together, returned to every writer of the
+/// group. It formats as the original error and has the same sources.
+#[derive(Debug, Clone)]
+pub struct GroupCommitError(Arc<anyhow::Error>);
+
+impl GroupCommitError {
+    pub(crate) fn new(error: anyhow::Error) -> Self {
+        Self(Arc::new(error))
+    }
+
+    /// The original error, e.g. to downcast it.
+    pub fn error(&self) -> &anyhow::Error {
+        &self.0
+    }
+}
+
+impl fmt::Display for GroupCommitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for GroupCommitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
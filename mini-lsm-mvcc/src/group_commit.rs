@@ -0,0 +1,237 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use anyhow::Result;
+use bytes::Bytes;
+use parking_lot::{Condvar, Mutex};
+
+use crate::error::GroupCommitError;
+use crate::key::{KeySlice, TS_DEFAULT};
+use crate::lsm_storage::WriteBatchRecord;
+use crate::mem_table::MemTable;
+use crate::range_tombstone::RangeTombstone;
+
+/// The key-value pairs and range tombstones of `batch`, committed at `ts`. An empty value
+/// deletes the key.
+pub(crate) fn batch_entries<T: AsRef<[u8]>>(
+    batch: &[WriteBatchRecord<T>],
+    ts: u64,
+) -> (Vec<(KeySlice<'_>, &[u8])>, Vec<RangeTombstone>) {
+    let mut data = Vec::new();
+    let mut range_tombstones = Vec::new();
+    for record in batch {
+        match record {
+            WriteBatchRecord::Del(key) => {
+                let key = key.as_ref();
+                assert!(!key.is_empty(), "key cannot be empty");
+                data.push((KeySlice::from_slice(key, ts), &b""[..]));
+            }
+            WriteBatchRecord::Put(key, value) => {
+                let key = key.as_ref();
+                let value = value.as_ref();
+                assert!(!key.is_empty(), "key cannot be empty");
+                assert!(!value.is_empty(), "value cannot be empty");
+                data.push((KeySlice::from_slice(key, ts), value));
+            }
+            WriteBatchRecord::DelRange(start, end) => {
+                range_tombstones.push(RangeTombstone::new(start.as_ref(), end.as_ref(), ts));
+            }
+        }
+    }
+    (data, range_tombstones)
+}
+
+/// A write batch, copied so that the leader of its group can write it to the WAL. Only made
+/// when the writer has to wait for another leader.
+pub(crate) struct GroupBatch {
+    /// Keys and values. An empty value deletes the key.
+    data: Vec<(Bytes, Bytes)>,
+    /// Start and end of the range deletions.
+    range_deletes: Vec<(Bytes, Bytes)>,
+}
+
+impl GroupBatch {
+    pub(crate) fn new<T: AsRef<[u8]>>(batch: &[WriteBatchRecord<T>]) -> Self {
+        let (data, range_tombstones) = batch_entries(batch, TS_DEFAULT);
+        Self {
+            data: data
+                .into_iter()
+                .map(|(key, value)| {
+                    (
+                        Bytes::copy_from_slice(key.key_ref()),
+                        Bytes::copy_from_slice(value),
+                    )
+                })
+                .collect(),
+            range_deletes: range_tombstones
+                .into_iter()
+                .map(|tombstone| (tombstone.start, tombstone.end))
+                .collect(),
+        }
+    }
+
+    /// The key-value pairs of the batch, committed at `ts`.
+    pub(crate) fn entries(&self, ts: u64) -> Vec<(KeySlice<'_>, &[u8])> {
+        self.data
+            .iter()
+            .map(|(key, value)| (KeySlice::from_slice(key, ts), &value[..]))
+            .collect()
+    }
+
+    /// The range tombstones of the batch, committed at `ts`.
+    pub(crate) fn range_tombstones(&self, ts: u64) -> Vec<RangeTombstone> {
+        self.range_deletes
+            .iter()
+            .map(|(start, end)| RangeTombstone {
+                start: start.clone(),
+                end: end.clone(),
+                ts,
+            })
+            .collect()
+    }
+}
+
+enum Stage {
+    /// Waiting for a leader to pick the batch.
+    Pending,
+    /// Picked to lead the next group.
+    Leader,
+    /// Written to the WAL of the memtable, to be inserted into it with the commit timestamp.
+    Insert(u64, Arc<MemTable>),
+    /// Inserted into the memtable, waiting for the leader to publish the commit timestamp.
+    Inserted,
+    /// Committed with the given timestamp, or failed with the error of the group.
+    Done(Result<u64, GroupCommitError>),
+}
+
+/// A writer in the group commit queue.
+pub(crate) struct GroupWriter {
+    pub(crate) batch: GroupBatch,
+    stage: Mutex<Stage>,
+    changed: Condvar,
+}
+
+impl GroupWriter {
+    pub(crate) fn new(batch: GroupBatch) -> Self {
+        Self {
+            batch,
+            stage: Mutex::new(Stage::Pending),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn set(&self, stage: Stage) {
+        *self.stage.lock() = stage;
+        self.changed.notify_all();
+    }
+
+    /// Hand the batch to its follower to insert it into `memtable` at `ts`. The insert must be
+    /// recorded with `MemTable::add_pending_inserts`.
+    pub(crate) fn insert(&self, ts: u64, memtable: Arc<MemTable>) {
+        self.set(Stage::Insert(ts, memtable));
+    }
+
+    /// Wait until the follower inserted its batch into the memtable.
+    pub(crate) fn wait_inserted(&self) {
+        let mut stage = self.stage.lock();
+        self.changed
+            .wait_while(&mut stage, |stage| !matches!(stage, Stage::Inserted));
+    }
+
+    /// Report the result of the commit to the follower.
+    pub(crate) fn finish(&self, result: Result<u64, GroupCommitError>) {
+        self.set(Stage::Done(result));
+    }
+
+    /// Wait as a follower, until the writer becomes the leader of a group, in which case this
+    /// returns `None`, or until its batch is committed by the leader of its group.
+    pub(crate) fn follow(&self) -> Option<Result<u64>> {
+        let mut stage = self.stage.lock();
+        loop {
+            match std::mem::replace(&mut *stage, Stage::Pending) {
+                Stage::Pending => {
+                    self.changed.wait(&mut stage);
+                }
+                Stage::Leader => return None,
+                Stage::Insert(ts, memtable) => {
+                    drop(stage);
+                    memtable.insert_pending_batch(
+                        &self.batch.entries(ts),
+                        &self.batch.range_tombstones(ts),
+                    );
+                    stage = self.stage.lock();
+                    *stage = Stage::Inserted;
+                    self.changed.notify_all();
+                    self.changed
+                        .wait_while(&mut stage, |stage| matches!(stage, Stage::Inserted));
+                }
+                Stage::Inserted => unreachable!(),
+                Stage::Done(result) => return Some(result.map_err(Into::into)),
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    /// Writers waiting for the current leader to finish.
+    pending: VecDeque<Arc<GroupWriter>>,
+    has_leader: bool,
+}
+
+/// A leader/follower queue for writers. The leader of a group writes the batches of all writers
+/// waiting in the queue to the WAL at once, then the writers insert their batch into the
+/// memtable in parallel.
+#[derive(Default)]
+pub(crate) struct GroupCommitQueue {
+    queue: Mutex<Queue>,
+}
+
+impl GroupCommitQueue {
+    /// Lead the next group if no writer is committing, without joining the queue.
+    pub(crate) fn try_lead(&self) -> bool {
+        let mut queue = self.queue.lock();
+        !std::mem::replace(&mut queue.has_leader, true)
+    }
+
+    /// Add a writer to the queue. Returns whether the writer leads the next group; otherwise it
+    /// waits for a leader with `GroupWriter::follow`.
+    pub(crate) fn join(&self, writer: &Arc<GroupWriter>) -> bool {
+        let mut queue = self.queue.lock();
+        if queue.has_leader {
+            queue.pending.push_back(writer.clone());
+            false
+        } else {
+            queue.has_leader = true;
+            true
+        }
+    }
+
+    /// Take the writers waiting in the queue as the followers of the leader's group.
+    pub(crate) fn take_followers(&self) -> Vec<Arc<GroupWriter>> {
+        self.queue.lock().pending.drain(..).collect()
+    }
+
+    /// Called by the leader once its group is committed, to hand over to the next leader.
+    pub(crate) fn finish_group(&self) {
+        let mut queue = self.queue.lock();
+        match queue.pending.pop_front() {
+            Some(next) => next.set(Stage::Leader),
+            None => queue.has_leader = false,
+        }
+    }
+}
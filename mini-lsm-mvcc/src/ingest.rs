@@ -0,0 +1,326 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+
+use crate::iterators::StorageIterator;
+use crate::key::{KeySlice, TS_DEFAULT, TS_RANGE_BEGIN};
+use crate::lsm_storage::{LsmStorageInner, LsmStorageOptions, LsmStorageState};
+use crate::manifest::ManifestRecord;
+use crate::mvcc::CommittedTxnData;
+use crate::range_tombstone::RangeTombstone;
+use crate::table::{FileObject, SsTable, SsTableBuilder, SsTableIterator};
+
+/// Writes sorted key-value pairs to an SST file outside of the storage, which can then be added
+/// to it with `MiniLsm::ingest_external_files`.
+pub struct SstFileWriter {
+    builder: SsTableBuilder,
+    path: PathBuf,
+    options: LsmStorageOptions,
+    last_key: Vec<u8>,
+    num_entries: usize,
+}
+
+impl SstFileWriter {
+    /// Create a writer for an SST at `path`, using the block options of the storage.
+    pub fn create(path: impl AsRef<Path>, options: &LsmStorageOptions) -> Self {
+        Self {
+            builder: SsTableBuilder::new(options.block_size).with_compression(options.compression),
+            path: path.as_ref().to_path_buf(),
+            options: options.clone(),
+            last_key: Vec::new(),
+            num_entries: 0,
+        }
+    }
+
+    /// Add a key-value pair. Keys must be added in strictly increasing order.
+    pub fn put(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        if value.is_empty() {
+            bail!("value cannot be empty");
+        }
+        self.add(key, value)
+    }
+
+    /// Add a deletion of `key`, which hides the versions of the key already in the storage.
+    pub fn delete(&mut self, key: &[u8]) -> Result<()> {
+        self.add(key, b"")
+    }
+
+    fn add(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        if key.is_empty() {
+            bail!("key cannot be empty");
+        }
+        if self.num_entries > 0 && key <= self.last_key.as_slice() {
+            bail!("keys must be added in strictly increasing order");
+        }
+        self.options.check_entry(key, value)?;
+        self.builder
+            .add(KeySlice::from_slice(key, TS_DEFAULT), value);
+        self.last_key.clear();
+        self.last_key.extend(key);
+        self.num_entries += 1;
+        Ok(())
+    }
+
+    /// Write the SST file.
+    pub fn finish(self) -> Result<()> {
+        if self.num_entries == 0 {
+            bail!("cannot write an SST without keys");
+        }
+        self.builder.build(0, None, &self.path)?;
+        Ok(())
+    }
+}
+
+impl LsmStorageState {
+    /// Add an ingested SST to `level`, without sorting the level. With tiered compaction, the
+    /// level is the id of the tier, which is created on the first SST.
+    pub(crate) fn add_ingested_sst(&mut self, level: usize, sst_id: usize, tiered: bool) {
+        if tiered {
+            if self.levels.first().map(|(tier_id, _)| *tier_id) != Some(level) {
+                self.levels.insert(0, (level, Vec::new()));
+            }
+            self.levels[0].1.push(sst_id);
+        } else if level == 0 {
+            self.l0_sstables.insert(0, sst_id);
+        } else {
+            self.levels[level - 1].1.push(sst_id);
+        }
+    }
+
+    /// The lowest level an SST with the given key range can be added to without overlapping the
+    /// SSTs of that level or of any level above it. Returns 0 for L0.
+    pub(crate) fn ingestion_level(&self, first_key: &[u8], last_key: &[u8]) -> usize {
+        let overlaps = |ids: &[usize]| {
+            ids.iter().any(|id| {
+                let table = &self.sstables[id];
+                table.first_key().key_ref() <= last_key && first_key <= table.last_key().key_ref()
+            })
+        };
+        if overlaps(&self.l0_sstables) {
+            return 0;
+        }
+        let mut level = 0;
+        for (idx, (_, ids)) in self.levels.iter().enumerate() {
+            if overlaps(ids) {
+                break;
+            }
+            level = idx + 1;
+        }
+        level
+    }
+
+    /// Whether SSTs at `level` are in the last level, where the filter may be disabled.
+    fn is_last_level(&self, level: usize) -> bool {
+        level > 0 && level == self.levels.len()
+    }
+
+    /// Whether the active or an immutable memtable overlaps `[first_key, last_key]`.
+    fn memtables_overlap(&self, first_key: &[u8], last_key: &[u8]) -> bool {
+        std::iter::once(&self.memtable)
+            .chain(&self.imm_memtables)
+            .any(|memtable| memtable.overlaps(first_key, last_key))
+    }
+}
+
+impl LsmStorageInner {
+    /// Add SST files written by `SstFileWriter` to the storage, committing all of their keys at a
+    /// single new timestamp. Each file is copied into the storage and placed at the lowest level
+    /// it does not overlap, or in L0, after the memtables that overlap the files are flushed. The
+    /// copies are written before the timestamp is assigned, so writes only wait for the SSTs to
+    /// be added to the state. The files must not overlap each other, and are left in place.
+    pub fn ingest_external_files(&self, paths: &[impl AsRef<Path>]) -> Result<()> {
+        let mut files = paths
+            .iter()
+            .map(|path| {
+                let path = path.as_ref();
+                let file = FileObject::open(path)
+                    .with_context(|| format!("failed to open {}", path.display()))?;
+                Ok(Arc::new(SsTable::open(0, None, file)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if files.is_empty() {
+            return Ok(());
+        }
+        files.sort_by(|x, y| x.first_key().key_ref().cmp(y.first_key().key_ref()));
+        for pair in files.windows(2) {
+            if pair[0].last_key().key_ref() >= pair[1].first_key().key_ref() {
+                bail!("external files overlap each other");
+            }
+        }
+        let first_key = files[0].first_key().key_ref();
+        let last_key = files[files.len() - 1].last_key().key_ref();
+
+        // The builder options depend on the level, so each file is copied for the level it fits
+        // in now. Copying and flushing happen without the locks, and are redone if the state
+        // changed before the locks were taken.
+        let tiered = !self.compaction_controller.flush_to_l0();
+        let mut rewritten: Vec<Option<(usize, bool, SsTable)>> =
+            files.iter().map(|_| None).collect();
+        loop {
+            {
+                let snapshot = self.state.read().clone();
+                for (file, slot) in files.iter().zip(&mut rewritten) {
+                    if let Some((level, is_last_level, _)) = slot
+                        && Self::fits_ingestion_level(
+                            &snapshot,
+                            file,
+                            *level,
+                            *is_last_level,
+                            tiered,
+                        )
+                    {
+                        continue;
+                    }
+                    if let Some((_, _, sst)) = slot.take() {
+                        std::fs::remove_file(self.path_of_sst(sst.sst_id()))?;
+                    }
+                    let level = if tiered {
+                        0
+                    } else {
+                        snapshot
+                            .ingestion_level(file.first_key().key_ref(), file.last_key().key_ref())
+                    };
+                    let is_last_level = !tiered && snapshot.is_last_level(level);
+                    let builder = self.new_sst_builder(level, is_last_level);
+                    let sst =
+                        self.rewrite_external_file(file.clone(), builder, self.next_sst_id())?;
+                    *slot = Some((level, is_last_level, sst));
+                }
+            }
+            self.sync_dir()?;
+            // Older versions of the keys must not stay in the memtables above the ingested SSTs,
+            // where compactions could drop deletions in the SSTs and bring the versions back.
+            self.flush_overlapping_memtables(first_key, last_key)?;
+
+            let _compaction_lock = self.compaction_lock.lock();
+            let _commit_lock = self
+                .options
+                .serializable
+                .then(|| self.mvcc().commit_lock.lock());
+            let _write_lock = self.mvcc().write_lock.lock();
+            let state_lock = self.state_lock.lock();
+            let mut snapshot = self.state.read().as_ref().clone();
+            // Keys written or SSTs added since the flush need another round.
+            if snapshot.memtables_overlap(first_key, last_key)
+                || files.iter().zip(&rewritten).any(|(file, slot)| {
+                    let (level, is_last_level, _) = slot.as_ref().unwrap();
+                    !Self::fits_ingestion_level(&snapshot, file, *level, *is_last_level, tiered)
+                })
+            {
+                continue;
+            }
+
+            let ts = self.mvcc().latest_commit_ts() + 1;
+            let tier_id = rewritten[0].as_ref().unwrap().2.sst_id();
+            let mut records = Vec::with_capacity(rewritten.len());
+            for (level, _, sst) in rewritten.into_iter().flatten() {
+                let level = if tiered { tier_id } else { level };
+                snapshot.add_ingested_sst(level, sst.sst_id(), tiered);
+                records.push((level, sst.sst_id()));
+                snapshot
+                    .sstables
+                    .insert(sst.sst_id(), Arc::new(sst.with_global_ts(ts)));
+            }
+            let sstables = &snapshot.sstables;
+            for (_, ssts) in &mut snapshot.levels {
+                ssts.sort_by(|x, y| sstables[x].first_key().cmp(sstables[y].first_key()));
+            }
+            *self.state.write() = Arc::new(snapshot);
+            self.manifest()
+                .add_record(&state_lock, ManifestRecord::Ingest(records, ts))?;
+            self.update_pending_compaction_bytes();
+            drop(state_lock);
+
+            if self.options.serializable {
+                self.mvcc().committed_txns.lock().insert(
+                    ts,
+                    CommittedTxnData {
+                        key_hashes: HashSet::new(),
+                        writes_untracked_keys: true,
+                        read_ts: ts - 1,
+                        commit_ts: ts,
+                    },
+                );
+            }
+            self.mvcc().update_commit_ts(ts);
+            return Ok(());
+        }
+    }
+
+    /// Whether `file` can still be added to `level`, the level it was copied for. Any level above
+    /// the lowest one the file fits in is free of overlaps too, but the copy must be rebuilt if
+    /// the level is no longer the last one, or has become it.
+    fn fits_ingestion_level(
+        snapshot: &LsmStorageState,
+        file: &SsTable,
+        level: usize,
+        is_last_level: bool,
+        tiered: bool,
+    ) -> bool {
+        tiered
+            || (level
+                <= snapshot.ingestion_level(file.first_key().key_ref(), file.last_key().key_ref())
+                && snapshot.is_last_level(level) == is_last_level)
+    }
+
+    /// Flush the memtables that overlap `[first_key, last_key]`, and the older ones.
+    fn flush_overlapping_memtables(&self, first_key: &[u8], last_key: &[u8]) -> Result<()> {
+        if self.state.read().memtable.overlaps(first_key, last_key) {
+            self.force_freeze_memtable(&self.state_lock.lock())?;
+        }
+        while self.state.read().memtables_overlap(first_key, last_key) {
+            self.force_flush_next_imm_memtable()?;
+        }
+        Ok(())
+    }
+
+    /// Copy an external SST into the storage. All keys and range tombstones are written with
+    /// `TS_RANGE_BEGIN`, and get their timestamp from `SsTable::with_global_ts`.
+    fn rewrite_external_file(
+        &self,
+        file: Arc<SsTable>,
+        mut builder: SsTableBuilder,
+        sst_id: usize,
+    ) -> Result<SsTable> {
+        for tombstone in file.range_tombstones() {
+            builder.add_range_tombstone(RangeTombstone {
+                ts: TS_RANGE_BEGIN,
+                ..tombstone.clone()
+            });
+        }
+        let mut iter = SsTableIterator::create_and_seek_to_first(file)?;
+        let mut last_key = Vec::new();
+        while iter.is_valid() {
+            let key = iter.key().key_ref();
+            if key.is_empty() || (!last_key.is_empty() && key <= last_key.as_slice()) {
+                bail!("external file has empty, duplicate or unsorted keys");
+            }
+            self.options.check_entry(key, iter.value())?;
+            builder.add(KeySlice::from_slice(key, TS_RANGE_BEGIN), iter.value());
+            last_key.clear();
+            last_key.extend(key);
+            iter.next()?;
+        }
+        builder.build(
+            sst_id,
+            Some(self.block_cache.clone()),
+            self.path_of_sst(sst_id),
+        )
+    }
+}
@@ -64,6 +64,19 @@ impl SstConcatIterator {
     }
 
     pub fn create_and_seek_to_key(sstables: Vec<Arc<SsTable>>, key: KeySlice) -> Result<Self> {
+        Self::create_and_seek_to_key_inner(sstables, key, SsTableIterator::create_and_seek_to_key)
+    }
+
+    /// Create an iterator for a point lookup of `key`. See `SsTableIterator::create_and_seek_for_get`.
+    pub fn create_and_seek_for_get(sstables: Vec<Arc<SsTable>>, key: KeySlice) -> Result<Self> {
+        Self::create_and_seek_to_key_inner(sstables, key, SsTableIterator::create_and_seek_for_get)
+    }
+
+    fn create_and_seek_to_key_inner(
+        sstables: Vec<Arc<SsTable>>,
+        key: KeySlice,
+        seek: impl FnOnce(Arc<SsTable>, KeySlice) -> Result<SsTableIterator>,
+    ) -> Result<Self> {
         Self::check_sst_valid(&sstables);
         let idx: usize = sstables
             .partition_point(|table| table.first_key().as_key_slice() <= key)
@@ -76,10 +89,7 @@ impl SstConcatIterator {
             });
         }
         let mut iter = Self {
-            current: Some(SsTableIterator::create_and_seek_to_key(
-                sstables[idx].clone(),
-                key,
-            )?),
+            current: Some(seek(sstables[idx].clone(), key)?),
             next_sst_idx: idx + 1,
             sstables,
         };
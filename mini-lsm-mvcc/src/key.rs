@@ -70,6 +70,11 @@ impl Key<Vec<u8>> {
         self.0.clear()
     }
 
+    /// Shortens the key to the first `len` bytes.
+    pub fn truncate(&mut self, len: usize) {
+        self.0.truncate(len)
+    }
+
     /// Append a slice to the end of the key
     pub fn append(&mut self, data: &[u8]) {
         self.0.extend(data)
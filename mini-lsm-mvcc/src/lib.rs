@@ -15,6 +15,9 @@
 pub mod block;
 pub mod compact;
 pub mod debug;
+pub mod error;
+pub(crate) mod group_commit;
+pub mod ingest;
 pub mod iterators;
 pub mod key;
 pub mod lsm_iterator;
@@ -22,8 +25,12 @@ pub mod lsm_storage;
 pub mod manifest;
 pub mod mem_table;
 pub mod mvcc;
+pub mod range_tombstone;
 pub mod table;
+pub(crate) mod varint;
 pub mod wal;
+pub mod write_buffer_manager;
+pub mod write_stall;
 
 #[cfg(test)]
 mod tests;
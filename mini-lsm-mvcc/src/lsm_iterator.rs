@@ -22,6 +22,7 @@ use crate::iterators::concat_iterator::SstConcatIterator;
 use crate::iterators::merge_iterator::MergeIterator;
 use crate::iterators::two_merge_iterator::TwoMergeIterator;
 use crate::mem_table::MemTableIterator;
+use crate::range_tombstone::RangeTombstone;
 use crate::table::SsTableIterator;
 
 /// Represents the internal type for an LSM iterator. This type will be changed across the course for multiple times.
@@ -36,6 +37,8 @@ pub struct LsmIterator {
     is_valid: bool,
     read_ts: u64,
     prev_key: Vec<u8>,
+    /// Range tombstones visible at `read_ts`.
+    range_tombstones: Vec<RangeTombstone>,
 }
 
 impl LsmIterator {
@@ -43,6 +46,7 @@ impl LsmIterator {
         iter: LsmIteratorInner,
         end_bound: Bound<Bytes>,
         read_ts: u64,
+        range_tombstones: Vec<RangeTombstone>,
     ) -> Result<Self> {
         let mut iter = Self {
             is_valid: iter.is_valid(),
@@ -50,6 +54,7 @@ impl LsmIterator {
             end_bound,
             read_ts,
             prev_key: Vec::new(),
+            range_tombstones,
         };
         iter.move_to_key()?;
         Ok(iter)
@@ -91,12 +96,20 @@ impl LsmIterator {
             if self.inner.key().key_ref() != self.prev_key {
                 continue;
             }
-            if !self.inner.value().is_empty() {
+            if !self.inner.value().is_empty() && !self.is_range_deleted() {
                 break;
             }
         }
         Ok(())
     }
+
+    /// Check whether the current version is deleted by a range tombstone.
+    fn is_range_deleted(&self) -> bool {
+        let key = self.inner.key();
+        self.range_tombstones
+            .iter()
+            .any(|tombstone| tombstone.covers(key.key_ref(), key.ts()))
+    }
 }
 
 impl StorageIterator for LsmIterator {
@@ -17,17 +17,20 @@ use std::fs::File;
 use std::ops::Bound;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicU64, AtomicUsize};
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use bytes::Bytes;
-use parking_lot::{Mutex, MutexGuard, RwLock};
+use parking_lot::{Condvar, Mutex, MutexGuard, RwLock};
 
-use crate::block::Block;
+use crate::block::{BlockLayout, DEFAULT_RESTART_INTERVAL};
 use crate::compact::{
     CompactionController, CompactionOptions, LeveledCompactionController, LeveledCompactionOptions,
     SimpleLeveledCompactionController, SimpleLeveledCompactionOptions, TieredCompactionController,
 };
+use crate::error::{CorruptionError, GroupCommitError, WriteStallError};
+use crate::group_commit::{GroupBatch, GroupCommitQueue, GroupWriter, batch_entries};
 use crate::iterators::StorageIterator;
 use crate::iterators::concat_iterator::SstConcatIterator;
 use crate::iterators::merge_iterator::MergeIterator;
@@ -35,12 +38,24 @@ use crate::iterators::two_merge_iterator::TwoMergeIterator;
 use crate::key::{self, KeySlice};
 use crate::lsm_iterator::{FusedIterator, LsmIterator};
 use crate::manifest::{Manifest, ManifestRecord};
-use crate::mem_table::{MemTable, map_bound, map_key_bound_plus_ts};
+use crate::mem_table::{MemTable, MemTableRepType, map_bound, map_key_bound_plus_ts};
 use crate::mvcc::LsmMvccInner;
 use crate::mvcc::txn::{Transaction, TxnIterator};
+use crate::range_tombstone::RangeTombstone;
+use crate::table::cache::{self, CachedBlock, IndexAndFilterMemory};
+use crate::table::compression::CompressionType;
+use crate::table::filter::FilterPolicy;
+use crate::table::prefix::PrefixExtractor;
 use crate::table::{FileObject, SsTable, SsTableBuilder, SsTableIterator};
+use crate::write_buffer_manager::WriteBufferManager;
+use crate::write_stall::{WriteStallOptions, WriteStallState};
 
-pub type BlockCache = moka::sync::Cache<(usize, usize), Arc<Block>>;
+pub type BlockCache = moka::sync::Cache<(usize, usize), CachedBlock>;
+
+/// The largest key accepted by the storage engine, in bytes.
+pub const MAX_KEY_SIZE: usize = 1 << 20;
+/// The largest value accepted by the storage engine, in bytes.
+pub const MAX_VALUE_SIZE: usize = 64 << 20;
 
 /// Represents the state of the storage engine.
 #[derive(Clone)]
@@ -61,6 +76,8 @@ pub struct LsmStorageState {
 pub enum WriteBatchRecord<T: AsRef<[u8]>> {
     Put(T, T),
     Del(T),
+    /// Delete all keys in `[start, end)`.
+    DelRange(T, T),
 }
 
 impl LsmStorageState {
@@ -75,13 +92,58 @@ impl LsmStorageState {
             CompactionOptions::NoCompaction => vec![(1, Vec::new())],
         };
         Self {
-            memtable: Arc::new(MemTable::create(0)),
+            memtable: Arc::new(MemTable::create_with_rep(0, options.memtable_rep)),
             imm_memtables: Vec::new(),
             l0_sstables: Vec::new(),
             levels,
             sstables: Default::default(),
         }
     }
+
+    /// Remove SSTs from L0 and the levels. With tiered compaction, tiers left empty are removed.
+    fn drop_ssts(&mut self, ssts: &[usize], tiered: bool) {
+        self.l0_sstables.retain(|id| !ssts.contains(id));
+        for (_, files) in &mut self.levels {
+            files.retain(|id| !ssts.contains(id));
+        }
+        if tiered {
+            self.levels.retain(|(_, files)| !files.is_empty());
+        }
+        for id in ssts {
+            self.sstables.remove(id);
+        }
+    }
+
+    /// Collect the range tombstones visible at `read_ts` that overlap the given range, from the
+    /// memtables and from the SSTs whose key range overlaps it.
+    fn range_tombstones(
+        &self,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+        read_ts: u64,
+    ) -> Vec<RangeTombstone> {
+        let memtable_tombstones = std::iter::once(&self.memtable)
+            .chain(self.imm_memtables.iter())
+            .flat_map(|memtable| memtable.range_tombstones());
+        let sst_tombstones = self
+            .l0_sstables
+            .iter()
+            .chain(self.levels.iter().flat_map(|(_, ssts)| ssts))
+            .map(|id| &self.sstables[id])
+            .filter(|table| {
+                range_overlap(
+                    lower,
+                    upper,
+                    table.first_key().as_key_slice(),
+                    table.last_key().as_key_slice(),
+                )
+            })
+            .flat_map(|table| table.range_tombstones().iter().cloned());
+        memtable_tombstones
+            .chain(sst_tombstones)
+            .filter(|tombstone| tombstone.ts <= read_ts && tombstone.overlaps(lower, upper))
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -95,9 +157,108 @@ pub struct LsmStorageOptions {
     pub compaction_options: CompactionOptions,
     pub enable_wal: bool,
     pub serializable: bool,
+    // Compression applied to SST data blocks
+    pub compression: CompressionType,
+    // Number of entries between two full keys in a data block
+    pub block_restart_interval: usize,
+    // Append a hash index to data blocks for point lookups
+    pub block_hash_index: bool,
+    // Layout of entries in data blocks; `FixedKey` requires all keys to have the same length
+    pub block_layout: BlockLayout,
+    // What to do with SSTs and WALs that fail checksum verification when opening the database
+    pub corruption_policy: CorruptionPolicy,
+    // Number of data blocks per index partition; `None` keeps the whole block index in memory
+    pub index_partition_size: Option<usize>,
+    // Add key prefixes to SST bloom filters so that `scan_prefix` can skip SSTs
+    pub prefix_extractor: Option<PrefixExtractor>,
+    // Type and size of SST filters for each level
+    pub filter_policy: FilterPolicy,
+    // Memory-map SST files and read blocks from the mapping instead of copying them
+    pub mmap_sst: bool,
+    // Read and write SST files with direct I/O, leaving the caching of blocks to the block cache
+    pub direct_io: bool,
+    // Capacity of the block cache in bytes
+    pub block_cache_size: u64,
+    // Load SST indexes and filters through the block cache, charged against its capacity
+    pub cache_index_and_filter_blocks: bool,
+    // Keep the indexes and filters of L0 SSTs (all SSTs with tiered compaction) loaded, charged
+    // against the block cache until they are evicted from it
+    pub pin_l0_index_and_filter_blocks: bool,
+    // Data structure of memtables; `Vector` suits bulk loads that do not read until they finish
+    pub memtable_rep: MemTableRepType,
+    // Memory budget shared with other storages for their active and immutable memtables
+    pub write_buffer_manager: Option<Arc<WriteBufferManager>>,
+    // Slow down or stop writes when flushes and compactions fall behind
+    pub write_stall: WriteStallOptions,
+    // Fsync the WAL after each group of writes, so that committed writes survive a crash
+    pub sync_wal_on_write: bool,
+}
+
+/// What `MiniLsm::open` does with an SST or WAL that fails checksum verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CorruptionPolicy {
+    /// Fail to open the database.
+    #[default]
+    Fail,
+    /// Open the database without the damaged file, leaving it in place. The batches of a WAL
+    /// before the damaged one are kept.
+    Skip,
+    /// Move the damaged file to the `quarantine` directory and open the database without it. A
+    /// WAL is copied instead, and truncated to the batches before the damaged one.
+    Quarantine,
 }
 
 impl LsmStorageOptions {
+    /// Whether the index and filter of an SST at `level` are loaded through the block cache.
+    pub(crate) fn index_and_filter_in_cache(&self, level: usize) -> bool {
+        self.cache_index_and_filter_blocks && !(level == 0 && self.pin_l0_index_and_filter_blocks)
+    }
+
+    /// Whether the index and filter of an SST at `level` are held by the SST and charged against
+    /// the block cache.
+    pub(crate) fn index_and_filter_pinned_in_cache(&self, level: usize) -> bool {
+        self.cache_index_and_filter_blocks && level == 0 && self.pin_l0_index_and_filter_blocks
+    }
+
+    /// Open an SST file for reading, following `mmap_sst` and `direct_io`.
+    pub(crate) fn open_sst_file(&self, path: &Path) -> Result<FileObject> {
+        if self.direct_io {
+            FileObject::open_direct(path)
+        } else if self.mmap_sst {
+            FileObject::open(path)?.mmap()
+        } else {
+            FileObject::open(path)
+        }
+    }
+
+    /// Check that a key-value pair fits in the limits of the storage.
+    pub(crate) fn check_entry(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        if key.len() > MAX_KEY_SIZE {
+            bail!(
+                "key of {} bytes exceeds the maximum key size of {} bytes",
+                key.len(),
+                MAX_KEY_SIZE
+            );
+        }
+        if let BlockLayout::FixedKey(key_len) = self.block_layout
+            && key.len() != key_len
+        {
+            bail!(
+                "key of {} bytes does not match the fixed key length of {} bytes",
+                key.len(),
+                key_len
+            );
+        }
+        if value.len() > MAX_VALUE_SIZE {
+            bail!(
+                "value of {} bytes exceeds the maximum value size of {} bytes",
+                value.len(),
+                MAX_VALUE_SIZE
+            );
+        }
+        Ok(())
+    }
+
     pub fn default_for_week1_test() -> Self {
         Self {
             block_size: 4096,
@@ -106,6 +267,23 @@ impl LsmStorageOptions {
             enable_wal: false,
             num_memtable_limit: 50,
             serializable: false,
+            compression: CompressionType::None,
+            block_restart_interval: DEFAULT_RESTART_INTERVAL,
+            block_hash_index: false,
+            block_layout: BlockLayout::Variable,
+            corruption_policy: CorruptionPolicy::Fail,
+            index_partition_size: None,
+            prefix_extractor: None,
+            filter_policy: FilterPolicy::default(),
+            mmap_sst: false,
+            direct_io: false,
+            block_cache_size: 4 << 30,
+            cache_index_and_filter_blocks: false,
+            pin_l0_index_and_filter_blocks: false,
+            memtable_rep: MemTableRepType::SkipList,
+            write_buffer_manager: None,
+            write_stall: WriteStallOptions::default(),
+            sync_wal_on_write: false,
         }
     }
 
@@ -117,6 +295,23 @@ impl LsmStorageOptions {
             enable_wal: false,
             num_memtable_limit: 2,
             serializable: false,
+            compression: CompressionType::None,
+            block_restart_interval: DEFAULT_RESTART_INTERVAL,
+            block_hash_index: false,
+            block_layout: BlockLayout::Variable,
+            corruption_policy: CorruptionPolicy::Fail,
+            index_partition_size: None,
+            prefix_extractor: None,
+            filter_policy: FilterPolicy::default(),
+            mmap_sst: false,
+            direct_io: false,
+            block_cache_size: 4 << 30,
+            cache_index_and_filter_blocks: false,
+            pin_l0_index_and_filter_blocks: false,
+            memtable_rep: MemTableRepType::SkipList,
+            write_buffer_manager: None,
+            write_stall: WriteStallOptions::default(),
+            sync_wal_on_write: false,
         }
     }
 
@@ -128,6 +323,23 @@ impl LsmStorageOptions {
             enable_wal: false,
             num_memtable_limit: 2,
             serializable: false,
+            compression: CompressionType::None,
+            block_restart_interval: DEFAULT_RESTART_INTERVAL,
+            block_hash_index: false,
+            block_layout: BlockLayout::Variable,
+            corruption_policy: CorruptionPolicy::Fail,
+            index_partition_size: None,
+            prefix_extractor: None,
+            filter_policy: FilterPolicy::default(),
+            mmap_sst: false,
+            direct_io: false,
+            block_cache_size: 4 << 30,
+            cache_index_and_filter_blocks: false,
+            pin_l0_index_and_filter_blocks: false,
+            memtable_rep: MemTableRepType::SkipList,
+            write_buffer_manager: None,
+            write_stall: WriteStallOptions::default(),
+            sync_wal_on_write: false,
         }
     }
 }
@@ -159,6 +371,19 @@ fn range_overlap(
     true
 }
 
+/// The smallest key greater than every key starting with `prefix`, or `None` if there is no such
+/// key.
+pub(crate) fn prefix_upper_bound(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut upper = prefix.to_vec();
+    while let Some(last) = upper.pop() {
+        if last != u8::MAX {
+            upper.push(last + 1);
+            return Some(upper);
+        }
+    }
+    None
+}
+
 fn key_within(user_key: &[u8], table_begin: KeySlice, table_end: KeySlice) -> bool {
     table_begin.key_ref() <= user_key && user_key <= table_end.key_ref()
 }
@@ -172,6 +397,16 @@ pub enum CompactionFilter {
 pub(crate) struct LsmStorageInner {
     pub(crate) state: Arc<RwLock<Arc<LsmStorageState>>>,
     pub(crate) state_lock: Mutex<()>,
+    /// Held while a compaction runs, so that no SST is ingested into the levels it writes to.
+    pub(crate) compaction_lock: Mutex<()>,
+    /// Notified when a flush or compaction finishes, to wake up the writers stopped by
+    /// `options.write_stall`.
+    backlog_reduced: Condvar,
+    backlog_lock: Mutex<()>,
+    /// The estimate of the bytes to compact for `options.write_stall`, updated when the SSTs
+    /// change instead of on each write, as it goes through all SSTs.
+    pending_compaction_bytes: AtomicU64,
+    group_commit: GroupCommitQueue,
     path: PathBuf,
     pub(crate) block_cache: Arc<BlockCache>,
     next_sst_id: AtomicUsize,
@@ -230,8 +465,9 @@ impl MiniLsm {
         // create memtable and skip updating manifest
         if !self.inner.state.read().memtable.is_empty() {
             self.inner
-                .freeze_memtable_with_memtable(Arc::new(MemTable::create(
+                .freeze_memtable_with_memtable(Arc::new(MemTable::create_with_rep(
                     self.inner.next_sst_id(),
+                    self.inner.options.memtable_rep,
                 )))?;
         }
 
@@ -250,6 +486,9 @@ impl MiniLsm {
     /// not exist.
     pub fn open(path: impl AsRef<Path>, options: LsmStorageOptions) -> Result<Arc<Self>> {
         let inner = Arc::new(LsmStorageInner::open(path, options)?);
+        if let Some(write_buffer_manager) = &inner.options.write_buffer_manager {
+            write_buffer_manager.register(&inner);
+        }
         let (tx1, rx) = crossbeam_channel::unbounded();
         let compaction_thread = inner.spawn_compaction_thread(rx)?;
         let (tx2, rx) = crossbeam_channel::unbounded();
@@ -283,6 +522,10 @@ impl MiniLsm {
         self.inner.delete(key)
     }
 
+    pub fn delete_range(&self, lower: &[u8], upper: &[u8]) -> Result<()> {
+        self.inner.delete_range(lower, upper)
+    }
+
     pub fn sync(&self) -> Result<()> {
         self.inner.sync()
     }
@@ -295,6 +538,10 @@ impl MiniLsm {
         self.inner.scan(lower, upper)
     }
 
+    pub fn scan_prefix(&self, prefix: &[u8]) -> Result<TxnIterator> {
+        self.inner.scan_prefix(prefix)
+    }
+
     /// Only call this in test cases due to race conditions
     pub fn force_flush(&self) -> Result<()> {
         if !self.inner.state.read().memtable.is_empty() {
@@ -310,6 +557,19 @@ impl MiniLsm {
     pub fn force_full_compaction(&self) -> Result<()> {
         self.inner.force_full_compaction()
     }
+
+    pub fn index_and_filter_memory(&self) -> IndexAndFilterMemory {
+        self.inner.index_and_filter_memory()
+    }
+
+    pub fn write_stall_state(&self) -> WriteStallState {
+        self.inner.write_stall_state()
+    }
+
+    /// Add SST files written by `SstFileWriter`, see `LsmStorageInner::ingest_external_files`.
+    pub fn ingest_external_files(&self, paths: &[impl AsRef<Path>]) -> Result<()> {
+        self.inner.ingest_external_files(paths)
+    }
 }
 
 impl LsmStorageInner {
@@ -326,13 +586,38 @@ impl LsmStorageInner {
         self.manifest.as_ref().unwrap()
     }
 
+    /// Create a builder for an SST written to `level`, where L0 is level 0.
+    pub(crate) fn new_sst_builder(&self, level: usize, is_last_level: bool) -> SsTableBuilder {
+        SsTableBuilder::new(self.options.block_size)
+            .with_compression(self.options.compression)
+            .with_restart_interval(self.options.block_restart_interval)
+            .with_block_layout(self.options.block_layout)
+            .with_hash_index(self.options.block_hash_index)
+            .with_index_partition_size(self.options.index_partition_size)
+            .with_prefix_extractor(self.options.prefix_extractor)
+            .with_filter(
+                self.options
+                    .filter_policy
+                    .filter_for_level(level, is_last_level),
+            )
+            .with_mmap(self.options.mmap_sst)
+            .with_direct_io(self.options.direct_io)
+            .with_index_and_filter_in_cache(self.options.index_and_filter_in_cache(level))
+            .with_index_and_filter_pinned_in_cache(
+                self.options.index_and_filter_pinned_in_cache(level),
+            )
+    }
+
     /// Start the storage engine by either loading an existing directory or creating a new one if the directory does
     /// not exist.
     pub(crate) fn open(path: impl AsRef<Path>, options: LsmStorageOptions) -> Result<Self> {
+        if options.mmap_sst && options.direct_io {
+            bail!("mmap_sst and direct_io cannot be enabled together");
+        }
         let mut state = LsmStorageState::create(&options);
         let path = path.as_ref();
         let mut next_sst_id = 1;
-        let block_cache = Arc::new(BlockCache::new(1 << 20)); // 4GB block cache,
+        let block_cache = Arc::new(cache::new_block_cache(options.block_cache_size));
         let manifest;
 
         let compaction_controller = match &options.compaction_options {
@@ -357,6 +642,7 @@ impl LsmStorageInner {
             if options.enable_wal {
                 state.memtable = Arc::new(MemTable::create_with_wal(
                     state.memtable.id(),
+                    options.memtable_rep,
                     Self::path_of_wal_static(path, state.memtable.id()),
                 )?);
             }
@@ -365,6 +651,7 @@ impl LsmStorageInner {
         } else {
             let (m, records) = Manifest::recover(&manifest_path)?;
             let mut memtables = BTreeSet::new();
+            let mut ingested_ts = HashMap::new();
             for record in records {
                 match record {
                     ManifestRecord::Flush(sst_id) => {
@@ -389,59 +676,117 @@ impl LsmStorageInner {
                         next_sst_id =
                             next_sst_id.max(output.iter().max().copied().unwrap_or_default());
                     }
+                    ManifestRecord::Ingest(ssts, ts) => {
+                        for &(level, sst_id) in &ssts {
+                            state.add_ingested_sst(
+                                level,
+                                sst_id,
+                                !compaction_controller.flush_to_l0(),
+                            );
+                            ingested_ts.insert(sst_id, ts);
+                            next_sst_id = next_sst_id.max(sst_id);
+                        }
+                        last_commit_ts = last_commit_ts.max(ts);
+                    }
+                    ManifestRecord::DropSsts(ssts) => {
+                        state.drop_ssts(&ssts, !compaction_controller.flush_to_l0());
+                    }
+                    ManifestRecord::MaxTs(ts) => {
+                        last_commit_ts = last_commit_ts.max(ts);
+                    }
                 }
             }
 
             let mut sst_cnt = 0;
+            let mut skipped_ssts = Vec::new();
             // recover SSTs
-            for table_id in state
-                .l0_sstables
-                .iter()
-                .chain(state.levels.iter().flat_map(|(_, files)| files))
-            {
-                let table_id = *table_id;
-                let sst = SsTable::open(
-                    table_id,
-                    Some(block_cache.clone()),
-                    FileObject::open(&Self::path_of_sst_static(path, table_id))
-                        .context("failed to open SST")?,
-                )?;
+            // With tiered compaction, all tiers count as L0.
+            let tiered = !compaction_controller.flush_to_l0();
+            let tables = state.l0_sstables.iter().map(|id| (0, *id)).chain(
+                state
+                    .levels
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(idx, (_, files))| {
+                        let level = if tiered { 0 } else { idx + 1 };
+                        files.iter().map(move |id| (level, *id))
+                    }),
+            );
+            for (level, table_id) in tables.collect::<Vec<_>>() {
+                let sst_path = Self::path_of_sst_static(path, table_id);
+                if Self::is_quarantined(path, &sst_path) {
+                    skipped_ssts.push(table_id);
+                    continue;
+                }
+                let sst = match options
+                    .open_sst_file(&sst_path)
+                    .context("failed to open SST")
+                    .and_then(|file| SsTable::open(table_id, Some(block_cache.clone()), file))
+                {
+                    Ok(sst) if options.index_and_filter_in_cache(level) => {
+                        sst.unpin_index_and_filter()
+                    }
+                    Ok(sst) if options.index_and_filter_pinned_in_cache(level) => {
+                        sst.pin_index_and_filter_in_cache()
+                    }
+                    Ok(sst) => sst,
+                    Err(e) => {
+                        Self::handle_corrupted_sst(path, &sst_path, options.corruption_policy, e)?;
+                        skipped_ssts.push(table_id);
+                        continue;
+                    }
+                };
+                let sst = match ingested_ts.get(&table_id) {
+                    Some(&ts) => sst.with_global_ts(ts),
+                    None => sst,
+                };
                 last_commit_ts = last_commit_ts.max(sst.max_ts());
                 state.sstables.insert(table_id, Arc::new(sst));
                 sst_cnt += 1;
             }
             println!("{} SSTs opened", sst_cnt);
+            if !skipped_ssts.is_empty() {
+                // Record the removal, so that compactions recorded later replay against the same
+                // set of SSTs.
+                state.drop_ssts(&skipped_ssts, tiered);
+                m.add_record_when_init(ManifestRecord::DropSsts(skipped_ssts))?;
+            }
 
             next_sst_id += 1;
 
-            // Sort SSTs on each level (only for leveled compaction)
-            if let CompactionController::Leveled(_) = &compaction_controller {
-                for (_id, ssts) in &mut state.levels {
-                    ssts.sort_by(|x, y| {
-                        state
-                            .sstables
-                            .get(x)
-                            .unwrap()
-                            .first_key()
-                            .cmp(state.sstables.get(y).unwrap().first_key())
-                    })
-                }
+            // Sort SSTs on each level, as leveled compaction and ingestion do not record the order
+            for (_id, ssts) in &mut state.levels {
+                ssts.sort_by(|x, y| {
+                    state
+                        .sstables
+                        .get(x)
+                        .unwrap()
+                        .first_key()
+                        .cmp(state.sstables.get(y).unwrap().first_key())
+                })
             }
 
             // recover memtables
             if options.enable_wal {
                 let mut wal_cnt = 0;
                 for id in memtables.iter() {
-                    let memtable =
-                        MemTable::recover_from_wal(*id, Self::path_of_wal_static(path, *id))?;
-                    let max_ts = memtable
-                        .map
-                        .iter()
-                        .map(|x| x.key().ts())
-                        .max()
-                        .unwrap_or_default();
-                    last_commit_ts = last_commit_ts.max(max_ts);
+                    let wal_path = Self::path_of_wal_static(path, *id);
+                    if Self::is_quarantined(path, &wal_path) {
+                        continue;
+                    }
+                    let (memtable, corruption) =
+                        MemTable::recover_from_wal_prefix(*id, options.memtable_rep, &wal_path)?;
+                    if let Some(corruption) = corruption {
+                        Self::handle_corrupted_wal(
+                            path,
+                            &wal_path,
+                            options.corruption_policy,
+                            corruption,
+                        )?;
+                    }
+                    last_commit_ts = last_commit_ts.max(memtable.max_ts());
                     if !memtable.is_empty() {
+                        memtable.freeze();
                         state.imm_memtables.insert(0, Arc::new(memtable));
                         wal_cnt += 1;
                     }
@@ -449,10 +794,12 @@ impl LsmStorageInner {
                 println!("{} WALs recovered", wal_cnt);
                 state.memtable = Arc::new(MemTable::create_with_wal(
                     next_sst_id,
+                    options.memtable_rep,
                     Self::path_of_wal_static(path, next_sst_id),
                 )?);
             } else {
-                state.memtable = Arc::new(MemTable::create(next_sst_id));
+                state.memtable =
+                    Arc::new(MemTable::create_with_rep(next_sst_id, options.memtable_rep));
             }
             m.add_record_when_init(ManifestRecord::NewMemtable(state.memtable.id()))?;
             next_sst_id += 1;
@@ -462,6 +809,11 @@ impl LsmStorageInner {
         let storage = Self {
             state: Arc::new(RwLock::new(Arc::new(state))),
             state_lock: Mutex::new(()),
+            compaction_lock: Mutex::new(()),
+            backlog_reduced: Condvar::new(),
+            backlog_lock: Mutex::new(()),
+            pending_compaction_bytes: AtomicU64::new(0),
+            group_commit: GroupCommitQueue::default(),
             path: path.to_path_buf(),
             block_cache,
             next_sst_id: AtomicUsize::new(next_sst_id),
@@ -471,6 +823,7 @@ impl LsmStorageInner {
             mvcc: Some(LsmMvccInner::new(last_commit_ts)),
             compaction_filters: Arc::new(Mutex::new(Vec::new())),
         };
+        storage.update_pending_compaction_bytes();
         storage.sync_dir()?;
 
         Ok(storage)
@@ -512,27 +865,21 @@ impl LsmStorageInner {
 
         let mut l0_iters = Vec::with_capacity(snapshot.l0_sstables.len());
 
-        let keep_table = |key: &[u8], table: &SsTable| {
+        let keep_table = |key: &[u8], table: &SsTable| -> Result<bool> {
             if key_within(
                 key,
                 table.first_key().as_key_slice(),
                 table.last_key().as_key_slice(),
             ) {
-                if let Some(bloom) = &table.bloom {
-                    if bloom.may_contain(farmhash::fingerprint32(key)) {
-                        return true;
-                    }
-                } else {
-                    return true;
-                }
+                return table.may_contain(key);
             }
-            false
+            Ok(false)
         };
 
         for table in snapshot.l0_sstables.iter() {
             let table = snapshot.sstables[table].clone();
-            if keep_table(key, &table) {
-                l0_iters.push(Box::new(SsTableIterator::create_and_seek_to_key(
+            if keep_table(key, &table)? {
+                l0_iters.push(Box::new(SsTableIterator::create_and_seek_for_get(
                     table,
                     KeySlice::from_slice(key, key::TS_RANGE_BEGIN),
                 )?));
@@ -544,11 +891,11 @@ impl LsmStorageInner {
             let mut level_ssts = Vec::with_capacity(level_sst_ids.len());
             for table in level_sst_ids {
                 let table = snapshot.sstables[table].clone();
-                if keep_table(key, &table) {
+                if keep_table(key, &table)? {
                     level_ssts.push(table);
                 }
             }
-            let level_iter = SstConcatIterator::create_and_seek_to_key(
+            let level_iter = SstConcatIterator::create_and_seek_for_get(
                 level_ssts,
                 KeySlice::from_slice(key, key::TS_RANGE_BEGIN),
             )?;
@@ -562,6 +909,7 @@ impl LsmStorageInner {
             )?,
             Bound::Unbounded,
             read_ts,
+            snapshot.range_tombstones(Bound::Included(key), Bound::Included(key), read_ts),
         )?;
 
         if iter.is_valid() && iter.key() == key && !iter.value().is_empty() {
@@ -571,34 +919,111 @@ impl LsmStorageInner {
     }
 
     pub fn write_batch_inner<T: AsRef<[u8]>>(&self, batch: &[WriteBatchRecord<T>]) -> Result<u64> {
-        let _lck = self.mvcc().write_lock.lock();
-        let ts = self.mvcc().latest_commit_ts() + 1;
-        let mut batch_datas: Vec<(key::Key<&[u8]>, &[u8])> = vec![];
-        let size;
         for record in batch {
-            match record {
-                WriteBatchRecord::Del(key) => {
-                    let key = key.as_ref();
-                    assert!(!key.is_empty(), "key cannot be empty");
-                    batch_datas.push((KeySlice::from_slice(key, ts), b""));
-                }
-                WriteBatchRecord::Put(key, value) => {
-                    let key = key.as_ref();
-                    let value = value.as_ref();
-                    assert!(!key.is_empty(), "key cannot be empty");
-                    assert!(!value.is_empty(), "value cannot be empty");
-                    batch_datas.push((KeySlice::from_slice(key, ts), value));
+            let (key, value) = match record {
+                WriteBatchRecord::Del(key) => (key.as_ref(), &[][..]),
+                WriteBatchRecord::Put(key, value) => (key.as_ref(), value.as_ref()),
+                WriteBatchRecord::DelRange(start, end) => {
+                    let (start, end) = (start.as_ref(), end.as_ref());
+                    if start >= end {
+                        bail!("the start of a range deletion must be smaller than its end");
+                    }
+                    if start.len().max(end.len()) > MAX_KEY_SIZE {
+                        bail!(
+                            "range deletion bound exceeds the maximum key size of {} bytes",
+                            MAX_KEY_SIZE
+                        );
+                    }
+                    continue;
                 }
+            };
+            self.options.check_entry(key, value)?;
+        }
+        if let Some(write_buffer_manager) = &self.options.write_buffer_manager {
+            write_buffer_manager.stall(self.options.write_stall.stop_timeout)?;
+        }
+        self.wait_for_write_stall()?;
+        // Without contention, the writer leads right away and its batch is not copied.
+        let follower_result = if self.group_commit.try_lead() {
+            None
+        } else {
+            let writer = Arc::new(GroupWriter::new(GroupBatch::new(batch)));
+            if self.group_commit.join(&writer) {
+                None
+            } else {
+                writer.follow()
+            }
+        };
+        let ts = match follower_result {
+            Some(result) => result?,
+            // Lead a group, either right away or once the previous leader hands over.
+            None => {
+                let result = self.commit_group(batch);
+                self.group_commit.finish_group();
+                result?
             }
+        };
+        if let Some(write_buffer_manager) = &self.options.write_buffer_manager {
+            let written = batch
+                .iter()
+                .map(|record| match record {
+                    WriteBatchRecord::Put(key, value) | WriteBatchRecord::DelRange(key, value) => {
+                        key.as_ref().len() + value.as_ref().len()
+                    }
+                    WriteBatchRecord::Del(key) => key.as_ref().len(),
+                })
+                .sum();
+            write_buffer_manager.maybe_freeze(written)?;
         }
-        {
+        Ok(ts)
+    }
+
+    /// Commit the batch of the leader and of the writers waiting in the group commit queue, with
+    /// consecutive timestamps. The batches are written to the WAL at once, then inserted into the
+    /// memtable in parallel by their writers.
+    fn commit_group<T: AsRef<[u8]>>(&self, batch: &[WriteBatchRecord<T>]) -> Result<u64> {
+        let _lck = self.mvcc().write_lock.lock();
+        let followers = self.group_commit.take_followers();
+        let ts = self.mvcc().latest_commit_ts() + 1;
+        let (mut data, mut range_tombstones) = batch_entries(batch, ts);
+        let leader_len = (data.len(), range_tombstones.len());
+        for (follower, ts) in followers.iter().zip(ts + 1..) {
+            data.extend(follower.batch.entries(ts));
+            range_tombstones.extend(follower.batch.range_tombstones(ts));
+        }
+        // Record the inserts of the group while the memtable is active, so that freezing it waits
+        // for them. The state is not held while the WAL is written, which would block the
+        // freezes, and the readers queued behind them.
+        let memtable = {
             let guard = self.state.read();
-            guard.memtable.put_batch(&batch_datas)?;
-            size = guard.memtable.approximate_size();
+            guard.memtable.add_pending_inserts(followers.len() + 1);
+            guard.memtable.clone()
+        };
+        let mut result = memtable.write_wal(&data, &range_tombstones);
+        if result.is_ok() && self.options.sync_wal_on_write {
+            result = memtable.sync_wal();
+        }
+        if let Err(e) = result {
+            memtable.cancel_pending_inserts(followers.len() + 1);
+            let e = GroupCommitError::new(e);
+            for follower in &followers {
+                follower.finish(Err(e.clone()));
+            }
+            return Err(e.into());
+        }
+        for (follower, ts) in followers.iter().zip(ts + 1..) {
+            follower.insert(ts, memtable.clone());
+        }
+        memtable.insert_pending_batch(&data[..leader_len.0], &range_tombstones[..leader_len.1]);
+        for follower in &followers {
+            follower.wait_inserted();
+        }
+        let size = memtable.approximate_size();
+        self.mvcc().update_commit_ts(ts + followers.len() as u64);
+        for (follower, ts) in followers.iter().zip(ts + 1..) {
+            follower.finish(Ok(ts));
         }
         self.try_freeze(size)?;
-
-        self.mvcc().update_commit_ts(ts);
         Ok(ts)
     }
 
@@ -618,6 +1043,9 @@ impl LsmStorageInner {
                     WriteBatchRecord::Put(key, value) => {
                         txn.put(key.as_ref(), value.as_ref());
                     }
+                    WriteBatchRecord::DelRange(start, end) => {
+                        txn.delete_range(start.as_ref(), end.as_ref());
+                    }
                 }
             }
             txn.commit()?;
@@ -649,6 +1077,18 @@ impl LsmStorageInner {
         Ok(())
     }
 
+    /// Remove all keys in `[lower, upper)` from the storage by writing a range tombstone.
+    pub fn delete_range(self: &Arc<Self>, lower: &[u8], upper: &[u8]) -> Result<()> {
+        if !self.options.serializable {
+            self.write_batch_inner(&[WriteBatchRecord::DelRange(lower, upper)])?;
+        } else {
+            let txn = self.mvcc().new_txn(self.clone(), self.options.serializable);
+            txn.delete_range(lower, upper);
+            txn.commit()?;
+        }
+        Ok(())
+    }
+
     fn try_freeze(&self, estimated_size: usize) -> Result<()> {
         if estimated_size >= self.options.target_sst_size {
             let state_lock = self.state_lock.lock();
@@ -662,6 +1102,76 @@ impl LsmStorageInner {
         Ok(())
     }
 
+    pub(crate) fn path_of_quarantine_static(path: impl AsRef<Path>) -> PathBuf {
+        path.as_ref().join("quarantine")
+    }
+
+    /// Whether `file` was moved to the quarantine directory by an earlier `open`.
+    fn is_quarantined(path: &Path, file: &Path) -> bool {
+        !file.exists()
+            && Self::path_of_quarantine_static(path)
+                .join(file.file_name().unwrap())
+                .exists()
+    }
+
+    /// Apply the corruption policy to an SST that failed to load. Returns `Ok` if the database
+    /// should be opened without the SST.
+    fn handle_corrupted_sst(
+        path: &Path,
+        file: &Path,
+        policy: CorruptionPolicy,
+        err: anyhow::Error,
+    ) -> Result<()> {
+        let Some(corruption) = err.downcast_ref::<CorruptionError>() else {
+            return Err(err);
+        };
+        match policy {
+            CorruptionPolicy::Fail => return Err(err),
+            CorruptionPolicy::Skip => {
+                println!("skipping {}: {}", file.display(), corruption);
+            }
+            CorruptionPolicy::Quarantine => {
+                let quarantine_dir = Self::path_of_quarantine_static(path);
+                std::fs::create_dir_all(&quarantine_dir)
+                    .context("failed to create quarantine dir")?;
+                std::fs::rename(file, quarantine_dir.join(file.file_name().unwrap()))
+                    .context("failed to quarantine file")?;
+                println!("quarantined {}: {}", file.display(), corruption);
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the corruption policy to a WAL whose replay stopped at a corrupted or incomplete
+    /// batch. Returns `Ok` if the database should be opened with the batches before it.
+    fn handle_corrupted_wal(
+        path: &Path,
+        wal: &Path,
+        policy: CorruptionPolicy,
+        corruption: CorruptionError,
+    ) -> Result<()> {
+        match policy {
+            CorruptionPolicy::Fail => return Err(corruption.into()),
+            CorruptionPolicy::Skip => {
+                println!("skipping the rest of {}: {}", wal.display(), corruption);
+            }
+            CorruptionPolicy::Quarantine => {
+                // Keep the batches before the damaged one in the WAL, in case the database
+                // crashes again before they are flushed.
+                let quarantine_dir = Self::path_of_quarantine_static(path);
+                std::fs::create_dir_all(&quarantine_dir)
+                    .context("failed to create quarantine dir")?;
+                std::fs::copy(wal, quarantine_dir.join(wal.file_name().unwrap()))
+                    .context("failed to quarantine file")?;
+                let file = File::options().write(true).open(wal)?;
+                file.set_len(corruption.offset)?;
+                file.sync_all()?;
+                println!("quarantined the rest of {}: {}", wal.display(), corruption);
+            }
+        }
+        Ok(())
+    }
+
     pub(crate) fn path_of_sst_static(path: impl AsRef<Path>, id: usize) -> PathBuf {
         path.as_ref().join(format!("{:05}.sst", id))
     }
@@ -694,6 +1204,7 @@ impl LsmStorageInner {
         *guard = Arc::new(snapshot);
 
         drop(guard);
+        old_memtable.freeze();
         old_memtable.sync_wal()?;
 
         Ok(())
@@ -705,10 +1216,14 @@ impl LsmStorageInner {
         let memtable = if self.options.enable_wal {
             Arc::new(MemTable::create_with_wal(
                 memtable_id,
+                self.options.memtable_rep,
                 self.path_of_wal(memtable_id),
             )?)
         } else {
-            Arc::new(MemTable::create(memtable_id))
+            Arc::new(MemTable::create_with_rep(
+                memtable_id,
+                self.options.memtable_rep,
+            ))
         };
 
         self.freeze_memtable_with_memtable(memtable)?;
@@ -730,14 +1245,14 @@ impl LsmStorageInner {
 
         {
             let guard = self.state.read();
-            flush_memtable = guard
-                .imm_memtables
-                .last()
-                .expect("no imm memtables!")
-                .clone();
+            // The flush thread may have flushed it after the caller checked `imm_memtables`.
+            let Some(memtable) = guard.imm_memtables.last() else {
+                return Ok(());
+            };
+            flush_memtable = memtable.clone();
         }
 
-        let mut builder = SsTableBuilder::new(self.options.block_size);
+        let mut builder = self.new_sst_builder(0, false);
         flush_memtable.flush(&mut builder)?;
         let sst_id = flush_memtable.id();
         let sst = Arc::new(builder.build(
@@ -745,6 +1260,7 @@ impl LsmStorageInner {
             Some(self.block_cache.clone()),
             self.path_of_sst(sst_id),
         )?);
+        let max_ts = sst.max_ts();
 
         // Add the flushed L0 table to the list.
         {
@@ -767,10 +1283,17 @@ impl LsmStorageInner {
             *guard = Arc::new(snapshot);
         }
 
+        if let Some(write_buffer_manager) = &self.options.write_buffer_manager {
+            write_buffer_manager.notify_flushed(flush_memtable.memory_usage());
+        }
+        self.notify_backlog_reduced();
+
         if self.options.enable_wal {
             std::fs::remove_file(self.path_of_wal(sst_id))?;
         }
 
+        self.manifest()
+            .add_record(&state_lock, ManifestRecord::MaxTs(max_ts))?;
         self.manifest()
             .add_record(&state_lock, ManifestRecord::Flush(sst_id))?;
 
@@ -793,10 +1316,114 @@ impl LsmStorageInner {
         txn.scan(lower, upper)
     }
 
+    /// Whether writes are slowed down or stopped by `options.write_stall`, and why.
+    pub fn write_stall_state(&self) -> WriteStallState {
+        let snapshot = self.state.read().clone();
+        let num_l0_files = if self.compaction_controller.flush_to_l0() {
+            snapshot.l0_sstables.len()
+        } else {
+            snapshot.levels.len()
+        };
+        self.options.write_stall.state(
+            snapshot.imm_memtables.len(),
+            num_l0_files,
+            self.pending_compaction_bytes
+                .load(std::sync::atomic::Ordering::Acquire),
+        )
+    }
+
+    /// Delay the write while writes are slowed down, and block it while they are stopped, up to
+    /// `stop_timeout`.
+    fn wait_for_write_stall(&self) -> Result<()> {
+        let options = &self.options.write_stall;
+        let start = Instant::now();
+        loop {
+            match self.write_stall_state() {
+                WriteStallState::Normal => return Ok(()),
+                WriteStallState::Delayed(_) => {
+                    std::thread::sleep(options.slowdown_delay);
+                    return Ok(());
+                }
+                WriteStallState::Stopped(cause) => {
+                    let elapsed = start.elapsed();
+                    if elapsed >= options.stop_timeout {
+                        return Err(WriteStallError {
+                            cause,
+                            timeout: options.stop_timeout,
+                        }
+                        .into());
+                    }
+                    let mut guard = self.backlog_lock.lock();
+                    // The state is also checked periodically, as a flush may finish before the
+                    // wait starts.
+                    self.backlog_reduced.wait_for(
+                        &mut guard,
+                        (options.stop_timeout - elapsed).min(Duration::from_millis(10)),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Wake up the writers stopped by `options.write_stall` after a flush or compaction.
+    pub(crate) fn notify_backlog_reduced(&self) {
+        self.update_pending_compaction_bytes();
+        self.backlog_reduced.notify_all();
+    }
+
+    /// Estimate the bytes to compact for the current SSTs. Only estimated when needed by
+    /// `options.write_stall`.
+    pub(crate) fn update_pending_compaction_bytes(&self) {
+        if self
+            .options
+            .write_stall
+            .has_pending_compaction_bytes_trigger()
+        {
+            let snapshot = self.state.read().clone();
+            let pending_compaction_bytes = self
+                .compaction_controller
+                .estimate_pending_compaction_bytes(&snapshot);
+            self.pending_compaction_bytes.store(
+                pending_compaction_bytes,
+                std::sync::atomic::Ordering::Release,
+            );
+        }
+    }
+
+    /// The memory held by the indexes and filters of all SSTs, by the SSTs themselves or by the
+    /// block cache.
+    pub fn index_and_filter_memory(&self) -> IndexAndFilterMemory {
+        let snapshot = self.state.read().clone();
+        let charged = snapshot
+            .sstables
+            .values()
+            .filter(|sst| sst.is_index_and_filter_charged())
+            .map(|sst| sst.sst_id())
+            .collect();
+        IndexAndFilterMemory {
+            pinned: snapshot
+                .sstables
+                .values()
+                .map(|sst| sst.pinned_index_and_filter_size())
+                .sum(),
+            cached: cache::cached_index_and_filter_size(&self.block_cache, &charged),
+        }
+    }
+
+    /// Create an iterator over all keys starting with `prefix`. SSTs whose prefix bloom filter
+    /// rules out the prefix are skipped.
+    pub fn scan_prefix(self: &Arc<Self>, prefix: &[u8]) -> Result<TxnIterator> {
+        let txn = self.mvcc().new_txn(self.clone(), self.options.serializable);
+        txn.scan_prefix(prefix)
+    }
+
+    /// Create an iterator over a range of keys at `read_ts`. If `prefix` is set, all keys in the
+    /// range start with it, and SSTs that do not contain the prefix are skipped.
     pub(crate) fn scan_with_ts(
         &self,
         lower: Bound<&[u8]>,
         upper: Bound<&[u8]>,
+        prefix: Option<&[u8]>,
         read_ts: u64,
     ) -> Result<FusedIterator<LsmIterator>> {
         let snapshot = {
@@ -812,15 +1439,25 @@ impl LsmStorageInner {
         }
         let memtable_iter = MergeIterator::create(memtable_iters);
 
-        let mut table_iters = Vec::with_capacity(snapshot.l0_sstables.len());
-        for table_id in snapshot.l0_sstables.iter() {
-            let table = snapshot.sstables[table_id].clone();
-            if range_overlap(
+        let keep_table = |table: &SsTable| -> Result<bool> {
+            if !range_overlap(
                 lower,
                 upper,
                 table.first_key().as_key_slice(),
                 table.last_key().as_key_slice(),
             ) {
+                return Ok(false);
+            }
+            match prefix {
+                Some(prefix) => table.may_contain_prefix(prefix),
+                None => Ok(true),
+            }
+        };
+
+        let mut table_iters = Vec::with_capacity(snapshot.l0_sstables.len());
+        for table_id in snapshot.l0_sstables.iter() {
+            let table = snapshot.sstables[table_id].clone();
+            if keep_table(&table)? {
                 let iter = match lower {
                     Bound::Included(key) => SsTableIterator::create_and_seek_to_key(
                         table,
@@ -851,12 +1488,7 @@ impl LsmStorageInner {
             let mut level_ssts = Vec::with_capacity(level_sst_ids.len());
             for table in level_sst_ids {
                 let table = snapshot.sstables[table].clone();
-                if range_overlap(
-                    lower,
-                    upper,
-                    table.first_key().as_key_slice(),
-                    table.last_key().as_key_slice(),
-                ) {
+                if keep_table(&table)? {
                     level_ssts.push(table);
                 }
             }
@@ -888,6 +1520,7 @@ impl LsmStorageInner {
             iter,
             map_bound(upper),
             read_ts,
+            snapshot.range_tombstones(lower, upper, read_ts),
         )?))
     }
 }
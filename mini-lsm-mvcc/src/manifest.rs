@@ -33,6 +33,14 @@ pub enum ManifestRecord {
     Flush(usize),
     NewMemtable(usize),
     Compaction(CompactionTask, Vec<usize>),
+    /// SSTs added by ingestion, as (level, SST id) pairs, and the timestamp of their keys. Level
+    /// 0 is L0, and with tiered compaction the level is the id of the tier.
+    Ingest(Vec<(usize, usize)>, u64),
+    /// SSTs dropped on open because they failed checksum verification.
+    DropSsts(Vec<usize>),
+    /// A timestamp at least as high as every timestamp in the SSTs added so far, so that
+    /// timestamps are not reused after a damaged SST is dropped.
+    MaxTs(u64),
 }
 
 impl Manifest {
@@ -12,6 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod arena;
+mod rep;
+mod skiplist;
+mod vector;
+
 use std::ops::Bound;
 use std::path::Path;
 use std::sync::Arc;
@@ -19,24 +24,33 @@ use std::sync::atomic::AtomicUsize;
 
 use anyhow::Result;
 use bytes::Bytes;
-use crossbeam_skiplist::SkipMap;
-use crossbeam_skiplist::map::Entry;
-use ouroboros::self_referencing;
+use parking_lot::{Condvar, Mutex, RwLock};
 
+use crate::error::CorruptionError;
 use crate::iterators::StorageIterator;
-use crate::key::{KeyBytes, KeySlice, TS_DEFAULT, TS_RANGE_BEGIN, TS_RANGE_END};
+use crate::key::{KeySlice, TS_DEFAULT, TS_RANGE_BEGIN, TS_RANGE_END};
+use crate::range_tombstone::RangeTombstone;
 use crate::table::SsTableBuilder;
 use crate::wal::Wal;
 
-/// A basic mem-table based on crossbeam-skiplist.
+pub use self::rep::{MemTableRep, MemTableRepIterator, MemTableRepType};
+use self::skiplist::SkipList;
+use self::vector::VectorRep;
+
+/// A mem-table, whose entries are kept in a `MemTableRep`.
 ///
 /// An initial implementation of memtable is part of week 1, day 1. It will be incrementally implemented in other
 /// chapters of week 1 and week 2.
 pub struct MemTable {
-    pub(crate) map: Arc<SkipMap<KeyBytes, Bytes>>,
+    map: Arc<dyn MemTableRep>,
+    range_tombstones: RwLock<Vec<RangeTombstone>>,
     wal: Option<Wal>,
     id: usize,
-    approximate_size: Arc<AtomicUsize>,
+    /// Memory held by `range_tombstones`.
+    range_tombstones_size: AtomicUsize,
+    /// Number of batches of group commits that are not inserted yet.
+    pending_inserts: Mutex<usize>,
+    inserts_done: Condvar,
 }
 
 /// Create a bound of `Bytes` from a bound of `&[u8]`.
@@ -48,21 +62,6 @@ pub(crate) fn map_bound(bound: Bound<&[u8]>) -> Bound<Bytes> {
     }
 }
 
-/// Create a bound of `Bytes` from a bound of `KeySlice`.
-pub(crate) fn map_key_bound(bound: Bound<KeySlice>) -> Bound<KeyBytes> {
-    match bound {
-        Bound::Included(x) => Bound::Included(KeyBytes::from_bytes_with_ts(
-            Bytes::copy_from_slice(x.key_ref()),
-            x.ts(),
-        )),
-        Bound::Excluded(x) => Bound::Excluded(KeyBytes::from_bytes_with_ts(
-            Bytes::copy_from_slice(x.key_ref()),
-            x.ts(),
-        )),
-        Bound::Unbounded => Bound::Unbounded,
-    }
-}
-
 /// Create a bound of `KeySlice` from a bound of `&[u8]`.
 pub(crate) fn map_key_bound_plus_ts<'a>(
     lower: Bound<&'a [u8]>,
@@ -88,45 +87,89 @@ pub(crate) fn map_key_bound_plus_ts<'a>(
     )
 }
 
+fn new_rep(rep: MemTableRepType) -> Arc<dyn MemTableRep> {
+    match rep {
+        MemTableRepType::SkipList => Arc::new(SkipList::new()),
+        MemTableRepType::Vector => Arc::new(VectorRep::new()),
+    }
+}
+
 impl MemTable {
     /// Create a new mem-table.
     pub fn create(id: usize) -> Self {
+        Self::create_with_rep(id, MemTableRepType::default())
+    }
+
+    /// Create a new mem-table with the given representation.
+    pub fn create_with_rep(id: usize, rep: MemTableRepType) -> Self {
         Self {
             id,
-            map: Arc::new(SkipMap::new()),
+            map: new_rep(rep),
+            range_tombstones: RwLock::new(Vec::new()),
             wal: None,
-            approximate_size: Arc::new(AtomicUsize::new(0)),
+            range_tombstones_size: AtomicUsize::new(0),
+            pending_inserts: Mutex::new(0),
+            inserts_done: Condvar::new(),
         }
     }
 
     /// Create a new mem-table with WAL
-    pub fn create_with_wal(id: usize, path: impl AsRef<Path>) -> Result<Self> {
+    pub fn create_with_wal(
+        id: usize,
+        rep: MemTableRepType,
+        path: impl AsRef<Path>,
+    ) -> Result<Self> {
         Ok(Self {
             id,
-            map: Arc::new(SkipMap::new()),
+            map: new_rep(rep),
+            range_tombstones: RwLock::new(Vec::new()),
             wal: Some(Wal::create(path.as_ref())?),
-            approximate_size: Arc::new(AtomicUsize::new(0)),
+            range_tombstones_size: AtomicUsize::new(0),
+            pending_inserts: Mutex::new(0),
+            inserts_done: Condvar::new(),
         })
     }
 
-    /// Create a memtable from WAL
-    pub fn recover_from_wal(id: usize, path: impl AsRef<Path>) -> Result<Self> {
-        let map = Arc::new(SkipMap::new());
-        Ok(Self {
+    /// Create a memtable from WAL. Fails if a batch in the WAL is corrupted or incomplete.
+    pub fn recover_from_wal(
+        id: usize,
+        rep: MemTableRepType,
+        path: impl AsRef<Path>,
+    ) -> Result<Self> {
+        match Self::recover_from_wal_prefix(id, rep, path)? {
+            (memtable, None) => Ok(memtable),
+            (_, Some(corruption)) => Err(corruption.into()),
+        }
+    }
+
+    /// Create a memtable from the batches of the WAL before the first corrupted or incomplete
+    /// one, and return the corruption that stopped the replay.
+    pub(crate) fn recover_from_wal_prefix(
+        id: usize,
+        rep: MemTableRepType,
+        path: impl AsRef<Path>,
+    ) -> Result<(Self, Option<CorruptionError>)> {
+        let map = new_rep(rep);
+        let mut range_tombstones = Vec::new();
+        let (wal, corruption) =
+            Wal::recover(id, path.as_ref(), map.as_ref(), &mut range_tombstones)?;
+        let memtable = Self {
             id,
-            wal: Some(Wal::recover(path.as_ref(), &map)?),
+            wal: Some(wal),
             map,
-            approximate_size: Arc::new(AtomicUsize::new(0)),
-        })
+            range_tombstones_size: AtomicUsize::new(
+                range_tombstones.iter().map(range_tombstone_size).sum(),
+            ),
+            range_tombstones: RwLock::new(range_tombstones),
+            pending_inserts: Mutex::new(0),
+            inserts_done: Condvar::new(),
+        };
+        Ok((memtable, corruption))
     }
 
     /// Get a value by key. Should not be used in week 3.
     pub fn get(&self, key: KeySlice) -> Option<Bytes> {
-        let key_bytes = KeyBytes::from_bytes_with_ts(
-            Bytes::from_static(unsafe { std::mem::transmute::<&[u8], &[u8]>(key.key_ref()) }),
-            key.ts(),
-        );
-        self.map.get(&key_bytes).map(|e| e.value().clone())
+        self.map.get(key)
     }
 
     pub fn for_testing_put_slice(&self, key: &[u8], value: &[u8]) -> Result<()> {
@@ -159,22 +202,102 @@ impl MemTable {
 
     /// Implement this in week 3, day 5.
     pub fn put_batch(&self, data: &[(KeySlice, &[u8])]) -> Result<()> {
-        let mut estimated_size = 0;
+        self.write_batch(data, &[])
+    }
+
+    /// Put key-value pairs and range tombstones into the mem-table as a single WAL batch.
+    pub fn write_batch(
+        &self,
+        data: &[(KeySlice, &[u8])],
+        range_tombstones: &[RangeTombstone],
+    ) -> Result<()> {
+        self.insert_batch(data, range_tombstones);
+        self.write_wal(data, range_tombstones)
+    }
+
+    /// Record `count` batches of a group commit that will be written to the WAL and inserted
+    /// with `insert_pending_batch`. The batches are recorded while the memtable is active, so
+    /// that a freeze or flush after that waits for them.
+    pub(crate) fn add_pending_inserts(&self, count: usize) {
+        *self.pending_inserts.lock() += count;
+    }
+
+    /// Forget `count` batches recorded with `add_pending_inserts`, which are not inserted.
+    pub(crate) fn cancel_pending_inserts(&self, count: usize) {
+        let mut pending_inserts = self.pending_inserts.lock();
+        *pending_inserts -= count;
+        if *pending_inserts == 0 {
+            self.inserts_done.notify_all();
+        }
+    }
+
+    /// Insert a batch recorded with `add_pending_inserts`.
+    pub(crate) fn insert_pending_batch(
+        &self,
+        data: &[(KeySlice, &[u8])],
+        range_tombstones: &[RangeTombstone],
+    ) {
+        self.insert_batch(data, range_tombstones);
+        self.cancel_pending_inserts(1);
+    }
+
+    /// Wait until the batches recorded with `add_pending_inserts` are inserted.
+    fn wait_for_inserts(&self) {
+        let mut pending_inserts = self.pending_inserts.lock();
+        self.inserts_done
+            .wait_while(&mut pending_inserts, |pending| *pending > 0);
+    }
+
+    /// Put key-value pairs and range tombstones into the mem-table without writing them to the
+    /// WAL. Used by group commit, where the leader writes the WAL for the whole group.
+    pub(crate) fn insert_batch(
+        &self,
+        data: &[(KeySlice, &[u8])],
+        range_tombstones: &[RangeTombstone],
+    ) {
         for (key, value) in data {
-            estimated_size += key.raw_len() + value.len();
-            self.map.insert(
-                key.to_key_vec().into_key_bytes(),
-                Bytes::copy_from_slice(value),
-            );
+            self.map.insert(*key, value);
+        }
+        if !range_tombstones.is_empty() {
+            let size = range_tombstones.iter().map(range_tombstone_size).sum();
+            self.range_tombstones
+                .write()
+                .extend_from_slice(range_tombstones);
+            self.range_tombstones_size
+                .fetch_add(size, std::sync::atomic::Ordering::Relaxed);
         }
-        self.approximate_size
-            .fetch_add(estimated_size, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Write key-value pairs and range tombstones to the WAL as a single batch, if the mem-table
+    /// has a WAL.
+    pub(crate) fn write_wal(
+        &self,
+        data: &[(KeySlice, &[u8])],
+        range_tombstones: &[RangeTombstone],
+    ) -> Result<()> {
         if let Some(ref wal) = self.wal {
-            wal.put_batch(data)?;
+            wal.write_batch(data, range_tombstones)?;
         }
         Ok(())
     }
 
+    /// Get the range tombstones in the mem-table.
+    pub fn range_tombstones(&self) -> Vec<RangeTombstone> {
+        self.range_tombstones.read().clone()
+    }
+
+    /// Get the largest timestamp of the keys and range tombstones in the mem-table.
+    pub fn max_ts(&self) -> u64 {
+        let mut max_key_ts = None;
+        let mut iter = self.map.clone().scan(Bound::Unbounded, Bound::Unbounded);
+        while iter.is_valid() {
+            max_key_ts = max_key_ts.max(Some(iter.key().ts()));
+            iter.next();
+        }
+        let max_tombstone_ts = self.range_tombstones.read().iter().map(|x| x.ts).max();
+        max_key_ts.max(max_tombstone_ts).unwrap_or_default()
+    }
+
     pub fn sync_wal(&self) -> Result<()> {
         if let Some(ref wal) = self.wal {
             wal.sync()?;
@@ -184,21 +307,33 @@ impl MemTable {
 
     /// Get an iterator over a range of keys.
     pub fn scan(&self, lower: Bound<KeySlice>, upper: Bound<KeySlice>) -> MemTableIterator {
-        let (lower, upper) = (map_key_bound(lower), map_key_bound(upper));
-        let mut iter = MemTableIteratorBuilder {
-            map: self.map.clone(),
-            iter_builder: |map| map.range((lower, upper)),
-            item: (KeyBytes::new(), Bytes::new()),
+        MemTableIterator {
+            iter: self.map.clone().scan(lower, upper),
         }
-        .build();
-        iter.next().unwrap();
-        iter
+    }
+
+    /// Whether the memtable has a key or a range tombstone in `[first_key, last_key]`.
+    pub(crate) fn overlaps(&self, first_key: &[u8], last_key: &[u8]) -> bool {
+        let iter = self.scan(
+            Bound::Included(KeySlice::from_slice(first_key, TS_RANGE_BEGIN)),
+            Bound::Included(KeySlice::from_slice(last_key, TS_RANGE_END)),
+        );
+        iter.is_valid()
+            || self.range_tombstones.read().iter().any(|tombstone| {
+                tombstone.overlaps(Bound::Included(first_key), Bound::Included(last_key))
+            })
     }
 
     /// Flush the mem-table to SSTable. Implement in week 1 day 6.
     pub fn flush(&self, builder: &mut SsTableBuilder) -> Result<()> {
-        for entry in self.map.iter() {
-            builder.add(entry.key().as_key_slice(), &entry.value()[..]);
+        self.wait_for_inserts();
+        let mut iter = self.map.clone().scan(Bound::Unbounded, Bound::Unbounded);
+        while iter.is_valid() {
+            builder.add(iter.key(), iter.value());
+            iter.next();
+        }
+        for tombstone in self.range_tombstones.read().iter() {
+            builder.add_range_tombstone(tombstone.clone());
         }
         Ok(())
     }
@@ -207,67 +342,66 @@ impl MemTable {
         self.id
     }
 
+    /// The bytes allocated for the entries and range tombstones of the mem-table, including the
+    /// overhead of its representation.
     pub fn approximate_size(&self) -> usize {
-        self.approximate_size
-            .load(std::sync::atomic::Ordering::Relaxed)
+        self.map.allocated_size()
+            + self
+                .range_tombstones_size
+                .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// The memory held by the mem-table, which also counts the space its representation reserved
+    /// but has not used yet.
+    pub fn memory_usage(&self) -> usize {
+        self.map.memory_usage()
+            + self
+                .range_tombstones_size
+                .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Mark the mem-table as immutable, so that its representation can prepare for reads. Waits
+    /// for the pending inserts of group commits, which may write the WAL after the mem-table is
+    /// replaced.
+    pub fn freeze(&self) {
+        self.wait_for_inserts();
+        self.map.freeze();
     }
 
     /// Only use this function when closing the database
     pub fn is_empty(&self) -> bool {
-        self.map.is_empty()
+        self.map.is_empty() && self.range_tombstones.read().is_empty()
     }
 }
 
-type SkipMapRangeIter<'a> = crossbeam_skiplist::map::Range<
-    'a,
-    KeyBytes,
-    (Bound<KeyBytes>, Bound<KeyBytes>),
-    KeyBytes,
-    Bytes,
->;
-
-/// An iterator over a range of `SkipMap`. This is a self-referential structure and please refer to week 1, day 2
-/// chapter for more information.
-///
-/// This is part of week 1, day 2.
-#[self_referencing]
-pub struct MemTableIterator {
-    /// Stores a reference to the skipmap.
-    map: Arc<SkipMap<KeyBytes, Bytes>>,
-    /// Stores a skipmap iterator that refers to the lifetime of `MemTableIterator` itself.
-    #[borrows(map)]
-    #[not_covariant]
-    iter: SkipMapRangeIter<'this>,
-    /// Stores the current key-value pair.
-    item: (KeyBytes, Bytes),
+/// The memory held by a range tombstone of a mem-table.
+fn range_tombstone_size(tombstone: &RangeTombstone) -> usize {
+    std::mem::size_of::<RangeTombstone>() + tombstone.start.len() + tombstone.end.len()
 }
 
-impl MemTableIterator {
-    fn entry_to_item(entry: Option<Entry<'_, KeyBytes, Bytes>>) -> (KeyBytes, Bytes) {
-        entry
-            .map(|x| (x.key().clone(), x.value().clone()))
-            .unwrap_or_else(|| (KeyBytes::new(), Bytes::new()))
-    }
+/// An iterator over a range of the mem-table. It holds a reference to the skiplist, so that it
+/// does not borrow the mem-table.
+pub struct MemTableIterator {
+    iter: Box<dyn MemTableRepIterator>,
 }
 
 impl StorageIterator for MemTableIterator {
     type KeyType<'a> = KeySlice<'a>;
 
     fn value(&self) -> &[u8] {
-        &self.borrow_item().1[..]
+        self.iter.value()
     }
 
     fn key(&self) -> KeySlice {
-        self.borrow_item().0.as_key_slice()
+        self.iter.key()
     }
 
     fn is_valid(&self) -> bool {
-        !self.borrow_item().0.is_empty()
+        self.iter.is_valid()
     }
 
     fn next(&mut self) -> Result<()> {
-        let entry = self.with_iter_mut(|iter| MemTableIterator::entry_to_item(iter.next()));
-        self.with_mut(|x| *x.item = entry);
+        self.iter.next();
         Ok(())
     }
 }
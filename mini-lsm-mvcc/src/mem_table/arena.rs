@@ -0,0 +1,126 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::alloc::Layout;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use parking_lot::Mutex;
+
+/// The size of the first chunk of an arena. Each new chunk doubles the size of the previous one,
+/// up to `MAX_CHUNK_SIZE`.
+const MIN_CHUNK_SIZE: usize = 4 << 10;
+const MAX_CHUNK_SIZE: usize = 1 << 20;
+/// The alignment of chunks, which is the largest alignment an allocation can ask for.
+const CHUNK_ALIGN: usize = 8;
+
+struct Chunks {
+    /// The chunks allocated so far, freed together when the arena is dropped.
+    chunks: Vec<(NonNull<u8>, Layout)>,
+    /// The free space at the end of the last chunk.
+    next: *mut u8,
+    remaining: usize,
+}
+
+/// A bump allocator. Allocations are never freed individually: the memory of all of them is
+/// released at once when the arena is dropped.
+pub(crate) struct Arena {
+    chunks: Mutex<Chunks>,
+    /// Bytes handed out by `alloc`, excluding alignment padding.
+    allocated: AtomicUsize,
+    /// Bytes of the chunks, allocated from the system.
+    reserved: AtomicUsize,
+}
+
+// SAFETY: the pointers in `Chunks` are owned by the arena, and only accessed under the mutex.
+unsafe impl Send for Arena {}
+unsafe impl Sync for Arena {}
+
+impl Arena {
+    pub(crate) fn new() -> Self {
+        Self {
+            chunks: Mutex::new(Chunks {
+                chunks: Vec::new(),
+                next: std::ptr::null_mut(),
+                remaining: 0,
+            }),
+            allocated: AtomicUsize::new(0),
+            reserved: AtomicUsize::new(0),
+        }
+    }
+
+    /// Allocate uninitialized memory of the given layout. The memory is valid until the arena is
+    /// dropped.
+    pub(crate) fn alloc(&self, layout: Layout) -> NonNull<u8> {
+        assert!(layout.align() <= CHUNK_ALIGN);
+        if layout.size() == 0 {
+            return NonNull::dangling();
+        }
+        let mut chunks = self.chunks.lock();
+        let padding = chunks.next.align_offset(layout.align());
+        let size = if chunks.remaining >= padding + layout.size() {
+            padding + layout.size()
+        } else {
+            let last_size = chunks.chunks.last().map_or(0, |(_, layout)| layout.size());
+            let chunk_size = (last_size * 2)
+                .clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE)
+                .max(layout.size());
+            let chunk_layout = Layout::from_size_align(chunk_size, CHUNK_ALIGN).unwrap();
+            // SAFETY: the layout has a non-zero size.
+            let chunk = unsafe { std::alloc::alloc(chunk_layout) };
+            let Some(chunk) = NonNull::new(chunk) else {
+                std::alloc::handle_alloc_error(chunk_layout);
+            };
+            chunks.chunks.push((chunk, chunk_layout));
+            chunks.next = chunk.as_ptr();
+            chunks.remaining = chunk_size;
+            self.reserved.fetch_add(chunk_size, Ordering::Relaxed);
+            layout.size()
+        };
+        // SAFETY: `size` bytes are left in the chunk.
+        let ptr = unsafe { chunks.next.add(size - layout.size()) };
+        chunks.next = unsafe { chunks.next.add(size) };
+        chunks.remaining -= size;
+        self.allocated.fetch_add(layout.size(), Ordering::Relaxed);
+        // SAFETY: `ptr` is in a chunk, which is not null.
+        unsafe { NonNull::new_unchecked(ptr) }
+    }
+
+    /// Copy `data` into the arena.
+    pub(crate) fn alloc_slice(&self, data: &[u8]) -> NonNull<u8> {
+        let ptr = self.alloc(Layout::array::<u8>(data.len()).unwrap());
+        // SAFETY: the allocation has room for `data`, and does not overlap it.
+        unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), ptr.as_ptr(), data.len()) };
+        ptr
+    }
+
+    /// Bytes handed out by the arena, excluding alignment padding.
+    pub(crate) fn allocated(&self) -> usize {
+        self.allocated.load(Ordering::Relaxed)
+    }
+
+    /// Bytes of memory held by the arena, which is at least `allocated`.
+    pub(crate) fn reserved(&self) -> usize {
+        self.reserved.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        for (chunk, layout) in self.chunks.get_mut().chunks.drain(..) {
+            // SAFETY: the chunk was allocated with `layout` in `alloc`.
+            unsafe { std::alloc::dealloc(chunk.as_ptr(), layout) };
+        }
+    }
+}
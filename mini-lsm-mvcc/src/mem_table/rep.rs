@@ -0,0 +1,73 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Bound;
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::key::KeySlice;
+
+/// The data structure holding the entries of a mem-table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemTableRepType {
+    /// A concurrent skiplist, which keeps the entries sorted as they are inserted.
+    #[default]
+    SkipList,
+    /// An append-only vector, which is sorted when the mem-table is frozen or read. Inserts are
+    /// cheaper than with a skiplist, but each read after an insert sorts the whole vector, so it
+    /// only suits bulk loads that do not read until the load finishes.
+    Vector,
+}
+
+/// The entries of a mem-table, ordered by `KeySlice`. Range tombstones and the WAL are kept by
+/// `MemTable` itself.
+pub trait MemTableRep: Send + Sync {
+    /// Insert an entry, replacing the value if the key is already in the mem-table.
+    fn insert(&self, key: KeySlice, value: &[u8]);
+
+    fn get(&self, key: KeySlice) -> Option<Bytes>;
+
+    /// Get an iterator over a range of keys, in order.
+    fn scan(
+        self: Arc<Self>,
+        lower: Bound<KeySlice>,
+        upper: Bound<KeySlice>,
+    ) -> Box<dyn MemTableRepIterator>;
+
+    fn is_empty(&self) -> bool;
+
+    /// Bytes allocated for the entries, including the overhead of the data structure.
+    fn allocated_size(&self) -> usize;
+
+    /// Bytes of memory held by the data structure, which is at least `allocated_size`.
+    fn memory_usage(&self) -> usize;
+
+    /// Called when the mem-table becomes immutable. Entries may still be inserted by writes that
+    /// started before the freeze.
+    fn freeze(&self) {}
+}
+
+/// An iterator over the entries of a `MemTableRep`, which does not borrow the representation.
+pub trait MemTableRepIterator: Send + Sync {
+    /// The current key, which is empty at the end.
+    fn key(&self) -> KeySlice<'_>;
+
+    /// The current value, which is empty at the end.
+    fn value(&self) -> &[u8];
+
+    fn is_valid(&self) -> bool;
+
+    fn next(&mut self);
+}
@@ -0,0 +1,376 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::alloc::Layout;
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::ops::Bound;
+use std::ptr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use bytes::Bytes;
+
+use super::arena::Arena;
+use super::rep::{MemTableRep, MemTableRepIterator};
+use crate::key::KeySlice;
+
+const MAX_HEIGHT: usize = 12;
+
+thread_local! {
+    /// The state of the xorshift generator of node heights, seeded randomly for each thread.
+    static HEIGHT_RNG: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+/// A node of the skiplist, followed in the arena by `height` next pointers. The key is immutable
+/// once the node is linked, the value may be replaced by a newer one.
+#[repr(C)]
+struct Node {
+    key: *const u8,
+    key_len: usize,
+    ts: u64,
+    /// Points to the length of the value as a `usize`, followed by the value.
+    value: AtomicPtr<u8>,
+    height: usize,
+    tower: [AtomicPtr<Node>; 0],
+}
+
+impl Node {
+    /// The next pointer of `node` at `level`. The pointers are computed from the raw pointer of
+    /// the node, as they lie past the `Node` itself, outside any reference to it.
+    ///
+    /// # Safety
+    ///
+    /// `node` must point to a node of a live list, and `level` must be below its height.
+    unsafe fn next<'a>(node: *const Node, level: usize) -> &'a AtomicPtr<Node> {
+        // SAFETY: the node was allocated with `height` next pointers after it.
+        unsafe {
+            debug_assert!(level < (*node).height);
+            &*ptr::addr_of!((*node).tower)
+                .cast::<AtomicPtr<Node>>()
+                .add(level)
+        }
+    }
+
+    fn key(&self) -> KeySlice<'_> {
+        // SAFETY: the key was copied into the arena, which outlives the node.
+        KeySlice::from_slice(
+            unsafe { std::slice::from_raw_parts(self.key, self.key_len) },
+            self.ts,
+        )
+    }
+
+    fn value(&self) -> &[u8] {
+        let value = self.value.load(Ordering::Acquire);
+        // SAFETY: values are written by `SkipList::alloc_value` before they are published.
+        unsafe {
+            let len = value.cast::<usize>().read();
+            std::slice::from_raw_parts(value.add(size_of::<usize>()), len)
+        }
+    }
+}
+
+/// A concurrent skiplist ordered by `KeySlice`, whose nodes, keys and values are allocated in an
+/// arena. Entries are never removed, and all of them are freed together when the list is dropped.
+pub(crate) struct SkipList {
+    arena: Arena,
+    head: *const Node,
+    /// The height of the tallest node.
+    height: AtomicUsize,
+    /// Bytes of the next pointers of the nodes above the first level, whose number is random.
+    upper_tower_size: AtomicUsize,
+}
+
+// SAFETY: nodes are only modified through atomics, and live as long as the arena.
+unsafe impl Send for SkipList {}
+unsafe impl Sync for SkipList {}
+
+impl SkipList {
+    pub(crate) fn new() -> Self {
+        let arena = Arena::new();
+        let head = Self::alloc_node(&arena, KeySlice::default(), ptr::null_mut(), MAX_HEIGHT);
+        Self {
+            arena,
+            head,
+            height: AtomicUsize::new(1),
+            upper_tower_size: AtomicUsize::new(0),
+        }
+    }
+
+    fn alloc_node(arena: &Arena, key: KeySlice, value: *mut u8, height: usize) -> *const Node {
+        let layout = Layout::new::<Node>()
+            .extend(Layout::array::<AtomicPtr<Node>>(height).unwrap())
+            .unwrap()
+            .0;
+        let node = arena.alloc(layout).cast::<Node>().as_ptr();
+        let key_ref = key.key_ref();
+        // SAFETY: the allocation has room for the node and its next pointers.
+        unsafe {
+            node.write(Node {
+                key: arena.alloc_slice(key_ref).as_ptr(),
+                key_len: key_ref.len(),
+                ts: key.ts(),
+                value: AtomicPtr::new(value),
+                height,
+                tower: [],
+            });
+            let tower = ptr::addr_of_mut!((*node).tower).cast::<AtomicPtr<Node>>();
+            for level in 0..height {
+                tower.add(level).write(AtomicPtr::new(ptr::null_mut()));
+            }
+        }
+        node
+    }
+
+    fn alloc_value(&self, value: &[u8]) -> *mut u8 {
+        let layout = Layout::new::<usize>()
+            .extend(Layout::array::<u8>(value.len()).unwrap())
+            .unwrap()
+            .0;
+        let ptr = self.arena.alloc(layout).as_ptr();
+        // SAFETY: the allocation has room for the length and the value.
+        unsafe {
+            ptr.cast::<usize>().write(value.len());
+            ptr::copy_nonoverlapping(value.as_ptr(), ptr.add(size_of::<usize>()), value.len());
+        }
+        ptr
+    }
+
+    /// A random height for a new node.
+    fn random_height() -> usize {
+        let random = HEIGHT_RNG.with(|rng| {
+            let mut x = rng.get();
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            rng.set(x);
+            x
+        });
+        // Each level has a quarter of the nodes of the level below.
+        let height = 1 + random.trailing_zeros() as usize / 2;
+        height.min(MAX_HEIGHT)
+    }
+
+    /// Starting from `before` at `level`, find the last node whose key is smaller than `key`
+    /// and the node after it, which is null at the end of the level.
+    fn find_splice_for_level(
+        &self,
+        key: KeySlice,
+        level: usize,
+        mut before: *const Node,
+    ) -> (*const Node, *const Node) {
+        loop {
+            // SAFETY: nodes are never freed while the list is alive.
+            let next = unsafe { Node::next(before, level).load(Ordering::Acquire) };
+            if next.is_null() || unsafe { (*next).key() } >= key {
+                return (before, next);
+            }
+            before = next;
+        }
+    }
+
+    /// The first node whose key is at least `key`, or null.
+    fn seek_node(&self, key: KeySlice) -> *const Node {
+        let mut before = self.head;
+        for level in (0..self.height.load(Ordering::Acquire)).rev() {
+            before = self.find_splice_for_level(key, level, before).0;
+        }
+        self.find_splice_for_level(key, 0, before).1
+    }
+
+    /// Get the value of `key`.
+    pub(crate) fn get(&self, key: KeySlice) -> Option<&[u8]> {
+        let node = self.seek_node(key);
+        if node.is_null() {
+            return None;
+        }
+        // SAFETY: nodes are never freed while the list is alive.
+        let node = unsafe { &*node };
+        (node.key() == key).then(|| node.value())
+    }
+
+    /// Iterate over the keys within the bounds. The iterator keeps the list alive.
+    pub(crate) fn range(
+        self: &Arc<Self>,
+        lower: Bound<KeySlice>,
+        upper: Bound<KeySlice>,
+    ) -> SkipListIter {
+        let node = match lower {
+            Bound::Included(key) => self.seek_node(key),
+            Bound::Excluded(key) => {
+                let node = self.seek_node(key);
+                // SAFETY: nodes are never freed while the list is alive.
+                if !node.is_null() && unsafe { (*node).key() } == key {
+                    unsafe { Node::next(node, 0).load(Ordering::Acquire) }
+                } else {
+                    node
+                }
+            }
+            // SAFETY: the head is allocated in the arena of the list.
+            Bound::Unbounded => unsafe { Node::next(self.head, 0).load(Ordering::Acquire) },
+        };
+        let mut iter = SkipListIter {
+            _list: self.clone(),
+            node,
+            upper: upper.map(|key| (key.key_ref().to_vec(), key.ts())),
+        };
+        iter.check_upper();
+        iter
+    }
+}
+
+impl MemTableRep for SkipList {
+    fn insert(&self, key: KeySlice, value: &[u8]) {
+        let list_height = self.height.load(Ordering::Acquire);
+        let mut preds = [self.head; MAX_HEIGHT];
+        let mut succs = [ptr::null(); MAX_HEIGHT];
+        let mut before = self.head;
+        for level in (0..list_height).rev() {
+            (preds[level], succs[level]) = self.find_splice_for_level(key, level, before);
+            before = preds[level];
+        }
+        let value = self.alloc_value(value);
+        let succ = succs[0];
+        if !succ.is_null() && unsafe { (*succ).key() } == key {
+            unsafe { (*succ).value.store(value, Ordering::Release) };
+            return;
+        }
+
+        let height = Self::random_height();
+        self.height.fetch_max(height, Ordering::AcqRel);
+        let node = Self::alloc_node(&self.arena, key, value, height);
+        self.upper_tower_size.fetch_add(
+            (height - 1) * size_of::<AtomicPtr<Node>>(),
+            Ordering::Relaxed,
+        );
+        for level in 0..height {
+            loop {
+                let (pred, succ) = (preds[level], succs[level]);
+                // SAFETY: `node` is not reachable at this level yet, and `pred` is alive.
+                unsafe {
+                    Node::next(node, level).store(succ.cast_mut(), Ordering::Relaxed);
+                    if Node::next(pred, level)
+                        .compare_exchange(
+                            succ.cast_mut(),
+                            node.cast_mut(),
+                            Ordering::AcqRel,
+                            Ordering::Acquire,
+                        )
+                        .is_ok()
+                    {
+                        break;
+                    }
+                }
+                // A concurrent insert changed the level, find the splice again.
+                (preds[level], succs[level]) = self.find_splice_for_level(key, level, pred);
+                let succ = succs[level];
+                if level == 0 && !succ.is_null() && unsafe { (*succ).key() } == key {
+                    // The key was inserted concurrently, the node is left unused in the arena.
+                    unsafe { (*succ).value.store(value, Ordering::Release) };
+                    return;
+                }
+            }
+        }
+    }
+
+    fn get(&self, key: KeySlice) -> Option<Bytes> {
+        SkipList::get(self, key).map(Bytes::copy_from_slice)
+    }
+
+    fn scan(
+        self: Arc<Self>,
+        lower: Bound<KeySlice>,
+        upper: Bound<KeySlice>,
+    ) -> Box<dyn MemTableRepIterator> {
+        Box::new(self.range(lower, upper))
+    }
+
+    fn is_empty(&self) -> bool {
+        // SAFETY: the head is allocated in the arena of the list.
+        unsafe { Node::next(self.head, 0).load(Ordering::Acquire).is_null() }
+    }
+
+    /// Bytes allocated from the arena for the nodes, keys and values, including replaced values,
+    /// excluding alignment padding. The next pointers of the nodes above the first level are not
+    /// counted, so that the same entries always have the same size, e.g. after WAL recovery.
+    fn allocated_size(&self) -> usize {
+        self.arena
+            .allocated()
+            .saturating_sub(self.upper_tower_size.load(Ordering::Relaxed))
+    }
+
+    /// Bytes of the chunks of the arena.
+    fn memory_usage(&self) -> usize {
+        self.arena.reserved()
+    }
+}
+
+/// An iterator over a `SkipList`, which holds a reference to the list instead of borrowing it.
+pub(crate) struct SkipListIter {
+    /// Keeps the arena holding `node` alive.
+    _list: Arc<SkipList>,
+    /// The current node, or null at the end.
+    node: *const Node,
+    upper: Bound<(Vec<u8>, u64)>,
+}
+
+// SAFETY: the node is kept alive by `_list`, and only read through atomics or immutable fields.
+unsafe impl Send for SkipListIter {}
+unsafe impl Sync for SkipListIter {}
+
+impl SkipListIter {
+    fn current(&self) -> Option<&Node> {
+        // SAFETY: the node is in the arena of `_list`.
+        unsafe { self.node.as_ref() }
+    }
+
+    /// End the iteration once the current key is past the upper bound.
+    fn check_upper(&mut self) {
+        if self.node.is_null() {
+            return;
+        }
+        let key = self.key();
+        let in_range = match &self.upper {
+            Bound::Included((upper, ts)) => key <= KeySlice::from_slice(upper, *ts),
+            Bound::Excluded((upper, ts)) => key < KeySlice::from_slice(upper, *ts),
+            Bound::Unbounded => true,
+        };
+        if !in_range {
+            self.node = ptr::null();
+        }
+    }
+}
+
+impl MemTableRepIterator for SkipListIter {
+    fn is_valid(&self) -> bool {
+        !self.node.is_null()
+    }
+
+    fn key(&self) -> KeySlice<'_> {
+        self.current().map(Node::key).unwrap_or_default()
+    }
+
+    fn value(&self) -> &[u8] {
+        self.current().map_or(&[], Node::value)
+    }
+
+    fn next(&mut self) {
+        if !self.node.is_null() {
+            // SAFETY: the node is in the arena of `_list`.
+            self.node = unsafe { Node::next(self.node, 0).load(Ordering::Acquire) };
+            self.check_upper();
+        }
+    }
+}
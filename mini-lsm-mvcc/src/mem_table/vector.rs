@@ -0,0 +1,231 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Bound;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::rep::{MemTableRep, MemTableRepIterator};
+use crate::key::{KeyBytes, KeySlice};
+
+type Entry = (KeyBytes, Bytes);
+
+/// Reads merge the unsorted entries into the sorted ones once there are more of them than this.
+const MAX_UNSORTED_ENTRIES: usize = 256;
+
+struct Entries {
+    /// Sorted without duplicate keys. Shared with the iterators, and replaced when the unsorted
+    /// entries are merged into it.
+    sorted: Arc<Vec<Entry>>,
+    /// Inserted since the last merge, in insertion order.
+    unsorted: Vec<Entry>,
+    /// Bytes of the keys and values in `sorted` and `unsorted`.
+    data_size: usize,
+}
+
+/// An append-only mem-table representation. Inserts are appended to a tail that is sorted on
+/// freeze, and reads sort a copy of the tail, which is kept small by merging it into the sorted
+/// entries once it grows past `MAX_UNSORTED_ENTRIES`.
+pub(crate) struct VectorRep {
+    entries: RwLock<Entries>,
+}
+
+/// Sort `entries` by key. The latest value of a key is kept.
+fn sort_entries(mut entries: Vec<Entry>) -> Vec<Entry> {
+    // The sort is stable, so after reversing, the latest value of a key comes first and is the
+    // one kept by `dedup_by`.
+    entries.reverse();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries.dedup_by(|a, b| a.0 == b.0);
+    entries
+}
+
+/// The range of the sorted `data` between `lower` and `upper`.
+fn range_of(data: &[Entry], lower: Bound<KeySlice>, upper: Bound<KeySlice>) -> (usize, usize) {
+    let start = data.partition_point(|(k, _)| match lower {
+        Bound::Included(lower) => k.as_key_slice() < lower,
+        Bound::Excluded(lower) => k.as_key_slice() <= lower,
+        Bound::Unbounded => false,
+    });
+    let end = data.partition_point(|(k, _)| match upper {
+        Bound::Included(upper) => k.as_key_slice() <= upper,
+        Bound::Excluded(upper) => k.as_key_slice() < upper,
+        Bound::Unbounded => true,
+    });
+    (start, end.max(start))
+}
+
+impl VectorRep {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: RwLock::new(Entries {
+                sorted: Arc::new(Vec::new()),
+                unsorted: Vec::new(),
+                data_size: 0,
+            }),
+        }
+    }
+
+    /// Merge the unsorted entries into the sorted ones.
+    fn merge_unsorted(entries: &mut Entries) {
+        if entries.unsorted.is_empty() {
+            return;
+        }
+        let unsorted = sort_entries(std::mem::take(&mut entries.unsorted));
+        let mut merged = Vec::with_capacity(entries.sorted.len() + unsorted.len());
+        let mut sorted = entries.sorted.iter().peekable();
+        for entry in unsorted {
+            while let Some(older) = sorted.next_if(|older| older.0 < entry.0) {
+                merged.push(older.clone());
+            }
+            sorted.next_if(|older| older.0 == entry.0);
+            merged.push(entry);
+        }
+        merged.extend(sorted.cloned());
+        entries.data_size = merged.iter().map(|(k, v)| k.raw_len() + v.len()).sum();
+        entries.sorted = Arc::new(merged);
+    }
+
+    /// Lock the entries for a read, merging the unsorted entries first if there are too many.
+    fn read(&self) -> RwLockReadGuard<'_, Entries> {
+        let entries = self.entries.read();
+        if entries.unsorted.len() <= MAX_UNSORTED_ENTRIES {
+            return entries;
+        }
+        drop(entries);
+        let mut entries = self.entries.write();
+        Self::merge_unsorted(&mut entries);
+        RwLockWriteGuard::downgrade(entries)
+    }
+}
+
+impl MemTableRep for VectorRep {
+    fn insert(&self, key: KeySlice, value: &[u8]) {
+        let mut entries = self.entries.write();
+        entries.data_size += key.raw_len() + value.len();
+        entries.unsorted.push((
+            key.to_key_vec().into_key_bytes(),
+            Bytes::copy_from_slice(value),
+        ));
+    }
+
+    fn get(&self, key: KeySlice) -> Option<Bytes> {
+        let entries = self.read();
+        if let Some((_, value)) = entries
+            .unsorted
+            .iter()
+            .rev()
+            .find(|(k, _)| k.as_key_slice() == key)
+        {
+            return Some(value.clone());
+        }
+        let idx = entries
+            .sorted
+            .binary_search_by(|(k, _)| k.as_key_slice().cmp(&key))
+            .ok()?;
+        Some(entries.sorted[idx].1.clone())
+    }
+
+    fn scan(
+        self: Arc<Self>,
+        lower: Bound<KeySlice>,
+        upper: Bound<KeySlice>,
+    ) -> Box<dyn MemTableRepIterator> {
+        let entries = self.read();
+        let (idx, end) = range_of(&entries.sorted, lower, upper);
+        let mut unsorted = sort_entries(entries.unsorted.clone());
+        let (start, unsorted_end) = range_of(&unsorted, lower, upper);
+        unsorted.truncate(unsorted_end);
+        unsorted.drain(..start);
+        Box::new(VectorRepIterator {
+            data: entries.sorted.clone(),
+            idx,
+            end,
+            unsorted,
+            unsorted_idx: 0,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        let entries = self.entries.read();
+        entries.sorted.is_empty() && entries.unsorted.is_empty()
+    }
+
+    fn allocated_size(&self) -> usize {
+        let entries = self.entries.read();
+        (entries.sorted.len() + entries.unsorted.len()) * size_of::<Entry>() + entries.data_size
+    }
+
+    fn memory_usage(&self) -> usize {
+        let entries = self.entries.read();
+        (entries.sorted.capacity() + entries.unsorted.capacity()) * size_of::<Entry>()
+            + entries.data_size
+    }
+
+    fn freeze(&self) {
+        Self::merge_unsorted(&mut self.entries.write());
+    }
+}
+
+struct VectorRepIterator {
+    data: Arc<Vec<Entry>>,
+    idx: usize,
+    end: usize,
+    /// Sorted copy of the entries that were not merged into `data`, which replace the entries of
+    /// `data` with the same key.
+    unsorted: Vec<Entry>,
+    unsorted_idx: usize,
+}
+
+impl VectorRepIterator {
+    fn current(&self) -> Option<&Entry> {
+        let sorted = self.data[self.idx..self.end].first();
+        match (sorted, self.unsorted.get(self.unsorted_idx)) {
+            (Some(sorted), Some(unsorted)) if sorted.0 < unsorted.0 => Some(sorted),
+            (_, Some(unsorted)) => Some(unsorted),
+            (sorted, None) => sorted,
+        }
+    }
+}
+
+impl MemTableRepIterator for VectorRepIterator {
+    fn key(&self) -> KeySlice<'_> {
+        self.current()
+            .map_or_else(KeySlice::default, |(k, _)| k.as_key_slice())
+    }
+
+    fn value(&self) -> &[u8] {
+        self.current().map_or(&[], |(_, v)| v)
+    }
+
+    fn is_valid(&self) -> bool {
+        self.idx < self.end || self.unsorted_idx < self.unsorted.len()
+    }
+
+    fn next(&mut self) {
+        let sorted = self.data[self.idx..self.end].first();
+        match (sorted, self.unsorted.get(self.unsorted_idx)) {
+            (Some(sorted), Some(unsorted)) if sorted.0 < unsorted.0 => self.idx += 1,
+            (Some(sorted), Some(unsorted)) if sorted.0 == unsorted.0 => {
+                self.idx += 1;
+                self.unsorted_idx += 1;
+            }
+            (_, Some(_)) => self.unsorted_idx += 1,
+            (Some(_), None) => self.idx += 1,
+            (None, None) => {}
+        }
+    }
+}
@@ -32,6 +32,9 @@ use self::{txn::Transaction, watermark::Watermark};
 
 pub(crate) struct CommittedTxnData {
     pub(crate) key_hashes: HashSet<u32>,
+    /// Whether the transaction wrote keys that are not in `key_hashes`, by deleting a range of
+    /// keys or by ingesting files.
+    pub(crate) writes_untracked_keys: bool,
     #[allow(dead_code)]
     pub(crate) read_ts: u64,
     #[allow(dead_code)]
@@ -78,6 +81,7 @@ impl LsmMvccInner {
             read_ts,
             local_storage: Arc::new(SkipMap::new()),
             committed: Arc::new(AtomicBool::new(false)),
+            local_range_deletes: Mutex::new(Vec::new()),
             key_hashes: if serializable {
                 Some(Mutex::new((HashSet::new(), HashSet::new())))
             } else {
@@ -30,7 +30,7 @@ use parking_lot::Mutex;
 use crate::{
     iterators::{StorageIterator, two_merge_iterator::TwoMergeIterator},
     lsm_iterator::{FusedIterator, LsmIterator},
-    lsm_storage::{LsmStorageInner, WriteBatchRecord},
+    lsm_storage::{LsmStorageInner, WriteBatchRecord, prefix_upper_bound},
     mem_table::map_bound,
     mvcc::CommittedTxnData,
 };
@@ -42,6 +42,8 @@ pub struct Transaction {
     pub(crate) committed: Arc<AtomicBool>,
     /// Write set and read set
     pub(crate) key_hashes: Option<Mutex<(HashSet<u32>, HashSet<u32>)>>,
+    /// Ranges deleted by the transaction, as `[start, end)`.
+    pub(crate) local_range_deletes: Mutex<Vec<(Bytes, Bytes)>>,
 }
 
 impl Transaction {
@@ -61,10 +63,41 @@ impl Transaction {
                 return Ok(Some(entry.value().clone()));
             }
         }
+        if self.is_range_deleted_locally(key) {
+            return Ok(None);
+        }
         self.inner.get_with_ts(key, self.read_ts)
     }
 
+    /// Check whether `key` is deleted by `delete_range` in this transaction and not written again
+    /// afterwards.
+    fn is_range_deleted_locally(&self, key: &[u8]) -> bool {
+        let ranges = self.local_range_deletes.lock();
+        !ranges.is_empty()
+            && !self.local_storage.contains_key(key)
+            && ranges.iter().any(|(start, end)| start <= key && key < end)
+    }
+
     pub fn scan(self: &Arc<Self>, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> Result<TxnIterator> {
+        self.scan_inner(lower, upper, None)
+    }
+
+    /// Scan all keys starting with `prefix`.
+    pub fn scan_prefix(self: &Arc<Self>, prefix: &[u8]) -> Result<TxnIterator> {
+        let upper = prefix_upper_bound(prefix);
+        let upper = match &upper {
+            Some(upper) => Bound::Excluded(upper.as_slice()),
+            None => Bound::Unbounded,
+        };
+        self.scan_inner(Bound::Included(prefix), upper, Some(prefix))
+    }
+
+    fn scan_inner(
+        self: &Arc<Self>,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+        prefix: Option<&[u8]>,
+    ) -> Result<TxnIterator> {
         if self.committed.load(Ordering::SeqCst) {
             panic!("cannot operate on committed txn!");
         }
@@ -81,7 +114,8 @@ impl Transaction {
             self.clone(),
             TwoMergeIterator::create(
                 local_iter,
-                self.inner.scan_with_ts(lower, upper, self.read_ts)?,
+                self.inner
+                    .scan_with_ts(lower, upper, prefix, self.read_ts)?,
             )?,
         )
     }
@@ -112,6 +146,24 @@ impl Transaction {
         }
     }
 
+    /// Delete all keys in `[lower, upper)`, including the ones written earlier in this transaction.
+    pub fn delete_range(&self, lower: &[u8], upper: &[u8]) {
+        if self.committed.load(Ordering::SeqCst) {
+            panic!("cannot operate on committed txn!");
+        }
+        if lower < upper {
+            for entry in self
+                .local_storage
+                .range::<[u8], _>((Bound::Included(lower), Bound::Excluded(upper)))
+            {
+                entry.remove();
+            }
+        }
+        self.local_range_deletes
+            .lock()
+            .push((Bytes::copy_from_slice(lower), Bytes::copy_from_slice(upper)));
+    }
+
     pub fn commit(&self) -> Result<()> {
         self.committed
             .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
@@ -125,9 +177,13 @@ impl Transaction {
                 "commit txn: write_set: {:?}, read_set: {:?}",
                 write_set, read_set
             );
-            if !write_set.is_empty() {
+            if !write_set.is_empty() || !self.local_range_deletes.lock().is_empty() {
                 let committed_txns = self.inner.mvcc().committed_txns.lock();
                 for (_, txn_data) in committed_txns.range((self.read_ts + 1)..) {
+                    // The keys written are unknown, so assume they were read.
+                    if txn_data.writes_untracked_keys && !read_set.is_empty() {
+                        bail!("serializable check failed");
+                    }
                     for key_hash in read_set {
                         if txn_data.key_hashes.contains(key_hash) {
                             bail!("serializable check failed");
@@ -139,16 +195,20 @@ impl Transaction {
         } else {
             serializability_check = false;
         }
-        let batch = self
-            .local_storage
-            .iter()
-            .map(|entry| {
+        let range_deletes = std::mem::take(&mut *self.local_range_deletes.lock());
+        let has_range_deletes = !range_deletes.is_empty();
+        // Range tombstones only delete versions older than the commit ts, so keys written after
+        // `delete_range` in this transaction are kept.
+        let batch = range_deletes
+            .into_iter()
+            .map(|(start, end)| WriteBatchRecord::DelRange(start, end))
+            .chain(self.local_storage.iter().map(|entry| {
                 if entry.value().is_empty() {
                     WriteBatchRecord::Del(entry.key().clone())
                 } else {
                     WriteBatchRecord::Put(entry.key().clone(), entry.value().clone())
                 }
-            })
+            }))
             .collect::<Vec<_>>();
         let ts = self.inner.write_batch_inner(&batch)?;
         if serializability_check {
@@ -160,6 +220,7 @@ impl Transaction {
                 ts,
                 CommittedTxnData {
                     key_hashes: std::mem::take(write_set),
+                    writes_untracked_keys: has_range_deletes,
                     read_ts: self.read_ts,
                     commit_ts: ts,
                 },
@@ -250,7 +311,9 @@ impl TxnIterator {
     }
 
     fn skip_deletes(&mut self) -> Result<()> {
-        while self.iter.is_valid() && self.iter.value().is_empty() {
+        while self.iter.is_valid()
+            && (self.iter.value().is_empty() || self.txn.is_range_deleted_locally(self.iter.key()))
+        {
             self.iter.next()?;
         }
         Ok(())
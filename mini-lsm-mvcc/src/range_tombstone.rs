@@ -0,0 +1,104 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Bound;
+
+use bytes::{Buf, BufMut, Bytes};
+
+use crate::varint::{get_varint, put_varint, varint_len};
+
+/// Deletes all versions of the keys in `[start, end)` written before `ts`. Versions written at
+/// `ts` itself, i.e. in the same write batch, are not deleted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeTombstone {
+    pub start: Bytes,
+    pub end: Bytes,
+    pub ts: u64,
+}
+
+impl RangeTombstone {
+    pub fn new(start: &[u8], end: &[u8], ts: u64) -> Self {
+        Self {
+            start: Bytes::copy_from_slice(start),
+            end: Bytes::copy_from_slice(end),
+            ts,
+        }
+    }
+
+    /// Check whether `key` is in `[start, end)`.
+    pub fn contains(&self, key: &[u8]) -> bool {
+        self.start <= key && key < self.end
+    }
+
+    /// Check whether the version of `key` at `ts` is deleted by this tombstone.
+    pub fn covers(&self, key: &[u8], ts: u64) -> bool {
+        ts < self.ts && self.contains(key)
+    }
+
+    /// Check whether any key in the given range is in `[start, end)`.
+    pub fn overlaps(&self, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> bool {
+        let below_upper = match upper {
+            Bound::Included(key) => self.start <= key,
+            Bound::Excluded(key) => self.start < key,
+            Bound::Unbounded => true,
+        };
+        let above_lower = match lower {
+            Bound::Included(key) | Bound::Excluded(key) => key < self.end,
+            Bound::Unbounded => true,
+        };
+        below_upper && above_lower
+    }
+
+    /// The part of this tombstone in `[lower, upper)`, where `None` is unbounded.
+    pub(crate) fn clip(&self, lower: Option<&[u8]>, upper: Option<&[u8]>) -> Option<Self> {
+        let start = match lower {
+            Some(lower) if lower > self.start => Bytes::copy_from_slice(lower),
+            _ => self.start.clone(),
+        };
+        let end = match upper {
+            Some(upper) if upper < self.end => Bytes::copy_from_slice(upper),
+            _ => self.end.clone(),
+        };
+        (start < end).then_some(Self {
+            start,
+            end,
+            ts: self.ts,
+        })
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        put_varint(buf, self.start.len() as u64);
+        buf.put_slice(&self.start);
+        put_varint(buf, self.end.len() as u64);
+        buf.put_slice(&self.end);
+        buf.put_u64(self.ts);
+    }
+
+    pub(crate) fn encoded_len(&self) -> usize {
+        varint_len(self.start.len() as u64)
+            + self.start.len()
+            + varint_len(self.end.len() as u64)
+            + self.end.len()
+            + std::mem::size_of::<u64>()
+    }
+
+    pub(crate) fn decode(buf: &mut &[u8]) -> Self {
+        let start_len = get_varint(buf) as usize;
+        let start = buf.copy_to_bytes(start_len);
+        let end_len = get_varint(buf) as usize;
+        let end = buf.copy_to_bytes(end_len);
+        let ts = buf.get_u64();
+        Self { start, end, ts }
+    }
+}
@@ -14,7 +14,15 @@
 
 pub(crate) mod bloom;
 mod builder;
+pub mod cache;
+pub mod compression;
+pub mod direct_io;
+pub mod filter;
+pub mod footer;
+pub mod index;
 mod iterator;
+pub mod prefix;
+pub mod properties;
 
 use std::fs::File;
 use std::path::Path;
@@ -22,14 +30,38 @@ use std::sync::Arc;
 
 use anyhow::{Result, anyhow, bail};
 pub use builder::SsTableBuilder;
-use bytes::{Buf, BufMut};
+use bytes::{Buf, BufMut, Bytes};
 pub use iterator::SsTableIterator;
 
-use crate::block::Block;
-use crate::key::{KeyBytes, KeySlice};
+use crate::block::{Block, BlockIterator, BlockLayout};
+use crate::error::{CorruptedFile, CorruptedSection, CorruptionError};
+use crate::key::{KeyBytes, KeySlice, TS_RANGE_BEGIN, TS_RANGE_END};
 use crate::lsm_storage::BlockCache;
+use crate::range_tombstone::RangeTombstone;
+use crate::varint::{get_varint, put_varint, varint_len};
 
 use self::bloom::Bloom;
+use self::cache::{CachedBlock, FILTER_BLOCK_IDX, INDEX_BLOCK_IDX};
+use self::compression::CompressionType;
+use self::filter::FilterType;
+use self::footer::{Footer, SST_FORMAT_VERSION};
+use self::index::{BlockHandle, IndexKind};
+use self::prefix::PrefixExtractor;
+use self::properties::TableProperties;
+
+/// Set in the compression type of a data block padded for direct I/O, see
+/// `SsTableBuilder::with_direct_io`.
+pub(crate) const PADDED_BLOCK_FLAG: u8 = 0x80;
+
+/// Everything stored in the meta section of an SST.
+pub struct DecodedBlockMeta {
+    pub block_meta: Vec<BlockMeta>,
+    pub block_layout: BlockLayout,
+    pub index: IndexKind,
+    pub prefix_extractor: Option<PrefixExtractor>,
+    pub range_tombstones: Vec<RangeTombstone>,
+    pub properties: TableProperties,
+}
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BlockMeta {
@@ -43,21 +75,36 @@ pub struct BlockMeta {
 
 impl BlockMeta {
     /// Encode block meta to a buffer.
-    pub fn encode_block_meta(block_meta: &[BlockMeta], max_ts: u64, buf: &mut Vec<u8>) {
+    pub fn encode_block_meta(
+        block_meta: &[BlockMeta],
+        layout: BlockLayout,
+        index: IndexKind,
+        prefix_extractor: Option<PrefixExtractor>,
+        range_tombstones: &[RangeTombstone],
+        properties: &TableProperties,
+        buf: &mut Vec<u8>,
+    ) {
         let mut estimated_size = std::mem::size_of::<u32>(); // number of blocks
         for meta in block_meta {
             // The size of offset
-            estimated_size += std::mem::size_of::<u32>();
+            estimated_size += std::mem::size_of::<u64>();
             // The size of key length
-            estimated_size += std::mem::size_of::<u16>();
+            estimated_size += varint_len(meta.first_key.key_len() as u64);
             // The size of actual key
             estimated_size += meta.first_key.raw_len();
             // The size of key length
-            estimated_size += std::mem::size_of::<u16>();
+            estimated_size += varint_len(meta.last_key.key_len() as u64);
             // The size of actual key
             estimated_size += meta.last_key.raw_len();
         }
-        estimated_size += std::mem::size_of::<u64>(); // max timestamp
+        estimated_size += layout.encoded_len(); // block layout
+        estimated_size += index.encoded_len(); // index kind
+        estimated_size += PrefixExtractor::encoded_len(prefix_extractor); // prefix extractor
+        estimated_size += varint_len(range_tombstones.len() as u64); // number of range tombstones
+        for tombstone in range_tombstones {
+            estimated_size += tombstone.encoded_len();
+        }
+        estimated_size += properties.encoded_len(); // table properties
         estimated_size += std::mem::size_of::<u32>(); // checksum
 
         // Reserve the space to improve performance, especially when the size of incoming data is
@@ -66,30 +113,55 @@ impl BlockMeta {
         let original_len = buf.len();
         buf.put_u32(block_meta.len() as u32);
         for meta in block_meta {
-            buf.put_u32(meta.offset as u32);
-            buf.put_u16(meta.first_key.key_len() as u16);
+            buf.put_u64(meta.offset as u64);
+            put_varint(buf, meta.first_key.key_len() as u64);
             buf.put_slice(meta.first_key.key_ref());
             buf.put_u64(meta.first_key.ts());
-            buf.put_u16(meta.last_key.key_len() as u16);
+            put_varint(buf, meta.last_key.key_len() as u64);
             buf.put_slice(meta.last_key.key_ref());
             buf.put_u64(meta.last_key.ts());
         }
-        buf.put_u64(max_ts);
+        layout.encode(buf);
+        index.encode(buf);
+        PrefixExtractor::encode(prefix_extractor, buf);
+        put_varint(buf, range_tombstones.len() as u64);
+        for tombstone in range_tombstones {
+            tombstone.encode(buf);
+        }
+        properties.encode(buf);
         buf.put_u32(crc32fast::hash(&buf[original_len + 4..]));
         assert_eq!(estimated_size, buf.len() - original_len);
     }
 
-    /// Decode block meta from a buffer.
-    pub fn decode_block_meta(mut buf: &[u8]) -> Result<(Vec<BlockMeta>, u64)> {
+    /// Decode block meta from a buffer, which is at `offset` in the SST with id `sst_id` written
+    /// in `format_version`.
+    pub fn decode_block_meta(
+        mut buf: &[u8],
+        sst_id: usize,
+        offset: u64,
+        format_version: u32,
+    ) -> Result<DecodedBlockMeta> {
+        let checksum = (&buf[buf.len() - 4..]).get_u32();
+        CorruptionError::check(
+            &buf[4..buf.len() - 4],
+            checksum,
+            CorruptedFile::Sst(sst_id),
+            CorruptedSection::BlockMeta,
+            offset,
+        )?;
         let mut block_meta = Vec::new();
         let num = buf.get_u32() as usize;
-        let checksum = crc32fast::hash(&buf[..buf.remaining() - 4]);
         for _ in 0..num {
-            let offset = buf.get_u32() as usize;
-            let first_key_len = buf.get_u16() as usize;
+            // Version 1 stores 32-bit offsets.
+            let offset = if format_version >= 2 {
+                buf.get_u64() as usize
+            } else {
+                buf.get_u32() as usize
+            };
+            let first_key_len = get_varint(&mut buf) as usize;
             let first_key =
                 KeyBytes::from_bytes_with_ts(buf.copy_to_bytes(first_key_len), buf.get_u64());
-            let last_key_len: usize = buf.get_u16() as usize;
+            let last_key_len = get_varint(&mut buf) as usize;
             let last_key =
                 KeyBytes::from_bytes_with_ts(buf.copy_to_bytes(last_key_len), buf.get_u64());
             block_meta.push(BlockMeta {
@@ -98,20 +170,87 @@ impl BlockMeta {
                 last_key,
             });
         }
-        let max_ts = buf.get_u64();
-        if buf.get_u32() != checksum {
-            bail!("meta checksum mismatched");
+        let layout = BlockLayout::decode(&mut buf)?;
+        let index = IndexKind::decode(&mut buf)?;
+        let prefix_extractor = PrefixExtractor::decode(&mut buf)?;
+        let num_range_tombstones = get_varint(&mut buf) as usize;
+        let range_tombstones = (0..num_range_tombstones)
+            .map(|_| RangeTombstone::decode(&mut buf))
+            .collect();
+        let properties = TableProperties::decode(&mut buf)?;
+
+        Ok(DecodedBlockMeta {
+            block_meta,
+            block_layout: layout,
+            index,
+            prefix_extractor,
+            range_tombstones,
+            properties,
+        })
+    }
+}
+
+/// The first and last key of an SST, which cover both its data blocks and its range tombstones.
+/// The exclusive end of a range tombstone is represented by its smallest possible version.
+pub(crate) fn table_bounds(
+    block_meta: &[BlockMeta],
+    range_tombstones: &[RangeTombstone],
+) -> (KeyBytes, KeyBytes) {
+    let mut first_key = block_meta.first().map(|meta| meta.first_key.clone());
+    let mut last_key = block_meta.last().map(|meta| meta.last_key.clone());
+    for tombstone in range_tombstones {
+        if first_key
+            .as_ref()
+            .is_none_or(|key| tombstone.start < key.key_ref())
+        {
+            first_key = Some(KeyBytes::from_bytes_with_ts(
+                tombstone.start.clone(),
+                TS_RANGE_BEGIN,
+            ));
+        }
+        if last_key
+            .as_ref()
+            .is_none_or(|key| tombstone.end > key.key_ref())
+        {
+            last_key = Some(KeyBytes::from_bytes_with_ts(
+                tombstone.end.clone(),
+                TS_RANGE_BEGIN,
+            ));
         }
+    }
+    (
+        first_key.expect("SST has no keys or range tombstones"),
+        last_key.expect("SST has no keys or range tombstones"),
+    )
+}
 
-        Ok((block_meta, max_ts))
+/// Convert an error shared by the block cache back into an owned one, keeping corruption errors
+/// downcastable.
+fn cache_error(err: Arc<anyhow::Error>) -> anyhow::Error {
+    match err.downcast_ref::<CorruptionError>() {
+        Some(corruption) => corruption.clone().into(),
+        None => anyhow!("{}", err),
     }
 }
 
+/// How a `FileObject` reads the file.
+enum FileMode {
+    /// Read through the page cache.
+    Buffered,
+    /// Slice the mapping of the whole file.
+    Mmap(Bytes),
+    /// Read aligned pages, bypassing the page cache.
+    Direct,
+}
+
 /// A file object.
-pub struct FileObject(Option<File>, u64);
+pub struct FileObject(Option<File>, u64, FileMode);
 
 impl FileObject {
     pub fn read(&self, offset: u64, len: u64) -> Result<Vec<u8>> {
+        if !matches!(self.2, FileMode::Buffered) {
+            return Ok(self.read_bytes(offset, len)?.to_vec());
+        }
         use std::os::unix::fs::FileExt;
         let mut data = vec![0; len as usize];
         self.0
@@ -121,6 +260,60 @@ impl FileObject {
         Ok(data)
     }
 
+    /// Read `len` bytes at `offset`. With a mapped file, the bytes are a slice of the mapping,
+    /// which stays valid as long as they are referenced.
+    pub fn read_bytes(&self, offset: u64, len: u64) -> Result<Bytes> {
+        if matches!(self.2, FileMode::Buffered) {
+            return Ok(self.read(offset, len)?.into());
+        }
+        let end = offset.checked_add(len).filter(|&end| end <= self.1);
+        let Some(end) = end else {
+            bail!(
+                "read of {} bytes at {} is past the end of the file of {} bytes",
+                len,
+                offset,
+                self.1
+            );
+        };
+        match &self.2 {
+            FileMode::Mmap(mapping) => Ok(mapping.slice(offset as usize..end as usize)),
+            FileMode::Direct => direct_io::read(self.0.as_ref().unwrap(), offset, len),
+            FileMode::Buffered => unreachable!(),
+        }
+    }
+
+    /// Whether the file is memory-mapped.
+    pub fn is_mmap(&self) -> bool {
+        matches!(self.2, FileMode::Mmap(_))
+    }
+
+    /// Whether the file is read with direct I/O.
+    pub fn is_direct(&self) -> bool {
+        matches!(self.2, FileMode::Direct)
+    }
+
+    /// Memory-map the file, so that reads slice the mapping instead of copying from the file.
+    pub fn mmap(self) -> Result<Self> {
+        let FileObject(Some(file), size, FileMode::Buffered) = self else {
+            return Ok(self);
+        };
+        // SAFETY: SST files are never modified after they are written. The mapping may outlive
+        // the removal of the file, which keeps the data until the mapping is dropped.
+        let mapping = unsafe { memmap2::Mmap::map(&file)? };
+        if mapping.len() as u64 != size {
+            bail!(
+                "file is {} bytes but {} bytes were mapped",
+                size,
+                mapping.len()
+            );
+        }
+        Ok(FileObject(
+            Some(file),
+            size,
+            FileMode::Mmap(Bytes::from_owner(mapping)),
+        ))
+    }
+
     pub fn size(&self) -> u64 {
         self.1
     }
@@ -132,13 +325,31 @@ impl FileObject {
         Ok(FileObject(
             Some(File::options().read(true).write(false).open(path)?),
             data.len() as u64,
+            FileMode::Buffered,
+        ))
+    }
+
+    /// Like `create`, but writes and later reads the file with direct I/O.
+    pub fn create_direct(path: &Path, data: Vec<u8>) -> Result<Self> {
+        direct_io::write(path, &data)?;
+        Ok(FileObject(
+            Some(direct_io::open(path)?),
+            data.len() as u64,
+            FileMode::Direct,
         ))
     }
 
     pub fn open(path: &Path) -> Result<Self> {
         let file = File::options().read(true).write(false).open(path)?;
         let size = file.metadata()?.len();
-        Ok(FileObject(Some(file), size))
+        Ok(FileObject(Some(file), size, FileMode::Buffered))
+    }
+
+    /// Open a file that is read with direct I/O.
+    pub fn open_direct(path: &Path) -> Result<Self> {
+        let file = direct_io::open(path)?;
+        let size = file.metadata()?.len();
+        Ok(FileObject(Some(file), size, FileMode::Direct))
     }
 }
 
@@ -146,16 +357,37 @@ impl FileObject {
 pub struct SsTable {
     /// The actual storage unit of SsTable, the format is as above.
     pub(crate) file: FileObject,
-    /// The meta blocks that hold info for data blocks.
-    pub(crate) block_meta: Vec<BlockMeta>,
+    /// The meta blocks that hold info for data blocks, or for index partitions if the index is
+    /// partitioned. Empty if the index is loaded through the block cache.
+    pub(crate) block_meta: Arc<Vec<BlockMeta>>,
     /// The offset that indicates the start point of meta blocks in `file`.
     pub(crate) block_meta_offset: usize,
+    /// Number of entries in `block_meta`, even if it is not held by the SST.
+    num_index_entries: usize,
+    pub(crate) index: IndexKind,
     id: usize,
     block_cache: Option<Arc<BlockCache>>,
     first_key: KeyBytes,
     last_key: KeyBytes,
-    pub(crate) bloom: Option<Bloom>,
-    max_ts: u64,
+    /// Empty if the SST has no filter, or if the filter is loaded through the block cache.
+    pub(crate) bloom: Option<Arc<Bloom>>,
+    filter_type: Option<FilterType>,
+    filter_offset: u64,
+    /// Whether `block_meta` and `bloom` are loaded through the block cache instead of being held
+    /// by the SST.
+    index_and_filter_in_cache: bool,
+    /// Whether the `block_meta` and `bloom` held by the SST are also entries of the block cache,
+    /// see `pin_index_and_filter_in_cache`.
+    index_and_filter_charged: bool,
+    block_layout: BlockLayout,
+    /// The extractor whose prefixes were added to `bloom`.
+    prefix_extractor: Option<PrefixExtractor>,
+    range_tombstones: Vec<RangeTombstone>,
+    properties: TableProperties,
+    format_version: u32,
+    /// The timestamp of all keys and range tombstones of an ingested SST, which are stored with
+    /// `TS_RANGE_BEGIN` so that the timestamp can be assigned after the SST is written.
+    global_ts: Option<u64>,
 }
 impl SsTable {
     #[cfg(test)]
@@ -166,24 +398,56 @@ impl SsTable {
     /// Open SSTable from a file.
     pub fn open(id: usize, block_cache: Option<Arc<BlockCache>>, file: FileObject) -> Result<Self> {
         let len = file.size();
-        let raw_bloom_offset = file.read(len - 4, 4)?;
-        let bloom_offset = (&raw_bloom_offset[..]).get_u32() as u64;
-        let raw_bloom = file.read(bloom_offset, len - 4 - bloom_offset)?;
-        let bloom_filter = Bloom::decode(&raw_bloom)?;
-        let raw_meta_offset = file.read(bloom_offset - 4, 4)?;
-        let block_meta_offset = (&raw_meta_offset[..]).get_u32() as u64;
-        let raw_meta = file.read(block_meta_offset, bloom_offset - 4 - block_meta_offset)?;
-        let (block_meta, max_ts) = BlockMeta::decode_block_meta(&raw_meta[..])?;
+        if len < Footer::SIZE as u64 {
+            bail!(
+                "SST {} is not an SST file: {} bytes is too small for the footer",
+                id,
+                len
+            );
+        }
+        let footer_offset = len - Footer::SIZE as u64;
+        let raw_footer = file.read_bytes(footer_offset, Footer::SIZE as u64)?;
+        let footer = Footer::decode(&raw_footer, id, footer_offset)?;
+        let bloom_offset = footer.filter_offset;
+        let raw_bloom = file.read_bytes(bloom_offset, footer_offset - bloom_offset)?;
+        let bloom_filter = Bloom::decode(&raw_bloom, id, bloom_offset)?;
+        let block_meta_offset = footer.meta_offset;
+        let raw_meta = file.read_bytes(block_meta_offset, bloom_offset - block_meta_offset)?;
+        let DecodedBlockMeta {
+            block_meta,
+            block_layout,
+            index,
+            prefix_extractor,
+            range_tombstones,
+            properties,
+        } = BlockMeta::decode_block_meta(
+            &raw_meta[..],
+            id,
+            block_meta_offset,
+            footer.format_version,
+        )?;
+        let (first_key, last_key) = table_bounds(&block_meta, &range_tombstones);
         Ok(Self {
             file,
-            first_key: block_meta.first().unwrap().first_key.clone(),
-            last_key: block_meta.last().unwrap().last_key.clone(),
-            block_meta,
+            first_key,
+            last_key,
+            num_index_entries: block_meta.len(),
+            block_meta: Arc::new(block_meta),
             block_meta_offset: block_meta_offset as usize,
+            index,
             id,
             block_cache,
-            bloom: Some(bloom_filter),
-            max_ts,
+            filter_type: bloom_filter.as_ref().map(|bloom| bloom.filter_type()),
+            bloom: bloom_filter.map(Arc::new),
+            filter_offset: bloom_offset,
+            index_and_filter_in_cache: false,
+            index_and_filter_charged: false,
+            block_layout,
+            prefix_extractor,
+            range_tombstones,
+            properties,
+            format_version: footer.format_version,
+            global_ts: None,
         })
     }
 
@@ -195,59 +459,320 @@ impl SsTable {
         last_key: KeyBytes,
     ) -> Self {
         Self {
-            file: FileObject(None, file_size),
-            block_meta: vec![],
+            file: FileObject(None, file_size, FileMode::Buffered),
+            block_meta: Arc::default(),
             block_meta_offset: 0,
+            num_index_entries: 0,
+            index: IndexKind::Full,
             id,
             block_cache: None,
             first_key,
             last_key,
             bloom: None,
-            max_ts: 0,
+            filter_type: None,
+            filter_offset: 0,
+            index_and_filter_in_cache: false,
+            index_and_filter_charged: false,
+            block_layout: BlockLayout::Variable,
+            prefix_extractor: None,
+            range_tombstones: Vec::new(),
+            properties: TableProperties::default(),
+            format_version: SST_FORMAT_VERSION,
+            global_ts: None,
         }
     }
 
-    /// Read a block from the disk.
-    pub fn read_block(&self, block_idx: usize) -> Result<Arc<Block>> {
-        let offset = self.block_meta[block_idx].offset;
-        let offset_end = self
-            .block_meta
-            .get(block_idx + 1)
-            .map_or(self.block_meta_offset, |x| x.offset);
-        let block_len = offset_end - offset - 4;
-        let block_data_with_chksum: Vec<u8> = self
-            .file
-            .read(offset as u64, (offset_end - offset) as u64)?;
-        let block_data = &block_data_with_chksum[..block_len];
-        let checksum = (&block_data_with_chksum[block_len..]).get_u32();
-        if checksum != crc32fast::hash(block_data) {
-            bail!("block checksum mismatched");
+    /// Report `ts` as the timestamp of all keys and range tombstones of an SST written with
+    /// `TS_RANGE_BEGIN` for all of them. The first and last keys keep `TS_RANGE_BEGIN`, which
+    /// still bounds the user keys of the SST.
+    pub(crate) fn with_global_ts(mut self, ts: u64) -> Self {
+        self.global_ts = Some(ts);
+        for tombstone in &mut self.range_tombstones {
+            tombstone.ts = ts;
+        }
+        self.properties.min_ts = ts;
+        self.properties.max_ts = ts;
+        self
+    }
+
+    /// The timestamp of all keys of the SST if it was ingested, see `with_global_ts`.
+    pub fn global_ts(&self) -> Option<u64> {
+        self.global_ts
+    }
+
+    /// Map a key to the key stored in the file, so that seeking to it finds the same position as
+    /// seeking to `key` among the keys with the global timestamp.
+    pub(crate) fn stored_key<'a>(&self, key: KeySlice<'a>) -> KeySlice<'a> {
+        match self.global_ts {
+            Some(ts) if ts <= key.ts() => KeySlice::from_slice(key.key_ref(), TS_RANGE_BEGIN),
+            Some(_) => KeySlice::from_slice(key.key_ref(), TS_RANGE_END),
+            None => key,
+        }
+    }
+
+    /// Drop the index and filter held by the SST, and load them through the block cache from now
+    /// on, charged against its capacity. Does nothing without a block cache.
+    pub fn unpin_index_and_filter(mut self) -> Self {
+        if self.block_cache.is_some() {
+            self.block_meta = Arc::default();
+            self.bloom = None;
+            self.index_and_filter_in_cache = true;
+        }
+        self
+    }
+
+    /// Keep the index and filter held by the SST, and also add them to the block cache, charged
+    /// against its capacity. The charge is best-effort: the block cache has no way to exclude
+    /// entries from eviction, and lookups use the copies held by the SST without touching the
+    /// cache, so once evicted the entries are not charged anymore. Does nothing without a block
+    /// cache.
+    pub fn pin_index_and_filter_in_cache(mut self) -> Self {
+        if self.block_cache.is_some() {
+            self.index_and_filter_charged = true;
+            self.charge_pinned(INDEX_BLOCK_IDX, CachedBlock::Index(self.block_meta.clone()));
+            if let Some(bloom) = &self.bloom {
+                self.charge_pinned(FILTER_BLOCK_IDX, CachedBlock::Filter(bloom.clone()));
+            }
+        }
+        self
+    }
+
+    /// Whether the index and filter are held by the SST, see `unpin_index_and_filter`.
+    pub fn is_index_and_filter_pinned(&self) -> bool {
+        !self.index_and_filter_in_cache
+    }
+
+    /// Whether the index and filter held by the SST are charged against the block cache, see
+    /// `pin_index_and_filter_in_cache`.
+    pub fn is_index_and_filter_charged(&self) -> bool {
+        self.index_and_filter_charged
+    }
+
+    /// The memory held by the index and filter of the SST itself.
+    pub fn pinned_index_and_filter_size(&self) -> usize {
+        cache::index_charge(&self.block_meta)
+            + self.bloom.as_deref().map_or(0, cache::filter_charge)
+    }
+
+    /// Insert the entry of a pinned index or filter into the block cache.
+    fn charge_pinned(&self, block_idx: usize, entry: CachedBlock) {
+        self.block_cache
+            .as_ref()
+            .unwrap()
+            .insert((self.id, block_idx), entry);
+    }
+
+    /// Call `f` with the block meta, loading it through the block cache if it is not pinned.
+    fn with_block_meta<R>(&self, f: impl FnOnce(&[BlockMeta]) -> R) -> Result<R> {
+        if !self.index_and_filter_in_cache {
+            return Ok(f(&self.block_meta));
         }
-        Ok(Arc::new(Block::decode(block_data)))
+        let load = || {
+            let offset = self.block_meta_offset as u64;
+            let raw_meta = self.file.read_bytes(offset, self.filter_offset - offset)?;
+            let decoded =
+                BlockMeta::decode_block_meta(&raw_meta, self.id, offset, self.format_version)?;
+            Ok::<_, anyhow::Error>(CachedBlock::Index(Arc::new(decoded.block_meta)))
+        };
+        let block_meta = self
+            .block_cache
+            .as_ref()
+            .unwrap()
+            .try_get_with((self.id, INDEX_BLOCK_IDX), load)
+            .map_err(cache_error)?
+            .into_index();
+        Ok(f(&block_meta))
+    }
+
+    /// Call `f` with the filter, loading it through the block cache if it is not pinned.
+    fn with_bloom<R>(&self, f: impl FnOnce(Option<&Bloom>) -> R) -> Result<R> {
+        if !self.index_and_filter_in_cache || self.filter_type.is_none() {
+            return Ok(f(self.bloom.as_deref()));
+        }
+        let load = || {
+            let footer_offset = self.file.size() - Footer::SIZE as u64;
+            let raw_bloom = self
+                .file
+                .read_bytes(self.filter_offset, footer_offset - self.filter_offset)?;
+            let Some(bloom) = Bloom::decode(&raw_bloom, self.id, self.filter_offset)? else {
+                bail!("SST {} lost its filter", self.id);
+            };
+            Ok(CachedBlock::Filter(Arc::new(bloom)))
+        };
+        let bloom = self
+            .block_cache
+            .as_ref()
+            .unwrap()
+            .try_get_with((self.id, FILTER_BLOCK_IDX), load)
+            .map_err(cache_error)?
+            .into_filter();
+        Ok(f(Some(&bloom)))
+    }
+
+    /// Read a block from the disk. The returned block is always decompressed.
+    pub fn read_block(&self, block_idx: usize) -> Result<Arc<Block>> {
+        let (offset, offset_end) = match self.index {
+            IndexKind::Full => self.meta_range(block_idx)?,
+            IndexKind::Partitioned { .. } => {
+                let handle = self.block_handle(block_idx)?;
+                (handle.offset, handle.offset_end)
+            }
+        };
+        self.read_block_at(
+            offset,
+            offset_end,
+            self.block_layout,
+            CorruptedSection::DataBlock(block_idx),
+        )
+    }
+
+    /// The range in `file` of the block or index partition described by `block_meta[idx]`.
+    fn meta_range(&self, idx: usize) -> Result<(usize, usize)> {
+        self.with_block_meta(|block_meta| {
+            let offset = block_meta[idx].offset;
+            let offset_end = block_meta
+                .get(idx + 1)
+                .map_or(self.block_meta_offset, |x| x.offset);
+            (offset, offset_end)
+        })
+    }
+
+    fn read_block_at(
+        &self,
+        offset: usize,
+        offset_end: usize,
+        layout: BlockLayout,
+        section: CorruptedSection,
+    ) -> Result<Arc<Block>> {
+        // The block is followed by a 1-byte compression type and a 4-byte checksum.
+        let len = offset_end - offset;
+        let block_data_with_chksum = self.file.read_bytes(offset as u64, len as u64)?;
+        let checksum = (&block_data_with_chksum[len - 4..]).get_u32();
+        CorruptionError::check(
+            &block_data_with_chksum[..len - 4],
+            checksum,
+            CorruptedFile::Sst(self.id),
+            section,
+            offset as u64,
+        )?;
+        let codec_id = block_data_with_chksum[len - 5];
+        let block_len = if codec_id & PADDED_BLOCK_FLAG != 0 {
+            let padding = (&block_data_with_chksum[len - 7..]).get_u16() as usize;
+            len - 7 - padding
+        } else {
+            len - 5
+        };
+        let block_data = block_data_with_chksum.slice(..block_len);
+        let block = match CompressionType::from_id(codec_id & !PADDED_BLOCK_FLAG)? {
+            CompressionType::None => Block::decode_with_layout(block_data, layout),
+            compression => Block::decode_with_layout(
+                compression.codec().decompress(&block_data)?.into(),
+                layout,
+            ),
+        };
+        Ok(Arc::new(block))
     }
 
     /// Read a block from disk, with block cache.
     pub fn read_block_cached(&self, block_idx: usize) -> Result<Arc<Block>> {
         if let Some(ref block_cache) = self.block_cache {
             let blk = block_cache
-                .try_get_with((self.id, block_idx), || self.read_block(block_idx))
-                .map_err(|e| anyhow!("{}", e))?;
-            Ok(blk)
+                .try_get_with((self.id, block_idx), || {
+                    self.read_block(block_idx).map(CachedBlock::Data)
+                })
+                .map_err(cache_error)?;
+            Ok(blk.into_data())
         } else {
             self.read_block(block_idx)
         }
     }
 
+    /// Remove the blocks of the SST from the block cache, once the SST is deleted. Cached blocks
+    /// of a memory-mapped SST keep the mapping alive.
+    pub fn evict_cached_blocks(&self) {
+        if let Some(ref block_cache) = self.block_cache {
+            let num_partitions = match self.index {
+                IndexKind::Full => 0,
+                IndexKind::Partitioned { .. } => self.num_index_entries,
+            };
+            for idx in 0..self.num_of_blocks() + num_partitions {
+                block_cache.invalidate(&(self.id, idx));
+            }
+            block_cache.invalidate(&(self.id, INDEX_BLOCK_IDX));
+            block_cache.invalidate(&(self.id, FILTER_BLOCK_IDX));
+        }
+    }
+
+    /// Read an index partition, with block cache. Partitions are cached after the data blocks of
+    /// the SST.
+    fn read_index_partition_cached(&self, partition_idx: usize) -> Result<Arc<Block>> {
+        let read = || {
+            let (offset, offset_end) = self.meta_range(partition_idx)?;
+            self.read_block_at(
+                offset,
+                offset_end,
+                BlockLayout::Variable,
+                CorruptedSection::IndexPartition(partition_idx),
+            )
+        };
+        if let Some(ref block_cache) = self.block_cache {
+            let blk = block_cache
+                .try_get_with((self.id, self.num_of_blocks() + partition_idx), || {
+                    read().map(CachedBlock::Data)
+                })
+                .map_err(cache_error)?;
+            Ok(blk.into_data())
+        } else {
+            read()
+        }
+    }
+
+    /// Look up the location of a data block in the partitioned index.
+    fn block_handle(&self, block_idx: usize) -> Result<BlockHandle> {
+        let IndexKind::Partitioned {
+            blocks_per_partition,
+            ..
+        } = self.index
+        else {
+            unreachable!("the index is not partitioned");
+        };
+        let partition = self.read_index_partition_cached(block_idx / blocks_per_partition)?;
+        let mut iter = BlockIterator::create_and_seek_to_first(partition);
+        for _ in 0..block_idx % blocks_per_partition {
+            iter.next();
+        }
+        Ok(BlockHandle::decode(iter.value()))
+    }
+
     /// Find the block that may contain `key`.
-    pub fn find_block_idx(&self, key: KeySlice) -> usize {
-        self.block_meta
-            .partition_point(|meta| meta.first_key.as_key_slice() <= key)
-            .saturating_sub(1)
+    pub fn find_block_idx(&self, key: KeySlice) -> Result<usize> {
+        let idx = self
+            .with_block_meta(|block_meta| {
+                block_meta.partition_point(|meta| meta.first_key.as_key_slice() <= key)
+            })?
+            .saturating_sub(1);
+        match self.index {
+            IndexKind::Full => Ok(idx),
+            IndexKind::Partitioned { .. } => {
+                let iter = BlockIterator::create_and_seek_for_prev(
+                    self.read_index_partition_cached(idx)?,
+                    key,
+                );
+                if !iter.is_valid() {
+                    // `key` is smaller than the first key of the SST.
+                    return Ok(0);
+                }
+                Ok(BlockHandle::decode(iter.value()).block_idx)
+            }
+        }
     }
 
     /// Get number of data blocks.
     pub fn num_of_blocks(&self) -> usize {
-        self.block_meta.len()
+        match self.index {
+            IndexKind::Full => self.num_index_entries,
+            IndexKind::Partitioned { num_blocks, .. } => num_blocks,
+        }
     }
 
     pub fn first_key(&self) -> &KeyBytes {
@@ -267,6 +792,53 @@ impl SsTable {
     }
 
     pub fn max_ts(&self) -> u64 {
-        self.max_ts
+        self.properties.max_ts
+    }
+
+    pub fn properties(&self) -> &TableProperties {
+        &self.properties
+    }
+
+    /// The version of the format the SST was written in.
+    pub fn format_version(&self) -> u32 {
+        self.format_version
+    }
+
+    /// The type of the filter of the SST, or `None` if it has no filter.
+    pub fn filter_type(&self) -> Option<FilterType> {
+        self.filter_type
+    }
+
+    pub fn block_layout(&self) -> BlockLayout {
+        self.block_layout
+    }
+
+    pub fn prefix_extractor(&self) -> Option<PrefixExtractor> {
+        self.prefix_extractor
+    }
+
+    pub fn range_tombstones(&self) -> &[RangeTombstone] {
+        &self.range_tombstones
+    }
+
+    /// Check whether the SST may contain `key`. Always true if the SST has no filter.
+    pub fn may_contain(&self, key: &[u8]) -> Result<bool> {
+        self.with_bloom(|bloom| {
+            bloom.is_none_or(|bloom| bloom.may_contain(farmhash::fingerprint32(key)))
+        })
+    }
+
+    /// Check whether the SST may contain keys starting with `prefix`. Always true if the SST has
+    /// no prefix bloom, or if `prefix` is too short for the extractor of the SST.
+    pub fn may_contain_prefix(&self, prefix: &[u8]) -> Result<bool> {
+        let Some(extractor) = self.prefix_extractor else {
+            return Ok(true);
+        };
+        let Some(prefix) = extractor.extract(prefix) else {
+            return Ok(true);
+        };
+        self.with_bloom(|bloom| {
+            bloom.is_none_or(|bloom| bloom.may_contain(farmhash::fingerprint32(prefix)))
+        })
     }
 }
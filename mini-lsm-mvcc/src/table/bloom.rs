@@ -17,12 +17,25 @@
 use anyhow::{Result, bail};
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 
+use super::filter::FilterType;
+use crate::error::{CorruptedFile, CorruptedSection, CorruptionError};
+
+/// Stored after `k` to mark a blocked bloom filter. The original encoding ends with `k`, which is
+/// at most 30, so larger values identify other encodings.
+const TAG_BLOCKED_BLOOM: u8 = 0xfe;
+/// Stored alone when the SST has no filter.
+const TAG_DISABLED: u8 = 0xff;
+
+/// Number of bits in each block of a blocked bloom filter, which is one cache line.
+const BLOCK_BITS: usize = 512;
+
 /// Implements a bloom filter
 pub struct Bloom {
     /// data of filter in bits
     pub(crate) filter: Bytes,
     /// number of hash functions
     pub(crate) k: u8,
+    filter_type: FilterType,
 }
 
 pub trait BitSlice {
@@ -59,18 +72,42 @@ impl<T: AsMut<[u8]>> BitSliceMut for T {
 }
 
 impl Bloom {
-    /// Decode a bloom filter
-    pub fn decode(buf: &[u8]) -> Result<Self> {
+    /// Decode a bloom filter, which is at `offset` in the SST with id `sst_id`. Returns `None` if
+    /// the SST was built without a filter.
+    pub fn decode(buf: &[u8], sst_id: usize, offset: u64) -> Result<Option<Self>> {
         let checksum = (&buf[buf.len() - 4..buf.len()]).get_u32();
-        if checksum != crc32fast::hash(&buf[..buf.len() - 4]) {
-            bail!("checksum mismatched for bloom filters");
+        CorruptionError::check(
+            &buf[..buf.len() - 4],
+            checksum,
+            CorruptedFile::Sst(sst_id),
+            CorruptedSection::Bloom,
+            offset,
+        )?;
+        let buf = &buf[..buf.len() - 4];
+        let (buf, filter_type) = match buf[buf.len() - 1] {
+            TAG_DISABLED => return Ok(None),
+            TAG_BLOCKED_BLOOM => (&buf[..buf.len() - 1], FilterType::BlockedBloom),
+            1..=30 => (buf, FilterType::Bloom),
+            tag => bail!(
+                "unknown bloom filter encoding {:#04x} in SST {}",
+                tag,
+                sst_id
+            ),
+        };
+        let filter = &buf[..buf.len() - 1];
+        let k = buf[buf.len() - 1];
+        if !(1..=30).contains(&k) {
+            bail!(
+                "invalid number of bloom filter hashes {} in SST {}",
+                k,
+                sst_id
+            );
         }
-        let filter = &buf[..buf.len() - 5];
-        let k = buf[buf.len() - 5];
-        Ok(Self {
+        Ok(Some(Self {
             filter: filter.to_vec().into(),
             k,
-        })
+            filter_type,
+        }))
     }
 
     /// Encode a bloom filter
@@ -78,10 +115,38 @@ impl Bloom {
         let offset = buf.len();
         buf.extend(&self.filter);
         buf.put_u8(self.k);
+        if self.filter_type == FilterType::BlockedBloom {
+            buf.put_u8(TAG_BLOCKED_BLOOM);
+        }
+        let checksum = crc32fast::hash(&buf[offset..]);
+        buf.put_u32(checksum);
+    }
+
+    /// Encode the filter section of an SST without a filter.
+    pub fn encode_disabled(buf: &mut Vec<u8>) {
+        let offset = buf.len();
+        buf.put_u8(TAG_DISABLED);
         let checksum = crc32fast::hash(&buf[offset..]);
         buf.put_u32(checksum);
     }
 
+    pub fn filter_type(&self) -> FilterType {
+        self.filter_type
+    }
+
+    fn num_hashes(bits_per_key: usize) -> u32 {
+        let k = (bits_per_key as f64 * 0.69) as u32;
+        k.clamp(1, 30)
+    }
+
+    /// Build a filter of the given type from key hashes
+    pub fn build(filter_type: FilterType, keys: &[u32], bits_per_key: usize) -> Self {
+        match filter_type {
+            FilterType::Bloom => Self::build_from_key_hashes(keys, bits_per_key),
+            FilterType::BlockedBloom => Self::build_blocked_from_key_hashes(keys, bits_per_key),
+        }
+    }
+
     /// Get bloom filter bits per key from entries count and FPR
     pub fn bloom_bits_per_key(entries: usize, false_positive_rate: f64) -> usize {
         let size =
@@ -92,8 +157,7 @@ impl Bloom {
 
     /// Build bloom filter from key hashes
     pub fn build_from_key_hashes(keys: &[u32], bits_per_key: usize) -> Self {
-        let k = (bits_per_key as f64 * 0.69) as u32;
-        let k = k.clamp(1, 30);
+        let k = Self::num_hashes(bits_per_key);
         let nbits = (keys.len() * bits_per_key).max(64);
         let nbytes = (nbits + 7) / 8;
         let nbits = nbytes * 8;
@@ -111,13 +175,49 @@ impl Bloom {
         Self {
             filter: filter.freeze(),
             k: k as u8,
+            filter_type: FilterType::Bloom,
         }
     }
 
+    /// Build a blocked bloom filter from key hashes
+    pub fn build_blocked_from_key_hashes(keys: &[u32], bits_per_key: usize) -> Self {
+        let k = Self::num_hashes(bits_per_key);
+        let nblocks = (keys.len() * bits_per_key).div_ceil(BLOCK_BITS).max(1);
+        let mut filter = BytesMut::with_capacity(nblocks * BLOCK_BITS / 8);
+        filter.resize(nblocks * BLOCK_BITS / 8, 0);
+        for h in keys {
+            let block = Self::block_of(*h, nblocks);
+            let mut h = *h;
+            let delta = h.rotate_left(15);
+            for _ in 0..k {
+                filter.set_bit(block + h as usize % BLOCK_BITS, true);
+                h = h.wrapping_add(delta);
+            }
+        }
+        Self {
+            filter: filter.freeze(),
+            k: k as u8,
+            filter_type: FilterType::BlockedBloom,
+        }
+    }
+
+    /// The first bit of the block that holds all probes of `h`. The block is picked with the high
+    /// bits of the hash, while the probes use the low bits.
+    fn block_of(h: u32, nblocks: usize) -> usize {
+        ((h as u64 * nblocks as u64) >> 32) as usize * BLOCK_BITS
+    }
+
     /// Check if a bloom filter may contain some data
     pub fn may_contain(&self, mut h: u32) -> bool {
-        if self.k > 30 {
-            // potential new encoding for short bloom filters
+        if self.filter_type == FilterType::BlockedBloom {
+            let block = Self::block_of(h, self.filter.bit_len() / BLOCK_BITS);
+            let delta = h.rotate_left(15);
+            for _ in 0..self.k {
+                if !self.filter.get_bit(block + h as usize % BLOCK_BITS) {
+                    return false;
+                }
+                h = h.wrapping_add(delta);
+            }
             true
         } else {
             let nbits = self.filter.bit_len();
@@ -14,15 +14,25 @@
 
 use std::path::Path;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 use bytes::BufMut;
 
 use super::bloom::Bloom;
-use super::{BlockMeta, FileObject, SsTable};
-use crate::block::BlockBuilder;
+use super::compression::{CompressionCodec, CompressionType, NoCompression};
+use super::direct_io::DIRECT_IO_ALIGNMENT;
+use super::filter::{FilterSize, FilterType};
+use super::footer::{Footer, SST_FORMAT_VERSION};
+use super::index::{BlockHandle, IndexKind};
+use super::prefix::PrefixExtractor;
+use super::properties::TableProperties;
+use super::{BlockMeta, FileObject, PADDED_BLOCK_FLAG, SsTable, table_bounds};
+use crate::block::{BlockBuilder, BlockLayout, DEFAULT_RESTART_INTERVAL};
+use crate::compact::CompactionTask;
 use crate::key::{KeySlice, KeyVec};
 use crate::lsm_storage::BlockCache;
+use crate::range_tombstone::RangeTombstone;
 
 /// Builds an SSTable from key-value pairs.
 pub struct SsTableBuilder {
@@ -33,7 +43,21 @@ pub struct SsTableBuilder {
     pub(crate) meta: Vec<BlockMeta>,
     block_size: usize,
     key_hashes: Vec<u32>,
-    max_ts: u64,
+    compression: CompressionType,
+    restart_interval: usize,
+    block_layout: BlockLayout,
+    hash_index: bool,
+    index_partition_size: Option<usize>,
+    prefix_extractor: Option<PrefixExtractor>,
+    /// The hash of the last prefix added to `key_hashes`, so that each prefix is added once.
+    last_prefix_hash: Option<u32>,
+    range_tombstones: Vec<RangeTombstone>,
+    properties: TableProperties,
+    filter: Option<(FilterType, FilterSize)>,
+    mmap: bool,
+    direct_io: bool,
+    index_and_filter_in_cache: bool,
+    pin_index_and_filter_in_cache: bool,
 }
 
 impl SsTableBuilder {
@@ -47,20 +71,149 @@ impl SsTableBuilder {
             block_size,
             builder: BlockBuilder::new(block_size),
             key_hashes: Vec::new(),
-            max_ts: 0,
+            compression: CompressionType::None,
+            restart_interval: DEFAULT_RESTART_INTERVAL,
+            block_layout: BlockLayout::Variable,
+            hash_index: false,
+            index_partition_size: None,
+            prefix_extractor: None,
+            last_prefix_hash: None,
+            range_tombstones: Vec::new(),
+            properties: TableProperties {
+                min_ts: u64::MAX,
+                ..Default::default()
+            },
+            filter: Some((FilterType::Bloom, FilterSize::FalsePositiveRate(0.01))),
+            mmap: false,
+            direct_io: false,
+            index_and_filter_in_cache: false,
+            pin_index_and_filter_in_cache: false,
         }
     }
 
+    /// Compress data blocks with the given codec.
+    pub fn with_compression(mut self, compression: CompressionType) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Store a full key every `restart_interval` entries in each data block.
+    pub fn with_restart_interval(mut self, restart_interval: usize) -> Self {
+        self.restart_interval = restart_interval;
+        self.builder = self.new_block_builder();
+        self
+    }
+
+    /// Lay out data blocks with the given layout, which is recorded in the SST meta.
+    pub fn with_block_layout(mut self, block_layout: BlockLayout) -> Self {
+        self.block_layout = block_layout;
+        self.builder = self.new_block_builder();
+        self
+    }
+
+    /// Append a hash index to each data block for point lookups.
+    pub fn with_hash_index(mut self, hash_index: bool) -> Self {
+        self.hash_index = hash_index;
+        self.builder = self.new_block_builder();
+        self
+    }
+
+    /// Split the block index into partitions of `blocks_per_partition` entries, which are loaded
+    /// on demand instead of being kept in memory. `None` keeps the whole index in memory.
+    pub fn with_index_partition_size(mut self, blocks_per_partition: Option<usize>) -> Self {
+        assert!(
+            blocks_per_partition != Some(0),
+            "index partition size must be positive"
+        );
+        self.index_partition_size = blocks_per_partition;
+        self
+    }
+
+    /// Add the prefix of each key to the bloom filter, so that prefix scans can skip the SST.
+    pub fn with_prefix_extractor(mut self, prefix_extractor: Option<PrefixExtractor>) -> Self {
+        self.prefix_extractor = prefix_extractor;
+        self
+    }
+
+    /// Build a filter of the given type and size over the keys, or no filter if `None`.
+    pub fn with_filter(mut self, filter: Option<(FilterType, FilterSize)>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Memory-map the SST file once it is written, see `FileObject::mmap`.
+    pub fn with_mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+
+    /// Write the SST file with direct I/O and read it back the same way, see
+    /// `FileObject::create_direct`. Data blocks are padded to `DIRECT_IO_ALIGNMENT`, so that
+    /// reading a block does not read the pages of its neighbours. Takes precedence over
+    /// `with_mmap`.
+    pub fn with_direct_io(mut self, direct_io: bool) -> Self {
+        self.direct_io = direct_io;
+        self
+    }
+
+    /// Load the index and filter of the SST through the block cache instead of holding them, see
+    /// `SsTable::unpin_index_and_filter`.
+    pub fn with_index_and_filter_in_cache(mut self, in_cache: bool) -> Self {
+        self.index_and_filter_in_cache = in_cache;
+        self
+    }
+
+    /// Hold the index and filter of the SST and charge them against the block cache, see
+    /// `SsTable::pin_index_and_filter_in_cache`. Ignored with `with_index_and_filter_in_cache`.
+    pub fn with_index_and_filter_pinned_in_cache(mut self, pinned: bool) -> Self {
+        self.pin_index_and_filter_in_cache = pinned;
+        self
+    }
+
+    /// Record the compaction that produces this SST in its properties.
+    pub fn with_compaction_task(mut self, task: CompactionTask) -> Self {
+        self.properties.compaction_task = Some(task);
+        self
+    }
+
+    fn new_block_builder(&self) -> BlockBuilder {
+        BlockBuilder::new(self.block_size)
+            .with_restart_interval(self.restart_interval)
+            .with_layout(self.block_layout)
+            .with_hash_index(self.hash_index)
+    }
+
     /// Adds a key-value pair to SSTable
     pub fn add(&mut self, key: KeySlice, value: &[u8]) {
+        // Versions of the same key are added in a row.
+        if self.properties.num_entries == 0 || self.last_key.key_ref() != key.key_ref() {
+            self.properties.num_distinct_keys += 1;
+        }
+        self.properties.num_entries += 1;
+        if value.is_empty() {
+            self.properties.num_deletions += 1;
+        }
+        self.properties.min_ts = self.properties.min_ts.min(key.ts());
+        self.properties.max_ts = self.properties.max_ts.max(key.ts());
+        self.properties.raw_key_size += key.key_len() as u64;
+        self.properties.raw_value_size += value.len() as u64;
+
         if self.first_key.is_empty() {
             self.first_key.set_from_slice(key);
         }
 
-        if key.ts() > self.max_ts {
-            self.max_ts = key.ts();
-        }
         self.key_hashes.push(farmhash::fingerprint32(key.key_ref()));
+        if let Some(prefix) = self
+            .prefix_extractor
+            .and_then(|extractor| extractor.extract(key.key_ref()))
+        {
+            // Keys are sorted, so all keys with the same prefix are added in a row.
+            let prefix_hash = farmhash::fingerprint32(prefix);
+            if self.last_prefix_hash != Some(prefix_hash) {
+                self.key_hashes.push(prefix_hash);
+                self.last_prefix_hash = Some(prefix_hash);
+            }
+        }
 
         if self.builder.add(key, value) {
             self.last_key.set_from_slice(key);
@@ -76,22 +229,103 @@ impl SsTableBuilder {
         self.last_key.set_from_slice(key);
     }
 
+    /// Adds a range tombstone to the SSTable. Range tombstones may be added in any order.
+    pub fn add_range_tombstone(&mut self, tombstone: RangeTombstone) {
+        self.properties.num_range_tombstones += 1;
+        self.properties.min_ts = self.properties.min_ts.min(tombstone.ts);
+        self.properties.max_ts = self.properties.max_ts.max(tombstone.ts);
+        self.range_tombstones.push(tombstone);
+    }
+
     /// Get the estimated size of the SSTable.
     pub fn estimated_size(&self) -> usize {
         self.data.len()
     }
 
     fn finish_block(&mut self) {
-        let builder = std::mem::replace(&mut self.builder, BlockBuilder::new(self.block_size));
+        if self.builder.is_empty() {
+            // The SST only holds range tombstones.
+            return;
+        }
+        let new_builder = self.new_block_builder();
+        let builder = std::mem::replace(&mut self.builder, new_builder);
         let encoded_block = builder.build().encode();
         self.meta.push(BlockMeta {
             offset: self.data.len(),
             first_key: std::mem::take(&mut self.first_key).into_key_bytes(),
             last_key: std::mem::take(&mut self.last_key).into_key_bytes(),
         });
-        let checksum = crc32fast::hash(&encoded_block);
-        self.data.extend(encoded_block);
-        self.data.put_u32(checksum);
+        Self::append_block(
+            &mut self.data,
+            &encoded_block,
+            self.compression,
+            self.direct_io,
+        );
+    }
+
+    /// Append a compressed block followed by its compression type and checksum. With `align`, the
+    /// block is padded to end on a multiple of `DIRECT_IO_ALIGNMENT`, so that direct I/O reads
+    /// only its own pages. The length of the padding is then stored as a `u16` before the
+    /// compression type, which is marked with `PADDED_BLOCK_FLAG`.
+    fn append_block(
+        buf: &mut Vec<u8>,
+        encoded_block: &[u8],
+        compression: CompressionType,
+        align: bool,
+    ) {
+        let offset = buf.len();
+        let codec = compression.codec();
+        codec.compress(encoded_block, buf);
+        // Store the block uncompressed if the codec does not make it smaller, so that a single SST
+        // may contain both compressed and raw blocks.
+        let mut codec_id = if buf.len() - offset >= encoded_block.len() {
+            buf.truncate(offset);
+            buf.extend_from_slice(encoded_block);
+            NoCompression.id()
+        } else {
+            codec.id()
+        };
+        if align {
+            let unpadded_end = buf.len() + 2 + 1 + 4;
+            let padding = unpadded_end.next_multiple_of(DIRECT_IO_ALIGNMENT) - unpadded_end;
+            buf.resize(buf.len() + padding, 0);
+            buf.put_u16(padding as u16);
+            codec_id |= PADDED_BLOCK_FLAG;
+        }
+        buf.put_u8(codec_id);
+        let checksum = crc32fast::hash(&buf[offset..]);
+        buf.put_u32(checksum);
+    }
+
+    /// Append the index partitions after the data blocks, and return the meta of each partition.
+    fn build_index_partitions(
+        &self,
+        buf: &mut Vec<u8>,
+        blocks_per_partition: usize,
+    ) -> Vec<BlockMeta> {
+        let data_end = buf.len();
+        let mut partitions = Vec::new();
+        for (partition_idx, chunk) in self.meta.chunks(blocks_per_partition).enumerate() {
+            let mut builder = BlockBuilder::new(usize::MAX);
+            for (idx, meta) in chunk.iter().enumerate() {
+                let block_idx = partition_idx * blocks_per_partition + idx;
+                let handle = BlockHandle {
+                    block_idx,
+                    offset: meta.offset,
+                    offset_end: self.meta.get(block_idx + 1).map_or(data_end, |x| x.offset),
+                };
+                let mut value = Vec::new();
+                handle.encode(&mut value);
+                assert!(builder.add(meta.first_key.as_key_slice(), &value));
+            }
+            partitions.push(BlockMeta {
+                offset: buf.len(),
+                first_key: chunk.first().unwrap().first_key.clone(),
+                last_key: chunk.last().unwrap().last_key.clone(),
+            });
+            Self::append_block(buf, &builder.build().encode(), self.compression, false);
+        }
+        partitions
     }
 
     /// Builds the SSTable and writes it to the given path. Use the `FileObject` structure to manipulate the disk objects.
@@ -102,29 +336,88 @@ impl SsTableBuilder {
         path: impl AsRef<Path>,
     ) -> Result<SsTable> {
         self.finish_block();
-        let mut buf = self.data;
+        let mut buf = std::mem::take(&mut self.data);
+        let (block_meta, index) = match self.index_partition_size {
+            Some(blocks_per_partition) => (
+                self.build_index_partitions(&mut buf, blocks_per_partition),
+                IndexKind::Partitioned {
+                    blocks_per_partition,
+                    num_blocks: self.meta.len(),
+                },
+            ),
+            None => (std::mem::take(&mut self.meta), IndexKind::Full),
+        };
+        if block_meta.len() > u32::MAX as usize {
+            bail!(
+                "SST has {} entries in its block meta, more than the limit of {}",
+                block_meta.len(),
+                u32::MAX
+            );
+        }
+        if self.properties.min_ts > self.properties.max_ts {
+            // The SST is empty.
+            self.properties.min_ts = 0;
+        }
+        self.properties.creation_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
         let meta_offset = buf.len();
-        BlockMeta::encode_block_meta(&self.meta, self.max_ts, &mut buf);
-        buf.put_u32(meta_offset as u32);
-        let bloom = Bloom::build_from_key_hashes(
-            &self.key_hashes,
-            Bloom::bloom_bits_per_key(self.key_hashes.len(), 0.01),
+        BlockMeta::encode_block_meta(
+            &block_meta,
+            self.block_layout,
+            index,
+            self.prefix_extractor,
+            &self.range_tombstones,
+            &self.properties,
+            &mut buf,
         );
+        let bloom = self.filter.map(|(filter_type, size)| {
+            let bits_per_key = size.bits_per_key(self.key_hashes.len());
+            Bloom::build(filter_type, &self.key_hashes, bits_per_key)
+        });
         let bloom_offset = buf.len();
-        bloom.encode(&mut buf);
-        buf.put_u32(bloom_offset as u32);
-        let file = FileObject::create(path.as_ref(), buf)?;
-        Ok(SsTable {
+        match &bloom {
+            Some(bloom) => bloom.encode(&mut buf),
+            None => Bloom::encode_disabled(&mut buf),
+        }
+        Footer::new(meta_offset as u64, bloom_offset as u64).encode(&mut buf);
+        let file = if self.direct_io {
+            FileObject::create_direct(path.as_ref(), buf)?
+        } else if self.mmap {
+            FileObject::create(path.as_ref(), buf)?.mmap()?
+        } else {
+            FileObject::create(path.as_ref(), buf)?
+        };
+        let (first_key, last_key) = table_bounds(&block_meta, &self.range_tombstones);
+        let sst = SsTable {
             id,
             file,
-            first_key: self.meta.first().unwrap().first_key.clone(),
-            last_key: self.meta.last().unwrap().last_key.clone(),
-            block_meta: self.meta,
+            first_key,
+            last_key,
+            num_index_entries: block_meta.len(),
+            block_meta: Arc::new(block_meta),
             block_meta_offset: meta_offset,
+            index,
             block_cache,
-            bloom: Some(bloom),
-            max_ts: self.max_ts,
-        })
+            filter_type: bloom.as_ref().map(|bloom| bloom.filter_type()),
+            bloom: bloom.map(Arc::new),
+            filter_offset: bloom_offset as u64,
+            index_and_filter_in_cache: false,
+            index_and_filter_charged: false,
+            block_layout: self.block_layout,
+            prefix_extractor: self.prefix_extractor,
+            range_tombstones: self.range_tombstones,
+            properties: self.properties,
+            format_version: SST_FORMAT_VERSION,
+            global_ts: None,
+        };
+        if self.index_and_filter_in_cache {
+            Ok(sst.unpin_index_and_filter())
+        } else if self.pin_index_and_filter_in_cache {
+            Ok(sst.pin_index_and_filter_in_cache())
+        } else {
+            Ok(sst)
+        }
     }
 
     #[cfg(test)]
@@ -0,0 +1,129 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::block::Block;
+use crate::lsm_storage::BlockCache;
+
+use super::BlockMeta;
+use super::bloom::Bloom;
+
+/// The block index of the cache key of the index of an SST, whose data blocks and index
+/// partitions use the indices from 0.
+pub(crate) const INDEX_BLOCK_IDX: usize = usize::MAX;
+/// The block index of the cache key of the filter of an SST.
+pub(crate) const FILTER_BLOCK_IDX: usize = usize::MAX - 1;
+
+/// An entry of the block cache.
+#[derive(Clone)]
+pub enum CachedBlock {
+    /// A data block or an index partition.
+    Data(Arc<Block>),
+    /// The block meta of an SST.
+    Index(Arc<Vec<BlockMeta>>),
+    Filter(Arc<Bloom>),
+}
+
+impl CachedBlock {
+    /// The approximate memory held by the entry in bytes, which is charged against the capacity
+    /// of the cache.
+    pub fn charge(&self) -> usize {
+        match self {
+            CachedBlock::Data(block) => block_charge(block),
+            CachedBlock::Index(block_meta) => index_charge(block_meta),
+            CachedBlock::Filter(bloom) => filter_charge(bloom),
+        }
+    }
+
+    pub(crate) fn into_data(self) -> Arc<Block> {
+        match self {
+            CachedBlock::Data(block) => block,
+            _ => unreachable!("not a data block"),
+        }
+    }
+
+    pub(crate) fn into_index(self) -> Arc<Vec<BlockMeta>> {
+        match self {
+            CachedBlock::Index(block_meta) => block_meta,
+            _ => unreachable!("not an index"),
+        }
+    }
+
+    pub(crate) fn into_filter(self) -> Arc<Bloom> {
+        match self {
+            CachedBlock::Filter(bloom) => bloom,
+            _ => unreachable!("not a filter"),
+        }
+    }
+}
+
+fn block_charge(block: &Block) -> usize {
+    std::mem::size_of::<Block>()
+        + block.data.len()
+        + block.restarts().len()
+        + block.hash_index.as_ref().map_or(0, |buckets| buckets.len())
+}
+
+pub(crate) fn index_charge(block_meta: &[BlockMeta]) -> usize {
+    block_meta
+        .iter()
+        .map(|meta| {
+            std::mem::size_of::<BlockMeta>() + meta.first_key.raw_len() + meta.last_key.raw_len()
+        })
+        .sum()
+}
+
+pub(crate) fn filter_charge(bloom: &Bloom) -> usize {
+    std::mem::size_of::<Bloom>() + bloom.filter.len()
+}
+
+/// Create a block cache that holds up to `capacity` bytes of entries.
+pub fn new_block_cache(capacity: u64) -> BlockCache {
+    BlockCache::builder()
+        .weigher(|_, block: &CachedBlock| block.charge().try_into().unwrap_or(u32::MAX))
+        .max_capacity(capacity)
+        .build()
+}
+
+/// Memory held by the indexes and filters of SSTs, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IndexAndFilterMemory {
+    /// Held by the SSTs themselves. The pinned indexes and filters of L0 SSTs are charged
+    /// against the block cache too, see `SsTable::pin_index_and_filter_in_cache`.
+    pub pinned: usize,
+    /// Loaded through the block cache and charged against its capacity.
+    pub cached: usize,
+}
+
+impl IndexAndFilterMemory {
+    pub fn total(&self) -> usize {
+        self.pinned + self.cached
+    }
+}
+
+/// The memory held by the indexes and filters in `block_cache`, except those of the SSTs in
+/// `pinned`, which are held by the SSTs.
+pub(crate) fn cached_index_and_filter_size(
+    block_cache: &BlockCache,
+    pinned: &HashSet<usize>,
+) -> usize {
+    block_cache
+        .iter()
+        .filter(|(key, _)| key.1 == INDEX_BLOCK_IDX || key.1 == FILTER_BLOCK_IDX)
+        .filter(|(key, _)| !pinned.contains(&key.0))
+        .map(|(_, block)| block.charge())
+        .sum()
+}
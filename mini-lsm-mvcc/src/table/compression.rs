@@ -0,0 +1,88 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Result, anyhow, bail};
+
+/// A codec that compresses and decompresses encoded data blocks.
+pub trait CompressionCodec: Send + Sync {
+    /// The tag written after each block, used by the reader to pick the codec.
+    fn id(&self) -> u8;
+
+    /// Compress `data` and append the result to `buf`.
+    fn compress(&self, data: &[u8], buf: &mut Vec<u8>);
+
+    /// Decompress a block previously produced by `compress`.
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Stores blocks as-is.
+pub struct NoCompression;
+
+impl CompressionCodec for NoCompression {
+    fn id(&self) -> u8 {
+        0
+    }
+
+    fn compress(&self, data: &[u8], buf: &mut Vec<u8>) {
+        buf.extend_from_slice(data);
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(data.to_vec())
+    }
+}
+
+/// LZ4 block compression, with the uncompressed size prepended.
+pub struct Lz4Compression;
+
+impl CompressionCodec for Lz4Compression {
+    fn id(&self) -> u8 {
+        1
+    }
+
+    fn compress(&self, data: &[u8], buf: &mut Vec<u8>) {
+        buf.extend(lz4_flex::block::compress_prepend_size(data));
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        lz4_flex::block::decompress_size_prepended(data)
+            .map_err(|e| anyhow!("failed to decompress lz4 block: {}", e))
+    }
+}
+
+/// The compression algorithm used when building SSTs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompressionType {
+    #[default]
+    None,
+    Lz4,
+}
+
+impl CompressionType {
+    pub fn codec(&self) -> &'static dyn CompressionCodec {
+        match self {
+            CompressionType::None => &NoCompression,
+            CompressionType::Lz4 => &Lz4Compression,
+        }
+    }
+
+    /// Find the compression type by the tag stored in a block.
+    pub fn from_id(id: u8) -> Result<Self> {
+        match id {
+            0 => Ok(CompressionType::None),
+            1 => Ok(CompressionType::Lz4),
+            _ => bail!("unknown compression type {}", id),
+        }
+    }
+}
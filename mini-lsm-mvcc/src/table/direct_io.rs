@@ -0,0 +1,113 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs::File;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::path::Path;
+
+use anyhow::Result;
+use bytes::Bytes;
+
+/// The alignment of the offsets, lengths and buffers of direct I/O, which is the page size on
+/// most systems and a multiple of the logical block size of most devices.
+pub const DIRECT_IO_ALIGNMENT: usize = 4096;
+
+#[derive(Clone, Copy)]
+#[repr(C, align(4096))]
+struct Page([u8; DIRECT_IO_ALIGNMENT]);
+
+/// A zeroed buffer whose start is aligned to `DIRECT_IO_ALIGNMENT`.
+struct AlignedBuffer {
+    pages: Vec<Page>,
+    len: usize,
+}
+
+impl AlignedBuffer {
+    /// A buffer of `len` bytes, with capacity rounded up to a multiple of the alignment.
+    fn new(len: usize) -> Self {
+        Self {
+            pages: vec![Page([0; DIRECT_IO_ALIGNMENT]); len.div_ceil(DIRECT_IO_ALIGNMENT)],
+            len,
+        }
+    }
+
+    /// The whole capacity of the buffer, which is aligned in both position and length.
+    fn padded_mut(&mut self) -> &mut [u8] {
+        // SAFETY: `Page` is a plain byte array, so the pages are contiguous initialized bytes.
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                self.pages.as_mut_ptr().cast::<u8>(),
+                self.pages.len() * DIRECT_IO_ALIGNMENT,
+            )
+        }
+    }
+}
+
+impl AsRef<[u8]> for AlignedBuffer {
+    fn as_ref(&self) -> &[u8] {
+        // SAFETY: see `padded_mut`.
+        unsafe { std::slice::from_raw_parts(self.pages.as_ptr().cast::<u8>(), self.len) }
+    }
+}
+
+fn align_down(offset: u64) -> u64 {
+    offset & !(DIRECT_IO_ALIGNMENT as u64 - 1)
+}
+
+/// Open `path` for reading, bypassing the page cache.
+pub(crate) fn open(path: &Path) -> Result<File> {
+    Ok(File::options()
+        .read(true)
+        .custom_flags(libc::O_DIRECT)
+        .open(path)?)
+}
+
+/// Write `data` to a new file at `path`, bypassing the page cache. The data is copied into an
+/// aligned buffer and padded to the alignment, and the file is truncated to the length of `data`
+/// afterwards.
+pub(crate) fn write(path: &Path, data: &[u8]) -> Result<()> {
+    let file = File::options()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .custom_flags(libc::O_DIRECT)
+        .open(path)?;
+    let mut buf = AlignedBuffer::new(data.len());
+    buf.padded_mut()[..data.len()].copy_from_slice(data);
+    file.write_all_at(buf.padded_mut(), 0)?;
+    file.set_len(data.len() as u64)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Read `len` bytes at `offset` from a file opened with `open`. The read covers the aligned pages
+/// around the range, which are kept by the returned bytes. Data blocks written for direct I/O are
+/// already aligned, so only the reads of the meta section are widened.
+pub(crate) fn read(file: &File, offset: u64, len: u64) -> Result<Bytes> {
+    let start = align_down(offset);
+    let end = offset + len;
+    let mut buf = AlignedBuffer::new((end - start) as usize);
+    let padded = buf.padded_mut();
+    let mut read = 0;
+    // The last page of the file is read short.
+    while (start as usize + read) < end as usize {
+        let n = file.read_at(&mut padded[read..], start + read as u64)?;
+        if n == 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        read += n;
+    }
+    let skip = (offset - start) as usize;
+    Ok(Bytes::from_owner(buf).slice(skip..skip + len as usize))
+}
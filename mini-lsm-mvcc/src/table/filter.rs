@@ -0,0 +1,85 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::bloom::Bloom;
+
+/// The kind of filter built for an SST. The kind is recorded in the filter section, so SSTs built
+/// with different kinds can be read together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    /// A standard bloom filter, which is the format of SSTs written before filter types existed.
+    Bloom,
+    /// A bloom filter whose probes for a key all fall in one 64-byte block. A lookup touches a
+    /// single cache line, at the cost of a slightly higher false positive rate for the same size.
+    BlockedBloom,
+}
+
+/// The size of a filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterSize {
+    BitsPerKey(usize),
+    /// The target false positive rate, from which the bits per key are derived.
+    FalsePositiveRate(f64),
+}
+
+impl FilterSize {
+    pub(crate) fn bits_per_key(&self, num_keys: usize) -> usize {
+        match *self {
+            FilterSize::BitsPerKey(bits_per_key) => bits_per_key,
+            FilterSize::FalsePositiveRate(rate) => Bloom::bloom_bits_per_key(num_keys, rate),
+        }
+    }
+}
+
+/// Decides which filter each SST gets, based on the level it is written to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterPolicy {
+    pub filter_type: FilterType,
+    /// The filter size of L0 at index 0 and of each lower level at its level number. Levels past
+    /// the end use the last entry, and an empty list disables filters. With tiered compaction, all
+    /// tiers use the size of L0.
+    pub level_sizes: Vec<FilterSize>,
+    /// Build no filter for SSTs written to the bottom level. It holds most of the keys, and a
+    /// lookup that reaches it usually finds the key.
+    pub disable_on_last_level: bool,
+}
+
+impl Default for FilterPolicy {
+    fn default() -> Self {
+        Self {
+            filter_type: FilterType::Bloom,
+            level_sizes: vec![FilterSize::FalsePositiveRate(0.01)],
+            disable_on_last_level: false,
+        }
+    }
+}
+
+impl FilterPolicy {
+    /// The filter of an SST written to `level`, or `None` if it gets no filter.
+    pub fn filter_for_level(
+        &self,
+        level: usize,
+        is_last_level: bool,
+    ) -> Option<(FilterType, FilterSize)> {
+        if is_last_level && self.disable_on_last_level {
+            return None;
+        }
+        let size = self
+            .level_sizes
+            .get(level)
+            .or(self.level_sizes.last())
+            .copied()?;
+        Some((self.filter_type, size))
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Result, bail};
+use bytes::{Buf, BufMut};
+
+use crate::error::{CorruptedFile, CorruptedSection, CorruptionError};
+
+/// The last 8 bytes of every SST, "mini-lsm" in ASCII.
+pub const SST_MAGIC: u64 = 0x6d69_6e69_2d6c_736d;
+
+/// The SST format version written by this build. Bump it when the encoding of blocks, the meta or
+/// the filter changes, so that older builds refuse the new files instead of misreading them.
+///
+/// - 1: the first versioned format.
+/// - 2: the offsets of blocks in the meta are 64-bit instead of 32-bit.
+pub const SST_FORMAT_VERSION: u32 = 2;
+
+/// The checksum algorithm of the blocks, meta and filter of an SST.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumType {
+    Crc32,
+}
+
+impl ChecksumType {
+    fn id(&self) -> u8 {
+        match self {
+            ChecksumType::Crc32 => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(ChecksumType::Crc32),
+            _ => None,
+        }
+    }
+}
+
+/// The fixed-size tail of an SST:
+///
+/// ```text
+/// | meta offset (u64) | filter offset (u64) | checksum type (u8) | checksum (u32) | version (u32) | magic (u64) |
+/// ```
+///
+/// The checksum covers the offsets and the checksum type. The version and the magic number are
+/// at fixed positions from the end of the file, so future versions may change the rest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Footer {
+    pub format_version: u32,
+    pub checksum_type: ChecksumType,
+    pub meta_offset: u64,
+    pub filter_offset: u64,
+}
+
+impl Footer {
+    pub const SIZE: usize = 8 + 8 + 1 + 4 + 4 + 8;
+
+    pub(crate) fn new(meta_offset: u64, filter_offset: u64) -> Self {
+        Self {
+            format_version: SST_FORMAT_VERSION,
+            checksum_type: ChecksumType::Crc32,
+            meta_offset,
+            filter_offset,
+        }
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        let offset = buf.len();
+        buf.put_u64(self.meta_offset);
+        buf.put_u64(self.filter_offset);
+        buf.put_u8(self.checksum_type.id());
+        let checksum = crc32fast::hash(&buf[offset..]);
+        buf.put_u32(checksum);
+        buf.put_u32(self.format_version);
+        buf.put_u64(SST_MAGIC);
+    }
+
+    /// Decode the footer, which is at `offset` in the SST with id `sst_id`.
+    pub(crate) fn decode(buf: &[u8], sst_id: usize, offset: u64) -> Result<Self> {
+        assert_eq!(buf.len(), Self::SIZE);
+        let magic = (&buf[Self::SIZE - 8..]).get_u64();
+        if magic != SST_MAGIC {
+            bail!(
+                "SST {} is not an SST file: bad magic number {:#018x}",
+                sst_id,
+                magic
+            );
+        }
+        let format_version = (&buf[Self::SIZE - 12..]).get_u32();
+        if format_version == 0 || format_version > SST_FORMAT_VERSION {
+            bail!(
+                "SST {} has unsupported format version {}, this build reads versions up to {}",
+                sst_id,
+                format_version,
+                SST_FORMAT_VERSION
+            );
+        }
+        let checksum = (&buf[17..]).get_u32();
+        CorruptionError::check(
+            &buf[..17],
+            checksum,
+            CorruptedFile::Sst(sst_id),
+            CorruptedSection::Footer,
+            offset,
+        )?;
+        let mut buf = buf;
+        let meta_offset = buf.get_u64();
+        let filter_offset = buf.get_u64();
+        let checksum_id = buf.get_u8();
+        let Some(checksum_type) = ChecksumType::from_id(checksum_id) else {
+            bail!("SST {} has unknown checksum type {}", sst_id, checksum_id);
+        };
+        Ok(Self {
+            format_version,
+            checksum_type,
+            meta_offset,
+            filter_offset,
+        })
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Result, bail};
+use bytes::{Buf, BufMut};
+
+use crate::varint::{get_varint, put_varint, varint_len};
+
+/// How the block index of an SST is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndexKind {
+    /// The SST meta holds the meta of every data block.
+    #[default]
+    Full,
+    /// The SST meta holds one entry per index partition. Each partition is a block stored after
+    /// the data blocks, which maps the first key of `blocks_per_partition` data blocks to a
+    /// `BlockHandle`.
+    Partitioned {
+        blocks_per_partition: usize,
+        num_blocks: usize,
+    },
+}
+
+impl IndexKind {
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            IndexKind::Full => buf.put_u8(0),
+            IndexKind::Partitioned {
+                blocks_per_partition,
+                num_blocks,
+            } => {
+                buf.put_u8(1);
+                put_varint(buf, *blocks_per_partition as u64);
+                put_varint(buf, *num_blocks as u64);
+            }
+        }
+    }
+
+    pub(crate) fn encoded_len(&self) -> usize {
+        match self {
+            IndexKind::Full => 1,
+            IndexKind::Partitioned {
+                blocks_per_partition,
+                num_blocks,
+            } => 1 + varint_len(*blocks_per_partition as u64) + varint_len(*num_blocks as u64),
+        }
+    }
+
+    pub(crate) fn decode(buf: &mut &[u8]) -> Result<Self> {
+        match buf.get_u8() {
+            0 => Ok(IndexKind::Full),
+            1 => Ok(IndexKind::Partitioned {
+                blocks_per_partition: get_varint(buf) as usize,
+                num_blocks: get_varint(buf) as usize,
+            }),
+            tag => bail!("unknown index kind {}", tag),
+        }
+    }
+}
+
+/// The location of a data block, stored as the value of an index partition entry.
+pub(crate) struct BlockHandle {
+    pub(crate) block_idx: usize,
+    pub(crate) offset: usize,
+    pub(crate) offset_end: usize,
+}
+
+impl BlockHandle {
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        put_varint(buf, self.block_idx as u64);
+        put_varint(buf, self.offset as u64);
+        put_varint(buf, self.offset_end as u64);
+    }
+
+    pub(crate) fn decode(mut buf: &[u8]) -> Self {
+        Self {
+            block_idx: get_varint(&mut buf) as usize,
+            offset: get_varint(&mut buf) as usize,
+            offset_end: get_varint(&mut buf) as usize,
+        }
+    }
+}
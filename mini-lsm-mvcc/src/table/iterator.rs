@@ -29,7 +29,15 @@ pub struct SsTableIterator {
 }
 
 impl SsTableIterator {
+    /// An invalid position, used for SSTs that only hold range tombstones.
+    fn empty_inner() -> (usize, BlockIterator) {
+        (0, BlockIterator::create_and_seek_to_first(Arc::default()))
+    }
+
     fn seek_to_first_inner(table: &Arc<SsTable>) -> Result<(usize, BlockIterator)> {
+        if table.num_of_blocks() == 0 {
+            return Ok(Self::empty_inner());
+        }
         Ok((
             0,
             BlockIterator::create_and_seek_to_first(table.read_block_cached(0)?),
@@ -55,10 +63,52 @@ impl SsTableIterator {
         Ok(())
     }
 
-    fn seek_to_key_inner(table: &Arc<SsTable>, key: KeySlice) -> Result<(usize, BlockIterator)> {
-        let mut blk_idx = table.find_block_idx(key);
-        let mut blk_iter =
-            BlockIterator::create_and_seek_to_key(table.read_block_cached(blk_idx)?, key);
+    fn seek_to_last_inner(table: &Arc<SsTable>) -> Result<(usize, BlockIterator)> {
+        if table.num_of_blocks() == 0 {
+            return Ok(Self::empty_inner());
+        }
+        let blk_idx = table.num_of_blocks() - 1;
+        Ok((
+            blk_idx,
+            BlockIterator::create_and_seek_to_last(table.read_block_cached(blk_idx)?),
+        ))
+    }
+
+    /// Create a new iterator and seek to the last key-value pair.
+    pub fn create_and_seek_to_last(table: Arc<SsTable>) -> Result<Self> {
+        let (blk_idx, blk_iter) = Self::seek_to_last_inner(&table)?;
+        let iter = Self {
+            blk_iter,
+            table,
+            blk_idx,
+        };
+        Ok(iter)
+    }
+
+    /// Seek to the last key-value pair.
+    pub fn seek_to_last(&mut self) -> Result<()> {
+        let (blk_idx, blk_iter) = Self::seek_to_last_inner(&self.table)?;
+        self.blk_idx = blk_idx;
+        self.blk_iter = blk_iter;
+        Ok(())
+    }
+
+    fn seek_to_key_inner(
+        table: &Arc<SsTable>,
+        key: KeySlice,
+        for_get: bool,
+    ) -> Result<(usize, BlockIterator)> {
+        if table.num_of_blocks() == 0 {
+            return Ok(Self::empty_inner());
+        }
+        let key = table.stored_key(key);
+        let mut blk_idx = table.find_block_idx(key)?;
+        let block = table.read_block_cached(blk_idx)?;
+        let mut blk_iter = if for_get {
+            BlockIterator::create_and_seek_for_get(block, key)
+        } else {
+            BlockIterator::create_and_seek_to_key(block, key)
+        };
         if !blk_iter.is_valid() {
             blk_idx += 1;
             if blk_idx < table.num_of_blocks() {
@@ -71,7 +121,19 @@ impl SsTableIterator {
 
     /// Create a new iterator and seek to the first key-value pair which >= `key`.
     pub fn create_and_seek_to_key(table: Arc<SsTable>, key: KeySlice) -> Result<Self> {
-        let (blk_idx, blk_iter) = Self::seek_to_key_inner(&table, key)?;
+        let (blk_idx, blk_iter) = Self::seek_to_key_inner(&table, key, false)?;
+        let iter = Self {
+            blk_iter,
+            table,
+            blk_idx,
+        };
+        Ok(iter)
+    }
+
+    /// Create a new iterator for a point lookup of `key`, using the hash index of data blocks.
+    /// If the user key is not in the table, the iterator may skip some keys larger than `key`.
+    pub fn create_and_seek_for_get(table: Arc<SsTable>, key: KeySlice) -> Result<Self> {
+        let (blk_idx, blk_iter) = Self::seek_to_key_inner(&table, key, true)?;
         let iter = Self {
             blk_iter,
             table,
@@ -82,11 +144,48 @@ impl SsTableIterator {
 
     /// Seek to the first key-value pair which >= `key`.
     pub fn seek_to_key(&mut self, key: KeySlice) -> Result<()> {
-        let (blk_idx, blk_iter) = Self::seek_to_key_inner(&self.table, key)?;
+        let (blk_idx, blk_iter) = Self::seek_to_key_inner(&self.table, key, false)?;
         self.blk_iter = blk_iter;
         self.blk_idx = blk_idx;
         Ok(())
     }
+
+    /// Create a new iterator and seek to the last key-value pair which <= `key`.
+    pub fn create_and_seek_for_prev(table: Arc<SsTable>, key: KeySlice) -> Result<Self> {
+        let mut iter = Self::create_and_seek_to_key(table, key)?;
+        iter.seek_for_prev_from_next(key)?;
+        Ok(iter)
+    }
+
+    /// Seek to the last key-value pair which <= `key`. The iterator is invalid if all keys are
+    /// larger than `key`.
+    pub fn seek_for_prev(&mut self, key: KeySlice) -> Result<()> {
+        self.seek_to_key(key)?;
+        self.seek_for_prev_from_next(key)
+    }
+
+    /// Step back from the first key-value pair which >= `key`.
+    fn seek_for_prev_from_next(&mut self, key: KeySlice) -> Result<()> {
+        if !self.blk_iter.is_valid() {
+            self.seek_to_last()
+        } else if self.blk_iter.key() > self.table.stored_key(key) {
+            self.prev()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Move to the previous key-value pair, crossing into the previous block if needed. The
+    /// iterator becomes invalid if it is at the first key.
+    pub fn prev(&mut self) -> Result<()> {
+        self.blk_iter.prev();
+        if !self.blk_iter.is_valid() && self.blk_idx > 0 {
+            self.blk_idx -= 1;
+            self.blk_iter =
+                BlockIterator::create_and_seek_to_last(self.table.read_block_cached(self.blk_idx)?);
+        }
+        Ok(())
+    }
 }
 
 impl StorageIterator for SsTableIterator {
@@ -97,7 +196,11 @@ impl StorageIterator for SsTableIterator {
     }
 
     fn key(&self) -> KeySlice {
-        self.blk_iter.key()
+        let key = self.blk_iter.key();
+        match self.table.global_ts() {
+            Some(ts) => KeySlice::from_slice(key.key_ref(), ts),
+            None => key,
+        }
     }
 
     fn is_valid(&self) -> bool {
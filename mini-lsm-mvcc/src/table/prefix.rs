@@ -0,0 +1,74 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Result, bail};
+use bytes::{Buf, BufMut};
+
+use crate::varint::{get_varint, put_varint, varint_len};
+
+/// Extracts the prefix of a key, which is added to the bloom filter of an SST in addition to the
+/// key itself so that prefix scans can skip SSTs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefixExtractor {
+    /// The first `n` bytes of the key. Keys shorter than `n` bytes have no prefix.
+    FixedLength(usize),
+    /// The key up to and including the first occurrence of the delimiter. Keys without the
+    /// delimiter have no prefix.
+    Delimiter(u8),
+}
+
+impl PrefixExtractor {
+    /// Extract the prefix of `key`. If `key` is itself a prefix being scanned, this returns the
+    /// extracted prefix shared by every key that starts with it, if any.
+    pub fn extract<'a>(&self, key: &'a [u8]) -> Option<&'a [u8]> {
+        match *self {
+            PrefixExtractor::FixedLength(len) => key.get(..len),
+            PrefixExtractor::Delimiter(delimiter) => key
+                .iter()
+                .position(|&x| x == delimiter)
+                .map(|pos| &key[..=pos]),
+        }
+    }
+
+    pub(crate) fn encode(extractor: Option<Self>, buf: &mut Vec<u8>) {
+        match extractor {
+            None => buf.put_u8(0),
+            Some(PrefixExtractor::FixedLength(len)) => {
+                buf.put_u8(1);
+                put_varint(buf, len as u64);
+            }
+            Some(PrefixExtractor::Delimiter(delimiter)) => {
+                buf.put_u8(2);
+                buf.put_u8(delimiter);
+            }
+        }
+    }
+
+    pub(crate) fn encoded_len(extractor: Option<Self>) -> usize {
+        match extractor {
+            None => 1,
+            Some(PrefixExtractor::FixedLength(len)) => 1 + varint_len(len as u64),
+            Some(PrefixExtractor::Delimiter(_)) => 2,
+        }
+    }
+
+    pub(crate) fn decode(buf: &mut &[u8]) -> Result<Option<Self>> {
+        match buf.get_u8() {
+            0 => Ok(None),
+            1 => Ok(Some(PrefixExtractor::FixedLength(get_varint(buf) as usize))),
+            2 => Ok(Some(PrefixExtractor::Delimiter(buf.get_u8()))),
+            tag => bail!("unknown prefix extractor {}", tag),
+        }
+    }
+}
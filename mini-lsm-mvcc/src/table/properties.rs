@@ -0,0 +1,104 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+use bytes::{Buf, BufMut};
+
+use crate::compact::CompactionTask;
+use crate::varint::{get_varint, put_varint, varint_len};
+
+/// Statistics of an SST, collected by `SsTableBuilder` and stored in the SST meta.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TableProperties {
+    /// Number of key-value pairs, counting each version of a key.
+    pub num_entries: u64,
+    /// Number of point deletions, i.e. entries with an empty value.
+    pub num_deletions: u64,
+    pub num_range_tombstones: u64,
+    /// The smallest timestamp of the entries and range tombstones.
+    pub min_ts: u64,
+    /// The largest timestamp of the entries and range tombstones.
+    pub max_ts: u64,
+    /// Total size of the keys, without timestamps.
+    pub raw_key_size: u64,
+    /// Total size of the values.
+    pub raw_value_size: u64,
+    /// Number of distinct keys, ignoring timestamps.
+    pub num_distinct_keys: u64,
+    /// Seconds since the Unix epoch when the SST was built.
+    pub creation_time: u64,
+    /// The compaction that produced the SST, or `None` if it was flushed from a memtable.
+    pub compaction_task: Option<CompactionTask>,
+}
+
+impl TableProperties {
+    fn encoded_task(&self) -> Vec<u8> {
+        self.compaction_task
+            .as_ref()
+            .map(|task| serde_json::to_vec(task).unwrap())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.put_u64(self.num_entries);
+        buf.put_u64(self.num_deletions);
+        buf.put_u64(self.num_range_tombstones);
+        buf.put_u64(self.min_ts);
+        buf.put_u64(self.max_ts);
+        buf.put_u64(self.raw_key_size);
+        buf.put_u64(self.raw_value_size);
+        buf.put_u64(self.num_distinct_keys);
+        buf.put_u64(self.creation_time);
+        // The task is stored as JSON, like in the manifest. An empty task means a flush.
+        let task = self.encoded_task();
+        put_varint(buf, task.len() as u64);
+        buf.put_slice(&task);
+    }
+
+    pub(crate) fn encoded_len(&self) -> usize {
+        let task_len = self.encoded_task().len();
+        9 * std::mem::size_of::<u64>() + varint_len(task_len as u64) + task_len
+    }
+
+    pub(crate) fn decode(buf: &mut &[u8]) -> Result<Self> {
+        let num_entries = buf.get_u64();
+        let num_deletions = buf.get_u64();
+        let num_range_tombstones = buf.get_u64();
+        let min_ts = buf.get_u64();
+        let max_ts = buf.get_u64();
+        let raw_key_size = buf.get_u64();
+        let raw_value_size = buf.get_u64();
+        let num_distinct_keys = buf.get_u64();
+        let creation_time = buf.get_u64();
+        let task_len = get_varint(buf) as usize;
+        let compaction_task = if task_len == 0 {
+            None
+        } else {
+            Some(serde_json::from_slice(&buf[..task_len])?)
+        };
+        buf.advance(task_len);
+        Ok(Self {
+            num_entries,
+            num_deletions,
+            num_range_tombstones,
+            min_ts,
+            max_ts,
+            raw_key_size,
+            raw_value_size,
+            num_distinct_keys,
+            creation_time,
+            compaction_task,
+        })
+    }
+}
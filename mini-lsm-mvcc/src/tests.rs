@@ -12,7 +12,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod arena_memtable;
+mod block_compression;
+mod block_fixed_key;
+mod block_hash_index;
+mod block_restarts;
+mod block_ts_delta;
+mod corruption;
+mod direct_io;
+mod filter_policy;
+mod group_commit;
 mod harness;
+mod index_filter_cache;
+mod ingestion;
+mod large_entries;
+mod memtable_rep;
+mod mmap;
+mod partitioned_index;
+mod prefix_bloom;
+mod range_deletion;
+mod reverse_iteration;
+mod sst_footer;
+mod table_properties;
 mod week1_day1;
 mod week1_day2;
 mod week1_day3;
@@ -33,3 +54,5 @@ mod week3_day4;
 mod week3_day5;
 mod week3_day6;
 mod week3_day7;
+mod write_buffer_manager;
+mod write_stall;
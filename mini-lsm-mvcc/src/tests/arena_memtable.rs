@@ -0,0 +1,216 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Bound;
+use std::sync::Arc;
+
+use tempfile::tempdir;
+
+use super::harness::{key_of, value_of};
+use crate::{
+    iterators::StorageIterator,
+    key::KeySlice,
+    mem_table::{MemTable, MemTableRepType},
+    range_tombstone::RangeTombstone,
+};
+
+fn collect(
+    mut iter: impl for<'a> StorageIterator<KeyType<'a> = KeySlice<'a>>,
+) -> Vec<(Vec<u8>, u64)> {
+    let mut keys = Vec::new();
+    while iter.is_valid() {
+        keys.push((iter.key().key_ref().to_vec(), iter.key().ts()));
+        iter.next().unwrap();
+    }
+    keys
+}
+
+#[test]
+fn test_arena_memtable_get_and_scan() {
+    let memtable = MemTable::create(0);
+    assert!(memtable.is_empty());
+    for idx in (0..100).rev() {
+        memtable
+            .put(KeySlice::from_slice(&key_of(idx), 1), &value_of(idx))
+            .unwrap();
+        memtable
+            .put(KeySlice::from_slice(&key_of(idx), 2), b"")
+            .unwrap();
+    }
+    assert!(!memtable.is_empty());
+    assert_eq!(memtable.max_ts(), 2);
+    for idx in 0..100 {
+        let key = key_of(idx);
+        assert_eq!(
+            memtable.get(KeySlice::from_slice(&key, 1)).unwrap(),
+            value_of(idx)
+        );
+        assert_eq!(memtable.get(KeySlice::from_slice(&key, 2)).unwrap(), "");
+        assert!(memtable.get(KeySlice::from_slice(&key, 3)).is_none());
+    }
+    assert!(memtable.get(KeySlice::from_slice(b"key", 1)).is_none());
+
+    // Keys are ordered by key, then by descending timestamp.
+    let keys = collect(memtable.scan(Bound::Unbounded, Bound::Unbounded));
+    assert_eq!(keys.len(), 200);
+    assert_eq!(keys[0], (key_of(0), 2));
+    assert_eq!(keys[1], (key_of(0), 1));
+    assert_eq!(keys[199], (key_of(99), 1));
+
+    let keys = collect(memtable.scan(
+        Bound::Included(KeySlice::from_slice(&key_of(10), 1)),
+        Bound::Excluded(KeySlice::from_slice(&key_of(12), 1)),
+    ));
+    assert_eq!(
+        keys,
+        vec![
+            (key_of(10), 1),
+            (key_of(11), 2),
+            (key_of(11), 1),
+            (key_of(12), 2)
+        ]
+    );
+    let keys = collect(memtable.scan(
+        Bound::Excluded(KeySlice::from_slice(&key_of(10), 2)),
+        Bound::Included(KeySlice::from_slice(&key_of(11), 2)),
+    ));
+    assert_eq!(keys, vec![(key_of(10), 1), (key_of(11), 2)]);
+    assert!(
+        !memtable
+            .scan(
+                Bound::Excluded(KeySlice::from_slice(&key_of(99), 1)),
+                Bound::Unbounded
+            )
+            .is_valid()
+    );
+}
+
+#[test]
+fn test_arena_memtable_overwrite() {
+    let memtable = MemTable::create(0);
+    let key = KeySlice::from_slice(b"key", 1);
+    memtable.put(key, b"value_1").unwrap();
+    let size = memtable.approximate_size();
+    memtable.put(key, b"value_2").unwrap();
+    assert_eq!(memtable.get(key).unwrap(), "value_2");
+    // The replaced value stays in the arena, and is still accounted for.
+    assert!(memtable.approximate_size() > size);
+    let keys = collect(memtable.scan(Bound::Unbounded, Bound::Unbounded));
+    assert_eq!(keys, vec![(b"key".to_vec(), 1)]);
+}
+
+#[test]
+fn test_arena_memtable_memory_accounting() {
+    let memtable = MemTable::create(0);
+    let mut data_size = 0;
+    for idx in 0..1000 {
+        let (key, value) = (key_of(idx), value_of(idx));
+        data_size += key.len() + value.len();
+        memtable.put(KeySlice::from_slice(&key, 1), &value).unwrap();
+    }
+    let size = memtable.approximate_size();
+    assert!(size > data_size);
+    assert!(memtable.memory_usage() >= size);
+
+    memtable
+        .write_batch(&[], &[RangeTombstone::new(b"a", b"b", 2)])
+        .unwrap();
+    assert!(memtable.approximate_size() > size);
+}
+
+#[test]
+fn test_arena_memtable_size_after_recovery() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("0.wal");
+    let memtable = MemTable::create_with_wal(0, MemTableRepType::SkipList, &path).unwrap();
+    for idx in 0..100 {
+        memtable
+            .put(KeySlice::from_slice(&key_of(idx), 1), &value_of(idx))
+            .unwrap();
+    }
+    memtable
+        .write_batch(&[], &[RangeTombstone::new(b"a", b"b", 2)])
+        .unwrap();
+    memtable.sync_wal().unwrap();
+    let size = memtable.approximate_size();
+    drop(memtable);
+
+    let memtable = MemTable::recover_from_wal(0, MemTableRepType::SkipList, &path).unwrap();
+    assert_eq!(memtable.approximate_size(), size);
+    assert_eq!(memtable.range_tombstones().len(), 1);
+    for idx in 0..100 {
+        assert_eq!(
+            memtable.get(KeySlice::from_slice(&key_of(idx), 1)).unwrap(),
+            value_of(idx)
+        );
+    }
+}
+
+#[test]
+fn test_arena_memtable_concurrent_insert() {
+    let memtable = Arc::new(MemTable::create(0));
+    let threads: Vec<_> = (0..4)
+        .map(|thread| {
+            let memtable = memtable.clone();
+            std::thread::spawn(move || {
+                for idx in 0..1000 {
+                    let idx = idx * 4 + thread;
+                    memtable
+                        .put(KeySlice::from_slice(&key_of(idx), 1), &value_of(idx))
+                        .unwrap();
+                    assert_eq!(
+                        memtable.get(KeySlice::from_slice(&key_of(idx), 1)).unwrap(),
+                        value_of(idx)
+                    );
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let keys = collect(memtable.scan(Bound::Unbounded, Bound::Unbounded));
+    assert_eq!(
+        keys,
+        (0..4000).map(|idx| (key_of(idx), 1)).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_arena_memtable_many_versions() {
+    let memtable = MemTable::create(0);
+    for ts in 1..=20000 {
+        memtable
+            .put(KeySlice::from_slice(b"key", ts), &value_of(ts as usize))
+            .unwrap();
+    }
+    // The versions of a key get different heights, so that lookups among them stay fast.
+    for ts in (1..=20000).step_by(97) {
+        assert_eq!(
+            memtable.get(KeySlice::from_slice(b"key", ts)).unwrap(),
+            value_of(ts as usize)
+        );
+    }
+    let keys = collect(memtable.scan(
+        Bound::Included(KeySlice::from_slice(b"key", 100)),
+        Bound::Included(KeySlice::from_slice(b"key", 1)),
+    ));
+    assert_eq!(
+        keys,
+        (1..=100)
+            .rev()
+            .map(|ts| (b"key".to_vec(), ts))
+            .collect::<Vec<_>>()
+    );
+}
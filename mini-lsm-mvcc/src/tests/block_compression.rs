@@ -0,0 +1,151 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use rand::Rng;
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    key::KeySlice,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    table::{
+        FileObject, SsTable, SsTableBuilder, SsTableIterator,
+        compression::{CompressionCodec, CompressionType, Lz4Compression},
+    },
+};
+
+use super::harness::{check_iter_result_by_key, key_of};
+
+fn json_value_of(idx: usize) -> Vec<u8> {
+    format!(
+        r#"{{"id":{},"name":"user{}","tags":["alpha","beta","gamma"],"active":true}}"#,
+        idx, idx
+    )
+    .into_bytes()
+}
+
+fn block_compression_types(sst: &SsTable) -> Vec<u8> {
+    (0..sst.num_of_blocks())
+        .map(|idx| {
+            let offset_end = sst
+                .block_meta
+                .get(idx + 1)
+                .map_or(sst.block_meta_offset, |x| x.offset);
+            sst.file.read(offset_end as u64 - 5, 1).unwrap()[0]
+        })
+        .collect()
+}
+
+#[test]
+fn test_lz4_codec_roundtrip() {
+    let data = json_value_of(0).repeat(10);
+    let mut compressed = Vec::new();
+    Lz4Compression.compress(&data, &mut compressed);
+    assert!(compressed.len() < data.len());
+    assert_eq!(Lz4Compression.decompress(&compressed).unwrap(), data);
+}
+
+#[test]
+fn test_sst_with_compression() {
+    let dir = tempdir().unwrap();
+    let mut data = Vec::new();
+    let mut raw_builder = SsTableBuilder::new(4096);
+    let mut lz4_builder = SsTableBuilder::new(4096).with_compression(CompressionType::Lz4);
+    for idx in 0..1000 {
+        let (key, value) = (key_of(idx), json_value_of(idx));
+        raw_builder.add(KeySlice::for_testing_from_slice_no_ts(&key), &value);
+        lz4_builder.add(KeySlice::for_testing_from_slice_no_ts(&key), &value);
+        data.push((Bytes::from(key), Bytes::from(value)));
+    }
+    let raw_sst = raw_builder
+        .build_for_test(dir.path().join("1.sst"))
+        .unwrap();
+    let lz4_sst = lz4_builder
+        .build_for_test(dir.path().join("2.sst"))
+        .unwrap();
+    assert!(lz4_sst.table_size() < raw_sst.table_size() / 2);
+    assert!(
+        block_compression_types(&lz4_sst)
+            .iter()
+            .all(|x| *x == Lz4Compression.id())
+    );
+
+    let sst = Arc::new(
+        SsTable::open(
+            2,
+            None,
+            FileObject::open(&dir.path().join("2.sst")).unwrap(),
+        )
+        .unwrap(),
+    );
+    check_iter_result_by_key(
+        &mut SsTableIterator::create_and_seek_to_first(sst).unwrap(),
+        data,
+    );
+}
+
+#[test]
+fn test_sst_mixed_compressed_and_raw_blocks() {
+    let dir = tempdir().unwrap();
+    let mut rng = rand::thread_rng();
+    let mut data = Vec::new();
+    let mut builder = SsTableBuilder::new(512).with_compression(CompressionType::Lz4);
+    for idx in 0..100 {
+        let key = key_of(idx);
+        let value = if idx < 50 {
+            json_value_of(idx)
+        } else {
+            (0..1024).map(|_| rng.r#gen::<u8>()).collect()
+        };
+        builder.add(KeySlice::for_testing_from_slice_no_ts(&key), &value);
+        data.push((Bytes::from(key), Bytes::from(value)));
+    }
+    let sst = Arc::new(builder.build_for_test(dir.path().join("1.sst")).unwrap());
+    let types = block_compression_types(&sst);
+    assert!(types.contains(&0));
+    assert!(types.contains(&Lz4Compression.id()));
+    check_iter_result_by_key(
+        &mut SsTableIterator::create_and_seek_to_first(sst).unwrap(),
+        data,
+    );
+}
+
+#[test]
+fn test_storage_with_compression() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.compression = CompressionType::Lz4;
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for idx in 0..1000 {
+        storage.put(&key_of(idx), &json_value_of(idx)).unwrap();
+    }
+    storage.force_flush().unwrap();
+    for idx in 0..1000 {
+        assert_eq!(
+            storage.get(&key_of(idx)).unwrap(),
+            Some(Bytes::from(json_value_of(idx)))
+        );
+    }
+    let snapshot = storage.inner.state.read().clone();
+    for sst in snapshot.sstables.values() {
+        assert!(
+            block_compression_types(sst)
+                .iter()
+                .any(|x| *x == Lz4Compression.id())
+        );
+    }
+}
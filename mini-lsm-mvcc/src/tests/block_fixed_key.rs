@@ -0,0 +1,171 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Bound;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use rand::Rng;
+use tempfile::tempdir;
+
+use crate::{
+    block::{Block, BlockBuilder, BlockIterator, BlockLayout},
+    compact::CompactionOptions,
+    key::KeySlice,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    table::{FileObject, SsTable, SsTableBuilder, SsTableIterator},
+};
+
+use super::harness::{check_iter_result_by_key, check_lsm_iter_result_by_key, value_of};
+
+const KEY_LEN: usize = 16;
+
+/// A big-endian (ts, id) tuple.
+fn key_of(idx: usize) -> Vec<u8> {
+    let mut key = (1_700_000_000u64 + idx as u64 / 4).to_be_bytes().to_vec();
+    key.extend((idx as u64 * 2).to_be_bytes());
+    key
+}
+
+fn generate_block(layout: BlockLayout) -> Block {
+    let mut builder = BlockBuilder::new(1 << 20)
+        .with_restart_interval(4)
+        .with_layout(layout);
+    for idx in 0..100 {
+        assert!(builder.add(
+            KeySlice::for_testing_from_slice_no_ts(&key_of(idx)),
+            &value_of(idx)
+        ));
+    }
+    builder.build()
+}
+
+#[test]
+fn test_block_fixed_key_layout() {
+    let layout = BlockLayout::FixedKey(KEY_LEN);
+    let block = Arc::new(Block::decode_with_layout(
+        generate_block(layout).encode(),
+        layout,
+    ));
+    let mut iter = BlockIterator::create_and_seek_to_first(block.clone());
+    for idx in 0..100 {
+        assert_eq!(iter.key().key_ref(), key_of(idx));
+        assert_eq!(iter.value(), value_of(idx));
+        iter.next();
+    }
+    assert!(!iter.is_valid());
+    for idx in 0..100 {
+        let iter = BlockIterator::create_and_seek_to_key(
+            block.clone(),
+            KeySlice::for_testing_from_slice_no_ts(&key_of(idx)),
+        );
+        assert_eq!(iter.value(), value_of(idx));
+        let mut missing = key_of(idx);
+        missing[KEY_LEN - 1] += 1;
+        let iter = BlockIterator::create_and_seek_for_prev(
+            block.clone(),
+            KeySlice::for_testing_from_slice_no_ts(&missing),
+        );
+        assert_eq!(iter.value(), value_of(idx));
+    }
+}
+
+#[test]
+fn test_block_fixed_key_layout_smaller_for_uuids() {
+    let mut rng = rand::thread_rng();
+    let mut keys = (0..100)
+        .map(|_| rng.r#gen::<[u8; KEY_LEN]>())
+        .collect::<Vec<_>>();
+    keys.sort();
+    let size_of = |layout| {
+        let mut builder = BlockBuilder::new(1 << 20).with_layout(layout);
+        for key in &keys {
+            assert!(builder.add(KeySlice::for_testing_from_slice_no_ts(key), b"v"));
+        }
+        builder.build().encode().len()
+    };
+    let fixed = size_of(BlockLayout::FixedKey(KEY_LEN));
+    let variable = size_of(BlockLayout::Variable);
+    // Random keys share almost no prefix, so each entry saves most of its two length headers.
+    assert!(
+        fixed + 100 < variable,
+        "fixed layout is {} bytes, variable layout is {} bytes",
+        fixed,
+        variable
+    );
+}
+
+#[test]
+#[should_panic(expected = "key does not match the block layout")]
+fn test_block_fixed_key_layout_wrong_key_len() {
+    let mut builder = BlockBuilder::new(4096).with_layout(BlockLayout::FixedKey(KEY_LEN));
+    let _ = builder.add(KeySlice::for_testing_from_slice_no_ts(b"short"), b"v");
+}
+
+#[test]
+fn test_sst_fixed_key_layout() {
+    let dir = tempdir().unwrap();
+    let mut builder = SsTableBuilder::new(256).with_block_layout(BlockLayout::FixedKey(KEY_LEN));
+    let mut data = Vec::new();
+    for idx in 0..200 {
+        builder.add(
+            KeySlice::for_testing_from_slice_no_ts(&key_of(idx)),
+            &value_of(idx),
+        );
+        data.push((Bytes::from(key_of(idx)), Bytes::from(value_of(idx))));
+    }
+    builder.build_for_test(dir.path().join("1.sst")).unwrap();
+    let sst = Arc::new(
+        SsTable::open(
+            1,
+            None,
+            FileObject::open(&dir.path().join("1.sst")).unwrap(),
+        )
+        .unwrap(),
+    );
+    assert_eq!(sst.block_layout(), BlockLayout::FixedKey(KEY_LEN));
+    assert!(sst.num_of_blocks() > 1);
+    check_iter_result_by_key(
+        &mut SsTableIterator::create_and_seek_to_first(sst).unwrap(),
+        data,
+    );
+}
+
+#[test]
+fn test_storage_fixed_key_layout() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.block_layout = BlockLayout::FixedKey(KEY_LEN);
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for idx in 0..500 {
+        storage.put(&key_of(idx), &value_of(idx)).unwrap();
+    }
+    storage.force_flush().unwrap();
+    let err = storage.put(b"short", b"v").unwrap_err();
+    assert!(err.to_string().contains("fixed key length"), "{}", err);
+    for idx in 0..500 {
+        assert_eq!(
+            storage.get(&key_of(idx)).unwrap(),
+            Some(Bytes::from(value_of(idx)))
+        );
+    }
+    check_lsm_iter_result_by_key(
+        &mut storage
+            .scan(Bound::Included(&key_of(100)), Bound::Excluded(&key_of(103)))
+            .unwrap(),
+        (100..103)
+            .map(|idx| (Bytes::from(key_of(idx)), Bytes::from(value_of(idx))))
+            .collect(),
+    );
+}
@@ -0,0 +1,137 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    block::{Block, BlockBuilder, BlockIterator},
+    compact::CompactionOptions,
+    key::{KeySlice, TS_RANGE_BEGIN},
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+};
+
+fn key_of(idx: usize) -> Vec<u8> {
+    format!("key_{:05}", idx * 2).into_bytes()
+}
+
+fn value_of(idx: usize, ts: u64) -> Vec<u8> {
+    format!("value_{:05}@{}", idx, ts).into_bytes()
+}
+
+/// Each key has three versions, so that versions of a key may span restart intervals.
+fn generate_block(hash_index: bool) -> Block {
+    let mut builder = BlockBuilder::new(1 << 15)
+        .with_restart_interval(2)
+        .with_hash_index(hash_index);
+    for idx in 0..100 {
+        for ts in (1..=3).rev() {
+            assert!(builder.add(
+                KeySlice::for_testing_from_slice_with_ts(&key_of(idx), ts),
+                &value_of(idx, ts)
+            ));
+        }
+    }
+    builder.build()
+}
+
+fn check_point_lookups(block: Arc<Block>) {
+    for idx in 0..100 {
+        let key = key_of(idx);
+        let iter = BlockIterator::create_and_seek_for_get(
+            block.clone(),
+            KeySlice::from_slice(&key, TS_RANGE_BEGIN),
+        );
+        assert!(iter.is_valid());
+        assert_eq!(iter.key().key_ref(), key);
+        assert_eq!(iter.key().ts(), 3);
+        assert_eq!(iter.value(), value_of(idx, 3));
+
+        let iter =
+            BlockIterator::create_and_seek_for_get(block.clone(), KeySlice::from_slice(&key, 2));
+        assert_eq!(iter.value(), value_of(idx, 2));
+
+        let missing = format!("key_{:05}", idx * 2 + 1).into_bytes();
+        let iter = BlockIterator::create_and_seek_for_get(
+            block.clone(),
+            KeySlice::from_slice(&missing, TS_RANGE_BEGIN),
+        );
+        assert!(!iter.is_valid() || iter.key().key_ref() > &missing[..]);
+    }
+}
+
+#[test]
+fn test_block_hash_index_encode_decode() {
+    let block = generate_block(true);
+    assert!(block.hash_index.is_some());
+    let decoded = Block::decode(&block.encode());
+    assert_eq!(decoded.hash_index, block.hash_index);
+    assert_eq!(decoded.restarts(), block.restarts());
+    assert_eq!(decoded.data, block.data);
+    check_point_lookups(Arc::new(decoded));
+}
+
+#[test]
+fn test_block_without_hash_index() {
+    let block = generate_block(false);
+    assert!(block.hash_index.is_none());
+    let decoded = Block::decode(&block.encode());
+    assert!(decoded.hash_index.is_none());
+    check_point_lookups(Arc::new(decoded));
+}
+
+#[test]
+fn test_block_too_many_restarts_for_hash_index() {
+    let mut builder = BlockBuilder::new(1 << 15)
+        .with_restart_interval(1)
+        .with_hash_index(true);
+    for idx in 0..300 {
+        assert!(builder.add(KeySlice::for_testing_from_slice_no_ts(&key_of(idx)), b"v"));
+    }
+    let block = builder.build();
+    assert!(block.hash_index.is_none());
+}
+
+#[test]
+fn test_storage_with_hash_index() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.block_hash_index = true;
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for ts in 1..=3 {
+        for idx in 0..500 {
+            storage.put(&key_of(idx), &value_of(idx, ts)).unwrap();
+        }
+        storage.force_flush().unwrap();
+    }
+    for idx in (0..500).step_by(3) {
+        storage.delete(&key_of(idx)).unwrap();
+    }
+    storage.force_flush().unwrap();
+    let snapshot = storage.inner.state.read().clone();
+    let sst = snapshot.sstables[&snapshot.l0_sstables[0]].clone();
+    assert!(sst.read_block(0).unwrap().hash_index.is_some());
+    for idx in 0..500 {
+        let expected = if idx % 3 == 0 {
+            None
+        } else {
+            Some(Bytes::from(value_of(idx, 3)))
+        };
+        assert_eq!(storage.get(&key_of(idx)).unwrap(), expected);
+        let missing = format!("key_{:05}", idx * 2 + 1).into_bytes();
+        assert_eq!(storage.get(&missing).unwrap(), None);
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    block::{Block, BlockBuilder, BlockIterator},
+    key::{KeySlice, KeyVec},
+    table::{SsTableBuilder, SsTableIterator},
+};
+
+use super::harness::{check_iter_result_by_key, value_of};
+
+/// Keys share a long prefix with their neighbours but the prefix drifts away from the first key.
+fn key_of(idx: usize) -> KeyVec {
+    KeyVec::for_testing_from_vec_no_ts(
+        format!("tenant/{:04}/events/{:06}", idx / 10, idx).into_bytes(),
+    )
+}
+
+fn generate_block(restart_interval: usize, num_keys: usize) -> Block {
+    let mut builder = BlockBuilder::new(1 << 20).with_restart_interval(restart_interval);
+    for idx in 0..num_keys {
+        assert!(builder.add(key_of(idx).as_key_slice(), &value_of(idx)));
+    }
+    builder.build()
+}
+
+#[test]
+fn test_block_restart_points() {
+    for (restart_interval, expected_restarts) in [(1, 100), (3, 34), (16, 7), (200, 1)] {
+        let block = generate_block(restart_interval, 100);
+        assert_eq!(block.num_restarts(), expected_restarts);
+        let decoded = Block::decode(&block.encode());
+        assert_eq!(decoded.restarts(), block.restarts());
+    }
+}
+
+#[test]
+fn test_block_delta_encoding_smaller() {
+    let full_keys = generate_block(1, 100).encode();
+    let delta_keys = generate_block(16, 100).encode();
+    assert!(
+        delta_keys.len() * 3 < full_keys.len() * 2,
+        "delta encoded block is {} bytes, full key block is {} bytes",
+        delta_keys.len(),
+        full_keys.len()
+    );
+}
+
+#[test]
+fn test_block_seek_with_restarts() {
+    for restart_interval in [1, 2, 7, 16, 200] {
+        let block = Arc::new(generate_block(restart_interval, 100));
+        let mut iter = BlockIterator::create_and_seek_to_first(block.clone());
+        for idx in 0..100 {
+            assert!(iter.is_valid());
+            assert_eq!(iter.key(), key_of(idx).as_key_slice());
+            assert_eq!(iter.value(), value_of(idx));
+            iter.next();
+        }
+        assert!(!iter.is_valid());
+
+        for idx in 0..100 {
+            let mut iter =
+                BlockIterator::create_and_seek_to_key(block.clone(), key_of(idx).as_key_slice());
+            assert_eq!(iter.key(), key_of(idx).as_key_slice());
+            // Seek to a key right after `key_of(idx)`, which lands on the next entry.
+            let mut after = key_of(idx).key_ref().to_vec();
+            after.push(0);
+            iter.seek_to_key(KeySlice::for_testing_from_slice_no_ts(&after));
+            if idx + 1 < 100 {
+                assert_eq!(iter.key(), key_of(idx + 1).as_key_slice());
+            } else {
+                assert!(!iter.is_valid());
+            }
+        }
+        iter.seek_to_key(KeySlice::for_testing_from_slice_no_ts(b"a"));
+        assert_eq!(iter.key(), key_of(0).as_key_slice());
+    }
+}
+
+#[test]
+fn test_sst_with_restart_interval() {
+    let dir = tempdir().unwrap();
+    let mut builder = SsTableBuilder::new(256).with_restart_interval(4);
+    let mut data = Vec::new();
+    for idx in 0..200 {
+        builder.add(key_of(idx).as_key_slice(), &value_of(idx));
+        data.push((
+            Bytes::copy_from_slice(key_of(idx).key_ref()),
+            Bytes::from(value_of(idx)),
+        ));
+    }
+    let sst = Arc::new(builder.build_for_test(dir.path().join("1.sst")).unwrap());
+    assert!(sst.num_of_blocks() > 1);
+    assert!(sst.read_block(0).unwrap().num_restarts() > 1);
+    check_iter_result_by_key(
+        &mut SsTableIterator::create_and_seek_to_first(sst).unwrap(),
+        data,
+    );
+}
+
+#[test]
+fn test_block_decode_from_bytes_shares_buffer() {
+    let block = generate_block(4, 100);
+    let encoded = block.encode();
+    let decoded = Block::decode_from_bytes(encoded.clone());
+    assert_eq!(decoded.data.as_ptr(), encoded.as_ptr());
+    assert_eq!(
+        decoded.restarts().as_ptr(),
+        encoded[block.data.len()..].as_ptr()
+    );
+    assert_eq!(decoded.num_restarts(), 25);
+    for idx in 0..decoded.num_restarts() {
+        assert_eq!(decoded.restart(idx), block.restart(idx));
+    }
+    let mut iter = BlockIterator::create_and_seek_to_first(Arc::new(decoded));
+    for idx in 0..100 {
+        assert_eq!(iter.key(), key_of(idx).as_key_slice());
+        iter.next();
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use super::harness::key_of;
+use crate::{
+    block::{Block, BlockBuilder, BlockIterator},
+    key::KeySlice,
+    varint::{zigzag_decode, zigzag_encode},
+};
+
+fn generate_block(entries: &[(Vec<u8>, u64)]) -> Block {
+    let mut builder = BlockBuilder::new(1 << 20);
+    for (key, ts) in entries {
+        assert!(builder.add(KeySlice::for_testing_from_slice_with_ts(key, *ts), b"v"));
+    }
+    builder.build()
+}
+
+fn check_block(block: Block, entries: &[(Vec<u8>, u64)]) {
+    let mut iter = BlockIterator::create_and_seek_to_first(Arc::new(block));
+    for (key, ts) in entries {
+        assert!(iter.is_valid());
+        assert_eq!(iter.key().key_ref(), key);
+        assert_eq!(iter.key().ts(), *ts);
+        iter.next();
+    }
+    assert!(!iter.is_valid());
+}
+
+#[test]
+fn test_zigzag() {
+    for value in [0, 1, -1, 63, -64, 64, i64::MAX, i64::MIN] {
+        assert_eq!(zigzag_decode(zigzag_encode(value)), value);
+    }
+    assert_eq!(zigzag_encode(0), 0);
+    assert_eq!(zigzag_encode(-1), 1);
+    assert_eq!(zigzag_encode(1), 2);
+}
+
+#[test]
+fn test_block_ts_delta_roundtrip() {
+    // Versions of a key are sorted by descending ts, so deltas from the base go both ways.
+    let mut entries = Vec::new();
+    for idx in 0..100 {
+        for ts in [1_000_050 - idx as u64, 1_000_000 + idx as u64 * 3] {
+            entries.push((key_of(idx), ts));
+        }
+    }
+    let block = generate_block(&entries);
+    assert_eq!(block.base_ts, 1_000_050);
+    check_block(Block::decode(&block.encode()), &entries);
+
+    let entries = vec![
+        (key_of(0), u64::MAX),
+        (key_of(1), 0),
+        (key_of(2), u64::MAX / 2),
+        (key_of(3), 1),
+    ];
+    check_block(Block::decode(&generate_block(&entries).encode()), &entries);
+}
+
+#[test]
+fn test_block_ts_delta_smaller() {
+    let close = (0..100)
+        .map(|idx| (key_of(idx), (1 << 40) + idx as u64))
+        .collect::<Vec<_>>();
+    let far = (0..100)
+        .map(|idx| (key_of(idx), (idx as u64) << 56))
+        .collect::<Vec<_>>();
+    let close_size = generate_block(&close).encode().len();
+    let far_size = generate_block(&far).encode().len();
+    // Close timestamps take one byte each, far apart ones take up to nine.
+    assert!(
+        close_size + 100 * 6 < far_size,
+        "block with close ts is {} bytes, block with far ts is {} bytes",
+        close_size,
+        far_size
+    );
+}
@@ -0,0 +1,319 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use super::harness::{generate_sst_of, key_of, value_of};
+use crate::{
+    compact::{CompactionOptions, SimpleLeveledCompactionOptions},
+    error::{CorruptedFile, CorruptedSection, CorruptionError},
+    lsm_storage::{BlockCache, CorruptionPolicy, LsmStorageInner, LsmStorageOptions, MiniLsm},
+    table::{FileObject, SsTable, SsTableBuilder},
+};
+
+fn flip_byte(path: &Path, offset: usize) {
+    let mut data = std::fs::read(path).unwrap();
+    data[offset] ^= 0xff;
+    std::fs::write(path, data).unwrap();
+}
+
+fn corruption_of(err: &anyhow::Error) -> CorruptionError {
+    err.downcast_ref::<CorruptionError>()
+        .unwrap_or_else(|| panic!("not a corruption error: {}", err))
+        .clone()
+}
+
+fn build_sst(path: &Path) -> SsTable {
+    generate_sst_of(SsTableBuilder::new(128), 0..100, 7, path, None)
+}
+
+#[test]
+fn test_sst_block_corruption() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("7.sst");
+    let sst = build_sst(&path);
+    let offset = sst.block_meta[3].offset;
+    flip_byte(&path, offset + 2);
+    let sst = SsTable::open(7, None, FileObject::open(&path).unwrap()).unwrap();
+    assert!(sst.read_block(2).is_ok());
+    let err = sst.read_block(3).err().unwrap();
+    let corruption = corruption_of(&err);
+    assert_eq!(corruption.file, CorruptedFile::Sst(7));
+    assert_eq!(corruption.section, CorruptedSection::DataBlock(3));
+    assert_eq!(corruption.offset, offset as u64);
+    assert_ne!(corruption.expected_checksum, corruption.actual_checksum);
+    assert!(err.to_string().contains("SST 7 data block 3"), "{}", err);
+}
+
+#[test]
+fn test_cached_block_corruption() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("7.sst");
+    let sst = build_sst(&path);
+    flip_byte(&path, sst.block_meta[3].offset + 2);
+    let sst = SsTable::open(
+        7,
+        Some(Arc::new(BlockCache::new(1024))),
+        FileObject::open(&path).unwrap(),
+    )
+    .unwrap();
+    let err = sst.read_block_cached(3).err().unwrap();
+    assert_eq!(corruption_of(&err).section, CorruptedSection::DataBlock(3));
+}
+
+#[test]
+fn test_sst_meta_corruption() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("7.sst");
+    let sst = build_sst(&path);
+    let meta_offset = sst.block_meta_offset;
+    flip_byte(&path, meta_offset + 10);
+    let err = SsTable::open(7, None, FileObject::open(&path).unwrap())
+        .err()
+        .unwrap();
+    let corruption = corruption_of(&err);
+    assert_eq!(corruption.file, CorruptedFile::Sst(7));
+    assert_eq!(corruption.section, CorruptedSection::BlockMeta);
+    assert_eq!(corruption.offset, meta_offset as u64);
+}
+
+/// Create a database with one corrupted SST, and return the id of the SST.
+fn create_with_corrupted_sst(dir: &Path, options: &LsmStorageOptions) -> usize {
+    let storage = MiniLsm::open(dir, options.clone()).unwrap();
+    for idx in 0..100 {
+        storage.put(&key_of(idx), &value_of(idx)).unwrap();
+    }
+    storage.force_flush().unwrap();
+    let corrupted_id = storage.inner.state.read().l0_sstables[0];
+    storage.put(b"another", b"value").unwrap();
+    storage.close().unwrap();
+    drop(storage);
+
+    let sst_path = LsmStorageInner::path_of_sst_static(dir, corrupted_id);
+    let meta_offset = SsTable::open(0, None, FileObject::open(&sst_path).unwrap())
+        .unwrap()
+        .block_meta_offset;
+    flip_byte(&sst_path, meta_offset + 10);
+    corrupted_id
+}
+
+#[test]
+fn test_open_with_corrupted_sst() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let corrupted_id = create_with_corrupted_sst(dir.path(), &options);
+    let sst_path = LsmStorageInner::path_of_sst_static(&dir, corrupted_id);
+
+    let err = MiniLsm::open(&dir, options.clone()).err().unwrap();
+    assert_eq!(corruption_of(&err).file, CorruptedFile::Sst(corrupted_id));
+
+    let mut skip_options = options.clone();
+    skip_options.corruption_policy = CorruptionPolicy::Skip;
+    let storage = MiniLsm::open(&dir, skip_options).unwrap();
+    assert_eq!(storage.get(&key_of(0)).unwrap(), None);
+    assert_eq!(
+        storage.get(b"another").unwrap(),
+        Some(Bytes::from_static(b"value"))
+    );
+    storage.close().unwrap();
+    drop(storage);
+    assert!(sst_path.exists());
+
+    // The skipped SST is dropped from the manifest, so the default policy no longer sees it.
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    assert_eq!(storage.get(&key_of(0)).unwrap(), None);
+}
+
+#[test]
+fn test_quarantine_corrupted_sst() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let corrupted_id = create_with_corrupted_sst(dir.path(), &options);
+    let sst_path = LsmStorageInner::path_of_sst_static(&dir, corrupted_id);
+
+    let mut quarantine_options = options.clone();
+    quarantine_options.corruption_policy = CorruptionPolicy::Quarantine;
+    let storage = MiniLsm::open(&dir, quarantine_options).unwrap();
+    assert_eq!(storage.get(&key_of(0)).unwrap(), None);
+    storage.close().unwrap();
+    drop(storage);
+    assert!(!sst_path.exists());
+    assert!(
+        LsmStorageInner::path_of_quarantine_static(&dir)
+            .join(sst_path.file_name().unwrap())
+            .exists()
+    );
+
+    // The quarantined file is left out even with the default policy.
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    assert_eq!(
+        storage.get(b"another").unwrap(),
+        Some(Bytes::from_static(b"value"))
+    );
+}
+
+#[test]
+fn test_open_with_corrupted_wal() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.enable_wal = true;
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    let wal_id = storage.inner.state.read().memtable.id();
+    for idx in 0..10 {
+        storage.put(&key_of(idx), &value_of(idx)).unwrap();
+    }
+    storage.close().unwrap();
+    drop(storage);
+
+    let wal_path = LsmStorageInner::path_of_wal_static(&dir, wal_id);
+    let batch_size = std::fs::read(&wal_path).unwrap().len() / 10;
+    // Corrupt the key of the third batch.
+    flip_byte(&wal_path, batch_size * 2 + 6);
+
+    let err = MiniLsm::open(&dir, options.clone()).err().unwrap();
+    let corruption = corruption_of(&err);
+    assert_eq!(corruption.file, CorruptedFile::Wal(wal_id));
+    assert_eq!(corruption.section, CorruptedSection::WalBatch);
+    assert_eq!(corruption.offset, (batch_size * 2) as u64);
+
+    // The batches before the corrupted one are kept, and the WAL is truncated to them.
+    options.corruption_policy = CorruptionPolicy::Quarantine;
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    assert_eq!(
+        storage.get(&key_of(1)).unwrap(),
+        Some(Bytes::from(value_of(1)))
+    );
+    assert_eq!(storage.get(&key_of(2)).unwrap(), None);
+    storage.close().unwrap();
+    drop(storage);
+    assert_eq!(
+        std::fs::metadata(&wal_path).unwrap().len(),
+        (batch_size * 2) as u64
+    );
+    assert!(
+        LsmStorageInner::path_of_quarantine_static(&dir)
+            .join(wal_path.file_name().unwrap())
+            .exists()
+    );
+
+    options.corruption_policy = CorruptionPolicy::Fail;
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    assert_eq!(
+        storage.get(&key_of(1)).unwrap(),
+        Some(Bytes::from(value_of(1)))
+    );
+}
+
+#[test]
+fn test_open_with_incomplete_wal() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.enable_wal = true;
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    let wal_id = storage.inner.state.read().memtable.id();
+    for idx in 0..10 {
+        storage.put(&key_of(idx), &value_of(idx)).unwrap();
+    }
+    storage.close().unwrap();
+    drop(storage);
+
+    // Cut the last batch short, as a crash in the middle of a write would.
+    let wal_path = LsmStorageInner::path_of_wal_static(&dir, wal_id);
+    let len = std::fs::metadata(&wal_path).unwrap().len();
+    let batch_size = len / 10;
+    std::fs::File::options()
+        .write(true)
+        .open(&wal_path)
+        .unwrap()
+        .set_len(len - 3)
+        .unwrap();
+
+    let err = MiniLsm::open(&dir, options.clone()).err().unwrap();
+    let corruption = corruption_of(&err);
+    assert_eq!(corruption.file, CorruptedFile::Wal(wal_id));
+    assert_eq!(corruption.section, CorruptedSection::IncompleteWalBatch);
+    assert_eq!(corruption.offset, batch_size * 9);
+
+    options.corruption_policy = CorruptionPolicy::Skip;
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    assert_eq!(
+        storage.get(&key_of(8)).unwrap(),
+        Some(Bytes::from(value_of(8)))
+    );
+    assert_eq!(storage.get(&key_of(9)).unwrap(), None);
+}
+
+#[test]
+fn test_skipped_sst_is_recorded_in_manifest() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::Simple(
+        SimpleLeveledCompactionOptions {
+            size_ratio_percent: 200,
+            level0_file_num_compaction_trigger: 2,
+            max_levels: 1,
+        },
+    ));
+    options.corruption_policy = CorruptionPolicy::Skip;
+    let wait_for_l1 = |storage: &MiniLsm| {
+        for _ in 0..100 {
+            let state = storage.inner.state.read();
+            if state.l0_sstables.is_empty() && !state.levels[0].1.is_empty() {
+                return state.levels[0].1.clone();
+            }
+            drop(state);
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        panic!("L0 is not compacted");
+    };
+
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    for round in 0..2 {
+        storage.put(&key_of(round), &value_of(round)).unwrap();
+        storage.force_flush().unwrap();
+    }
+    let corrupted = wait_for_l1(&storage);
+    let max_ts = storage.inner.mvcc().latest_commit_ts();
+    storage.close().unwrap();
+    drop(storage);
+    for &id in &corrupted {
+        let sst_path = LsmStorageInner::path_of_sst_static(&dir, id);
+        let meta_offset = SsTable::open(0, None, FileObject::open(&sst_path).unwrap())
+            .unwrap()
+            .block_meta_offset;
+        flip_byte(&sst_path, meta_offset + 10);
+    }
+
+    // Compact L0 into the L1 the corrupted SSTs were dropped from, and open the database again.
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    assert!(storage.inner.mvcc().latest_commit_ts() >= max_ts);
+    for round in 2..4 {
+        storage.put(&key_of(round), &value_of(round)).unwrap();
+        storage.force_flush().unwrap();
+    }
+    wait_for_l1(&storage);
+    storage.close().unwrap();
+    drop(storage);
+
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    assert_eq!(storage.get(&key_of(0)).unwrap(), None);
+    assert_eq!(
+        storage.get(&key_of(3)).unwrap(),
+        Some(Bytes::from(value_of(3)))
+    );
+}
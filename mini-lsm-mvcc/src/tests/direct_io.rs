@@ -0,0 +1,104 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use super::harness::{key_of, value_of};
+use crate::{
+    compact::CompactionOptions,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    table::{FileObject, direct_io::DIRECT_IO_ALIGNMENT},
+};
+
+#[test]
+fn test_direct_io_file_object() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    let data = (0..DIRECT_IO_ALIGNMENT * 2 + 100)
+        .map(|idx| idx as u8)
+        .collect::<Vec<_>>();
+    let file = FileObject::create_direct(&path, data.clone()).unwrap();
+    assert!(file.is_direct());
+    // The padding of the last page is truncated.
+    assert_eq!(std::fs::read(&path).unwrap(), data);
+    assert_eq!(file.size(), data.len() as u64);
+
+    let size = data.len() as u64;
+    for (offset, len) in [
+        (0, size),
+        (1, 10),
+        (4090, 20),
+        (DIRECT_IO_ALIGNMENT as u64, 100),
+        (size - 5, 5),
+    ] {
+        let range = offset as usize..(offset + len) as usize;
+        assert_eq!(file.read_bytes(offset, len).unwrap(), data[range.clone()]);
+        assert_eq!(file.read(offset, len).unwrap(), data[range]);
+    }
+    assert!(file.read_bytes(size - 1, 2).is_err());
+
+    let file = FileObject::open_direct(&path).unwrap();
+    assert!(file.is_direct());
+    assert_eq!(file.read_bytes(0, size).unwrap(), data);
+
+    let path = dir.path().join("2.sst");
+    FileObject::create_direct(&path, Vec::new()).unwrap();
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+}
+
+#[test]
+fn test_direct_io_storage() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.direct_io = true;
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    for idx in 0..1000 {
+        storage.put(&key_of(idx), &value_of(idx)).unwrap();
+    }
+    storage.force_flush().unwrap();
+    for idx in (0..1000).step_by(3) {
+        storage.delete(&key_of(idx)).unwrap();
+    }
+    storage.force_flush().unwrap();
+    storage.close().unwrap();
+    drop(storage);
+
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    storage.force_full_compaction().unwrap();
+    {
+        let snapshot = storage.inner.state.read();
+        assert!(!snapshot.sstables.is_empty());
+        assert!(snapshot.sstables.values().all(|sst| sst.file.is_direct()));
+    }
+    for idx in 0..1000 {
+        let expected = (idx % 3 != 0).then(|| Bytes::from(value_of(idx)));
+        assert_eq!(storage.get(&key_of(idx)).unwrap(), expected);
+    }
+    {
+        // Each data block starts and ends on an aligned offset.
+        let snapshot = storage.inner.state.read();
+        for sst in snapshot.sstables.values() {
+            assert!(sst.num_of_blocks() > 1);
+            for meta in sst.block_meta.iter() {
+                assert_eq!(meta.offset % DIRECT_IO_ALIGNMENT, 0);
+            }
+            assert_eq!(sst.block_meta_offset % DIRECT_IO_ALIGNMENT, 0);
+        }
+    }
+    drop(storage);
+
+    options.mmap_sst = true;
+    assert!(MiniLsm::open(&dir, options).is_err());
+}
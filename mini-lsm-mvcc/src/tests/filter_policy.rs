@@ -0,0 +1,170 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use super::harness::key_of;
+use crate::{
+    compact::CompactionOptions,
+    key::KeySlice,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    table::{
+        FileObject, SsTable, SsTableBuilder,
+        bloom::Bloom,
+        filter::{FilterPolicy, FilterSize, FilterType},
+    },
+};
+
+fn false_positives(bloom: &Bloom) -> usize {
+    (10000..20000)
+        .filter(|idx| bloom.may_contain(farmhash::fingerprint32(&key_of(*idx))))
+        .count()
+}
+
+#[test]
+fn test_filter_types() {
+    let hashes = (0..10000)
+        .map(|idx| farmhash::fingerprint32(&key_of(idx)))
+        .collect::<Vec<_>>();
+    for filter_type in [FilterType::Bloom, FilterType::BlockedBloom] {
+        let small = Bloom::build(filter_type, &hashes, 5);
+        let large = Bloom::build(filter_type, &hashes, 15);
+        for bloom in [&small, &large] {
+            assert!(hashes.iter().all(|h| bloom.may_contain(*h)));
+        }
+        let small_fp = false_positives(&small);
+        let large_fp = false_positives(&large);
+        assert!(
+            small_fp < 1500,
+            "{:?}: {} false positives",
+            filter_type,
+            small_fp
+        );
+        assert!(
+            large_fp < small_fp / 10,
+            "{:?}: {} false positives",
+            filter_type,
+            large_fp
+        );
+
+        let mut buf = Vec::new();
+        large.encode(&mut buf);
+        let decoded = Bloom::decode(&buf, 0, 0).unwrap().unwrap();
+        assert_eq!(decoded.filter_type(), filter_type);
+        assert_eq!(false_positives(&decoded), large_fp);
+    }
+
+    // Filters written before filter types existed end with `k`.
+    let bloom = Bloom::build_from_key_hashes(&hashes, 10);
+    let mut buf = bloom.filter.to_vec();
+    buf.push(bloom.k);
+    buf.extend(crc32fast::hash(&buf).to_be_bytes());
+    let decoded = Bloom::decode(&buf, 0, 0).unwrap().unwrap();
+    assert_eq!(decoded.filter_type(), FilterType::Bloom);
+    assert_eq!(false_positives(&decoded), false_positives(&bloom));
+
+    let mut buf = Vec::new();
+    Bloom::encode_disabled(&mut buf);
+    assert!(Bloom::decode(&buf, 0, 0).unwrap().is_none());
+
+    // Unknown encodings are rejected instead of matching every key.
+    let mut buf = bloom.filter.to_vec();
+    buf.push(31);
+    buf.extend(crc32fast::hash(&buf).to_be_bytes());
+    assert!(Bloom::decode(&buf, 0, 0).is_err());
+}
+
+#[test]
+fn test_filter_policy() {
+    let policy = FilterPolicy {
+        filter_type: FilterType::BlockedBloom,
+        level_sizes: vec![
+            FilterSize::BitsPerKey(20),
+            FilterSize::FalsePositiveRate(0.05),
+        ],
+        disable_on_last_level: true,
+    };
+    assert_eq!(
+        policy.filter_for_level(0, false),
+        Some((FilterType::BlockedBloom, FilterSize::BitsPerKey(20)))
+    );
+    assert_eq!(
+        policy.filter_for_level(3, false),
+        Some((
+            FilterType::BlockedBloom,
+            FilterSize::FalsePositiveRate(0.05)
+        ))
+    );
+    assert_eq!(policy.filter_for_level(3, true), None);
+    let policy = FilterPolicy {
+        level_sizes: vec![],
+        ..Default::default()
+    };
+    assert_eq!(policy.filter_for_level(0, false), None);
+}
+
+#[test]
+fn test_sst_without_filter() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    let mut builder = SsTableBuilder::new(128).with_filter(None);
+    for idx in 0..100 {
+        builder.add(
+            KeySlice::for_testing_from_slice_no_ts(&key_of(idx)),
+            b"value",
+        );
+    }
+    builder.build_for_test(&path).unwrap();
+    let sst = SsTable::open(0, None, FileObject::open(&path).unwrap()).unwrap();
+    assert_eq!(sst.filter_type(), None);
+    assert!(sst.may_contain_prefix(b"key").unwrap());
+}
+
+#[test]
+fn test_storage_filter_policy() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.filter_policy = FilterPolicy {
+        filter_type: FilterType::BlockedBloom,
+        level_sizes: vec![FilterSize::BitsPerKey(10)],
+        disable_on_last_level: true,
+    };
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for idx in 0..100 {
+        storage.put(&key_of(idx), b"value").unwrap();
+    }
+    storage.force_flush().unwrap();
+    {
+        let snapshot = storage.inner.state.read();
+        let sst = &snapshot.sstables[&snapshot.l0_sstables[0]];
+        assert_eq!(sst.filter_type(), Some(FilterType::BlockedBloom));
+    }
+
+    // Full compaction writes to the bottom level.
+    storage.force_full_compaction().unwrap();
+    {
+        let snapshot = storage.inner.state.read();
+        for id in &snapshot.levels[0].1 {
+            assert_eq!(snapshot.sstables[id].filter_type(), None);
+        }
+    }
+    for idx in 0..100 {
+        assert_eq!(
+            storage.get(&key_of(idx)).unwrap(),
+            Some(Bytes::from_static(b"value"))
+        );
+    }
+    assert_eq!(storage.get(&key_of(100)).unwrap(), None);
+}
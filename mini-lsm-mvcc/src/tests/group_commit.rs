@@ -0,0 +1,161 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    error::GroupCommitError,
+    group_commit::{GroupBatch, GroupCommitQueue, GroupWriter},
+    key::KeySlice,
+    lsm_storage::{LsmStorageInner, LsmStorageOptions, WriteBatchRecord},
+    mem_table::MemTable,
+    table::SsTableBuilder,
+};
+
+fn writer_of(key: &[u8], value: &[u8]) -> Arc<GroupWriter> {
+    Arc::new(GroupWriter::new(GroupBatch::new(&[
+        WriteBatchRecord::Put(key, value),
+        WriteBatchRecord::DelRange(b"x", b"y"),
+    ])))
+}
+
+#[test]
+fn test_group_commit_queue() {
+    let queue = GroupCommitQueue::default();
+    let leader = writer_of(b"a", b"1");
+    let follower = writer_of(b"b", b"2");
+    let next_leader = writer_of(b"c", b"3");
+    assert!(queue.join(&leader));
+    assert!(!queue.join(&follower));
+    let followers = queue.take_followers();
+    assert_eq!(followers.len(), 1);
+    // Writers joining after the group is taken wait for the next one.
+    assert!(!queue.join(&next_leader));
+
+    let memtable = Arc::new(MemTable::create(0));
+    let handle = {
+        let follower = follower.clone();
+        std::thread::spawn(move || follower.follow())
+    };
+    memtable.add_pending_inserts(1);
+    follower.insert(5, memtable.clone());
+    follower.wait_inserted();
+    assert_eq!(memtable.get(KeySlice::from_slice(b"b", 5)).unwrap(), "2");
+    assert_eq!(memtable.range_tombstones()[0].ts, 5);
+    follower.finish(Ok(5));
+    assert_eq!(handle.join().unwrap().unwrap().unwrap(), 5);
+
+    // The leader hands over to the next writer in the queue.
+    queue.finish_group();
+    assert!(next_leader.follow().is_none());
+    queue.finish_group();
+    assert!(queue.join(&writer_of(b"d", b"4")));
+}
+
+#[test]
+fn test_group_commit_failure() {
+    let follower = writer_of(b"a", b"1");
+    let error = std::io::Error::other("WAL write failed");
+    follower.finish(Err(GroupCommitError::new(error.into())));
+    let err = follower.follow().unwrap().unwrap_err();
+    assert_eq!(err.to_string(), "WAL write failed");
+    // The original error is passed through.
+    let err = err.downcast_ref::<GroupCommitError>().unwrap();
+    assert!(err.error().downcast_ref::<std::io::Error>().is_some());
+}
+
+#[test]
+fn test_group_commit_flush_waits_for_followers() {
+    let dir = tempdir().unwrap();
+    let memtable = Arc::new(MemTable::create(0));
+    let follower = writer_of(b"a", b"1");
+    memtable.add_pending_inserts(1);
+    follower.insert(1, memtable.clone());
+    let flush = {
+        let memtable = memtable.clone();
+        let path = dir.path().join("1.sst");
+        std::thread::spawn(move || {
+            let mut builder = SsTableBuilder::new(4096);
+            memtable.flush(&mut builder).unwrap();
+            builder.build_for_test(path).unwrap()
+        })
+    };
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(!flush.is_finished());
+
+    let handle = {
+        let follower = follower.clone();
+        std::thread::spawn(move || follower.follow())
+    };
+    follower.wait_inserted();
+    // The batch handed to the follower is in the flushed SST.
+    let sst = flush.join().unwrap();
+    assert_eq!(sst.first_key().key_ref(), b"a");
+    assert_eq!(sst.range_tombstones().len(), 1);
+    follower.finish(Ok(1));
+    assert_eq!(handle.join().unwrap().unwrap().unwrap(), 1);
+}
+
+#[test]
+fn test_group_commit_concurrent_writers() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.enable_wal = true;
+    options.sync_wal_on_write = true;
+    let storage = Arc::new(LsmStorageInner::open(&dir, options.clone()).unwrap());
+    let handles = (0..8)
+        .map(|thread| {
+            let storage = storage.clone();
+            std::thread::spawn(move || {
+                (0..100)
+                    .map(|idx| {
+                        let key = format!("key_{}_{:03}", thread, idx);
+                        let value = format!("value_{}_{:03}", thread, idx);
+                        let ts = storage
+                            .write_batch_inner(&[WriteBatchRecord::Put(&key, &value)])
+                            .unwrap();
+                        // The write is visible once committed.
+                        assert_eq!(storage.get(key.as_bytes()).unwrap().unwrap(), value);
+                        ts
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut commit_ts = BTreeSet::new();
+    for handle in handles {
+        for ts in handle.join().unwrap() {
+            assert!(commit_ts.insert(ts));
+        }
+    }
+    // Each batch has its own commit timestamp, with no gaps.
+    assert_eq!(commit_ts, (1..=800).collect());
+    assert_eq!(storage.mvcc().latest_commit_ts(), 800);
+    drop(storage);
+
+    // All writes are recovered from the WAL.
+    let storage = Arc::new(LsmStorageInner::open(&dir, options).unwrap());
+    for thread in 0..8 {
+        for idx in 0..100 {
+            let key = format!("key_{}_{:03}", thread, idx);
+            let value = format!("value_{}_{:03}", thread, idx);
+            assert_eq!(storage.get(key.as_bytes()).unwrap().unwrap(), value);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use moka::sync::ConcurrentCacheExt;
+use tempfile::tempdir;
+
+use super::harness::{generate_sst_of, key_of, value_of};
+use crate::{
+    compact::CompactionOptions,
+    iterators::StorageIterator,
+    key::KeySlice,
+    lsm_storage::{BlockCache, LsmStorageOptions, MiniLsm},
+    table::{
+        SsTable, SsTableBuilder, SsTableIterator,
+        cache::{self, CachedBlock},
+        filter::FilterType,
+    },
+};
+
+fn build_sst(in_cache: bool, path: &Path) -> (Arc<SsTable>, Arc<BlockCache>) {
+    let block_cache = Arc::new(cache::new_block_cache(1 << 20));
+    let builder = SsTableBuilder::new(128).with_index_and_filter_in_cache(in_cache);
+    let sst = generate_sst_of(builder, 0..1000, 1, path, Some(block_cache.clone()));
+    (Arc::new(sst), block_cache)
+}
+
+#[test]
+fn test_index_and_filter_in_cache() {
+    let dir = tempdir().unwrap();
+    let (pinned, _) = build_sst(false, &dir.path().join("1.sst"));
+    assert!(pinned.is_index_and_filter_pinned());
+    let pinned_size = pinned.pinned_index_and_filter_size();
+    assert!(pinned_size > 0);
+
+    let (sst, block_cache) = build_sst(true, &dir.path().join("2.sst"));
+    assert!(!sst.is_index_and_filter_pinned());
+    assert!(sst.block_meta.is_empty());
+    assert!(sst.bloom.is_none());
+    assert_eq!(sst.pinned_index_and_filter_size(), 0);
+    assert_eq!(sst.filter_type(), Some(FilterType::Bloom));
+    assert_eq!(sst.num_of_blocks(), pinned.num_of_blocks());
+
+    assert!(sst.may_contain(&key_of(10)).unwrap());
+    let mut iter = SsTableIterator::create_and_seek_to_key(
+        sst.clone(),
+        KeySlice::for_testing_from_slice_no_ts(&key_of(500)),
+    )
+    .unwrap();
+    for idx in 500..1000 {
+        assert_eq!(iter.key().for_testing_key_ref(), key_of(idx));
+        assert_eq!(iter.value(), value_of(idx));
+        iter.next().unwrap();
+    }
+    assert!(!iter.is_valid());
+
+    // The index and filter are charged to the cache with the size they have when pinned.
+    assert_eq!(
+        cache::cached_index_and_filter_size(&block_cache, &HashSet::new()),
+        pinned_size
+    );
+    let index = block_cache.get(&(1, cache::INDEX_BLOCK_IDX)).unwrap();
+    assert!(matches!(index, CachedBlock::Index(_)));
+    block_cache.sync();
+    assert!(block_cache.weighted_size() >= pinned_size as u64);
+
+    sst.evict_cached_blocks();
+    assert_eq!(
+        cache::cached_index_and_filter_size(&block_cache, &HashSet::new()),
+        0
+    );
+    block_cache.sync();
+    assert_eq!(block_cache.entry_count(), 0);
+}
+
+#[test]
+fn test_index_and_filter_memory() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.block_size = 128;
+    options.cache_index_and_filter_blocks = true;
+    options.pin_l0_index_and_filter_blocks = true;
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    for idx in 0..1000 {
+        storage.put(&key_of(idx), &value_of(idx)).unwrap();
+    }
+    storage.force_flush().unwrap();
+    let memory = storage.index_and_filter_memory();
+    assert!(memory.pinned > 0);
+    assert_eq!(memory.cached, 0);
+    {
+        // The pinned index and filter are charged against the block cache.
+        let snapshot = storage.inner.state.read();
+        let sst = &snapshot.sstables[&snapshot.l0_sstables[0]];
+        assert!(sst.is_index_and_filter_pinned());
+        assert!(sst.is_index_and_filter_charged());
+        let block_cache = &storage.inner.block_cache;
+        assert!(block_cache.contains_key(&(sst.sst_id(), cache::INDEX_BLOCK_IDX)));
+        assert!(block_cache.contains_key(&(sst.sst_id(), cache::FILTER_BLOCK_IDX)));
+        block_cache.sync();
+        assert!(block_cache.weighted_size() >= memory.pinned as u64);
+
+        // Lookups use the copies held by the SST once the entries are evicted.
+        block_cache.invalidate(&(sst.sst_id(), cache::INDEX_BLOCK_IDX));
+        block_cache.invalidate(&(sst.sst_id(), cache::FILTER_BLOCK_IDX));
+        assert_eq!(
+            storage.get(&key_of(10)).unwrap(),
+            Some(Bytes::from(value_of(10)))
+        );
+        assert!(!block_cache.contains_key(&(sst.sst_id(), cache::INDEX_BLOCK_IDX)));
+    }
+
+    // The compaction output in L1 is loaded through the cache.
+    storage.force_full_compaction().unwrap();
+    {
+        let snapshot = storage.inner.state.read();
+        assert!(snapshot.l0_sstables.is_empty());
+        for id in &snapshot.levels[0].1 {
+            assert!(!snapshot.sstables[id].is_index_and_filter_pinned());
+        }
+    }
+    assert_eq!(storage.index_and_filter_memory().total(), 0);
+    for idx in 0..1000 {
+        assert_eq!(
+            storage.get(&key_of(idx)).unwrap(),
+            Some(Bytes::from(value_of(idx)))
+        );
+    }
+    let memory = storage.index_and_filter_memory();
+    assert_eq!(memory.pinned, 0);
+    assert!(memory.cached > 0);
+}
+
+#[test]
+fn test_index_and_filter_recovery() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    for idx in 0..100 {
+        storage.put(&key_of(idx), &value_of(idx)).unwrap();
+    }
+    storage.force_flush().unwrap();
+    storage.close().unwrap();
+    drop(storage);
+
+    options.cache_index_and_filter_blocks = true;
+    for pin_l0 in [false, true] {
+        options.pin_l0_index_and_filter_blocks = pin_l0;
+        let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+        {
+            let snapshot = storage.inner.state.read();
+            let sst = &snapshot.sstables[&snapshot.l0_sstables[0]];
+            assert_eq!(sst.is_index_and_filter_pinned(), pin_l0);
+        }
+        assert_eq!(
+            storage.get(&key_of(10)).unwrap(),
+            Some(Bytes::from(value_of(10)))
+        );
+        assert_eq!(storage.get(b"missing").unwrap(), None);
+        storage.close().unwrap();
+    }
+}
+
+#[test]
+fn test_block_cache_capacity() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.block_size = 256;
+    options.block_cache_size = 16 << 10;
+    options.cache_index_and_filter_blocks = true;
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for batch in 0..4 {
+        for idx in batch * 500..(batch + 1) * 500 {
+            storage.put(&key_of(idx), &value_of(idx)).unwrap();
+        }
+        storage.force_flush().unwrap();
+    }
+    for _ in 0..2 {
+        for idx in 0..2000 {
+            assert_eq!(
+                storage.get(&key_of(idx)).unwrap(),
+                Some(Bytes::from(value_of(idx)))
+            );
+        }
+    }
+    let block_cache = &storage.inner.block_cache;
+    block_cache.sync();
+    assert!(block_cache.weighted_size() <= 16 << 10);
+    assert!(storage.index_and_filter_memory().cached <= 16 << 10);
+}
@@ -0,0 +1,273 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::{CompactionOptions, TieredCompactionOptions},
+    ingest::SstFileWriter,
+    key::KeyBytes,
+    lsm_storage::{LsmStorageOptions, LsmStorageState, MiniLsm},
+    mem_table::MemTable,
+    table::SsTable,
+};
+
+use super::harness::{check_lsm_iter_result_by_key, key_of};
+
+fn value_of(idx: usize, version: usize) -> Vec<u8> {
+    format!("value_{:05}_{}", idx, version).into_bytes()
+}
+
+fn expected(range: impl Iterator<Item = (usize, usize)>) -> Vec<(Bytes, Bytes)> {
+    range
+        .map(|(idx, version)| {
+            (
+                Bytes::from(key_of(idx)),
+                Bytes::from(value_of(idx, version)),
+            )
+        })
+        .collect()
+}
+
+fn write_file(
+    path: &Path,
+    options: &LsmStorageOptions,
+    keys: impl Iterator<Item = usize>,
+    version: usize,
+) -> PathBuf {
+    let mut writer = SstFileWriter::create(path, options);
+    for idx in keys {
+        writer.put(&key_of(idx), &value_of(idx, version)).unwrap();
+    }
+    writer.finish().unwrap();
+    path.to_path_buf()
+}
+
+#[test]
+fn test_sst_file_writer() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let mut writer = SstFileWriter::create(dir.path().join("1.sst"), &options);
+    writer.put(b"b", b"1").unwrap();
+    assert!(writer.put(b"b", b"2").is_err());
+    assert!(writer.put(b"a", b"2").is_err());
+    assert!(writer.put(b"c", b"").is_err());
+    assert!(writer.put(b"", b"1").is_err());
+    writer.delete(b"c").unwrap();
+    writer.finish().unwrap();
+
+    let writer = SstFileWriter::create(dir.path().join("2.sst"), &options);
+    assert!(writer.finish().is_err());
+}
+
+#[test]
+fn test_ingestion_level() {
+    let meta = |id: usize, first: usize, last: usize| {
+        Arc::new(SsTable::create_meta_only(
+            id,
+            0,
+            KeyBytes::for_testing_from_bytes_no_ts(key_of(first).into()),
+            KeyBytes::for_testing_from_bytes_no_ts(key_of(last).into()),
+        ))
+    };
+    let state = LsmStorageState {
+        memtable: Arc::new(MemTable::create(0)),
+        imm_memtables: Vec::new(),
+        l0_sstables: vec![1],
+        levels: vec![(1, vec![2]), (2, vec![3]), (3, vec![4])],
+        sstables: [
+            (1, meta(1, 0, 10)),
+            (2, meta(2, 20, 30)),
+            (3, meta(3, 40, 50)),
+            (4, meta(4, 60, 70)),
+        ]
+        .into_iter()
+        .collect(),
+    };
+    let level = |first: usize, last: usize| state.ingestion_level(&key_of(first), &key_of(last));
+    assert_eq!(level(5, 15), 0);
+    assert_eq!(level(25, 35), 0);
+    assert_eq!(level(45, 55), 1);
+    assert_eq!(level(65, 75), 2);
+    assert_eq!(level(80, 90), 3);
+    // The SST is placed above the first level that overlaps it.
+    assert_eq!(level(31, 65), 1);
+}
+
+#[test]
+fn test_ingest_external_files() {
+    let dir = tempdir().unwrap();
+    let external = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.enable_wal = true;
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    for idx in 0..50 {
+        storage.put(&key_of(idx), &value_of(idx, 0)).unwrap();
+    }
+    storage.force_flush().unwrap();
+    let snapshot = storage.new_txn().unwrap();
+
+    // Overlaps the L0 SST.
+    let overlapping = write_file(&external.path().join("1.sst"), &options, 40..60, 1);
+    // Goes to L1.
+    let disjoint = write_file(&external.path().join("2.sst"), &options, 100..120, 1);
+    let mut writer = SstFileWriter::create(external.path().join("3.sst"), &options);
+    writer.delete(&key_of(0)).unwrap();
+    writer.finish().unwrap();
+    let deletion = external.path().join("3.sst");
+    storage
+        .ingest_external_files(&[&overlapping, &disjoint, &deletion])
+        .unwrap();
+    assert!(disjoint.exists());
+
+    {
+        let state = storage.inner.state.read();
+        assert_eq!(state.l0_sstables.len(), 3);
+        assert_eq!(state.levels[0].1.len(), 1);
+        let l1_sst = &state.sstables[&state.levels[0].1[0]];
+        assert_eq!(l1_sst.first_key().key_ref(), key_of(100));
+    }
+    let expected_keys = (1..40)
+        .map(|idx| (idx, 0))
+        .chain((40..60).map(|idx| (idx, 1)))
+        .chain((100..120).map(|idx| (idx, 1)));
+    check_lsm_iter_result_by_key(
+        &mut storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap(),
+        expected(expected_keys.clone()),
+    );
+    // Transactions started before the ingestion do not see the files.
+    check_lsm_iter_result_by_key(
+        &mut snapshot.scan(Bound::Unbounded, Bound::Unbounded).unwrap(),
+        expected((0..50).map(|idx| (idx, 0))),
+    );
+    assert_eq!(snapshot.get(&key_of(110)).unwrap(), None);
+    check_lsm_iter_result_by_key(
+        &mut snapshot
+            .scan(Bound::Included(&key_of(45)), Bound::Included(&key_of(110)))
+            .unwrap(),
+        expected((45..50).map(|idx| (idx, 0))),
+    );
+
+    // Overlapping external files are rejected.
+    let first = write_file(&external.path().join("4.sst"), &options, 200..210, 2);
+    let second = write_file(&external.path().join("5.sst"), &options, 205..215, 2);
+    assert!(storage.ingest_external_files(&[first, second]).is_err());
+
+    // Writes after the ingestion get a newer timestamp.
+    storage.put(&key_of(50), &value_of(50, 2)).unwrap();
+    storage.close().unwrap();
+    drop(storage);
+
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    assert_eq!(
+        storage.get(&key_of(50)).unwrap(),
+        Some(Bytes::from(value_of(50, 2)))
+    );
+    storage.put(&key_of(110), &value_of(110, 3)).unwrap();
+    assert_eq!(
+        storage.get(&key_of(110)).unwrap(),
+        Some(Bytes::from(value_of(110, 3)))
+    );
+    assert_eq!(storage.get(&key_of(0)).unwrap(), None);
+    assert_eq!(
+        storage.get(&key_of(119)).unwrap(),
+        Some(Bytes::from(value_of(119, 1)))
+    );
+}
+
+#[test]
+fn test_ingestion_serializable_conflict() {
+    let dir = tempdir().unwrap();
+    let external = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.serializable = true;
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    storage.put(&key_of(0), &value_of(0, 0)).unwrap();
+    let txn = storage.new_txn().unwrap();
+    txn.get(&key_of(1)).unwrap();
+    txn.put(&key_of(0), &value_of(0, 1));
+    let path = write_file(&external.path().join("1.sst"), &options, 1..2, 1);
+    storage.ingest_external_files(&[path]).unwrap();
+    assert!(txn.commit().is_err());
+}
+
+#[test]
+fn test_ingest_below_memtable() {
+    let dir = tempdir().unwrap();
+    let external = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    storage.put(&key_of(0), &value_of(0, 0)).unwrap();
+
+    // The deletion would be dropped by a compaction to the last level while the older version
+    // is still in the memtable.
+    let mut writer = SstFileWriter::create(external.path().join("1.sst"), &options);
+    writer.delete(&key_of(0)).unwrap();
+    writer.finish().unwrap();
+    storage
+        .ingest_external_files(&[external.path().join("1.sst")])
+        .unwrap();
+    {
+        let state = storage.inner.state.read();
+        assert!(state.memtable.is_empty());
+        // The memtable is flushed to L0 first, and the SST is added above it.
+        assert_eq!(state.l0_sstables.len(), 2);
+        assert!(state.sstables[&state.l0_sstables[0]].global_ts().is_some());
+    }
+    storage.force_full_compaction().unwrap();
+    storage.force_flush().unwrap();
+    assert_eq!(storage.get(&key_of(0)).unwrap(), None);
+}
+
+#[test]
+fn test_ingest_tiered_flushes_memtables() {
+    let dir = tempdir().unwrap();
+    let external = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::Tiered(
+        TieredCompactionOptions {
+            num_tiers: 100,
+            max_size_amplification_percent: 200,
+            size_ratio: 1,
+            min_merge_width: 2,
+            max_merge_width: None,
+        },
+    ));
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    storage.put(&key_of(0), &value_of(0, 0)).unwrap();
+    storage.put(&key_of(100), &value_of(100, 0)).unwrap();
+
+    let path = write_file(&external.path().join("1.sst"), &options, 0..10, 1);
+    storage.ingest_external_files(&[path]).unwrap();
+    {
+        let state = storage.inner.state.read();
+        assert!(state.memtable.is_empty());
+        assert!(state.imm_memtables.is_empty());
+        // The ingested tier is above the flushed one.
+        assert_eq!(state.levels.len(), 2);
+        assert!(state.levels[0].0 > state.levels[1].0);
+    }
+    assert_eq!(
+        storage.get(&key_of(0)).unwrap(),
+        Some(Bytes::from(value_of(0, 1)))
+    );
+    assert_eq!(
+        storage.get(&key_of(100)).unwrap(),
+        Some(Bytes::from(value_of(100, 0)))
+    );
+}
@@ -0,0 +1,161 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    block::{Block, BlockBuilder, BlockIterator},
+    compact::CompactionOptions,
+    key::KeySlice,
+    lsm_storage::{LsmStorageOptions, MAX_KEY_SIZE, MAX_VALUE_SIZE, MiniLsm},
+    table::{FileObject, SsTable, SsTableBuilder, SsTableIterator},
+};
+
+use super::harness::check_iter_result_by_key;
+
+fn large_key_of(idx: usize, len: usize) -> Vec<u8> {
+    let mut key = format!("key_{:05}_", idx).into_bytes();
+    key.resize(len, b'k');
+    key
+}
+
+fn large_value_of(idx: usize, len: usize) -> Vec<u8> {
+    (0..len).map(|x| ((x * 31 + idx) % 251) as u8).collect()
+}
+
+#[test]
+fn test_block_large_entries() {
+    let mut builder = BlockBuilder::new(4096).with_restart_interval(2);
+    let entries = (0..5)
+        .map(|idx| (large_key_of(idx, 70000), large_value_of(idx, 200000)))
+        .collect::<Vec<_>>();
+    for (idx, (key, value)) in entries.iter().enumerate() {
+        // The block is over the size limit after the first entry.
+        assert_eq!(
+            builder.add(KeySlice::for_testing_from_slice_no_ts(key), value),
+            idx == 0
+        );
+    }
+    let block = Arc::new(Block::decode(&builder.build().encode()));
+    let iter = BlockIterator::create_and_seek_to_first(block);
+    assert_eq!(iter.key().key_ref(), entries[0].0);
+    assert_eq!(iter.value(), entries[0].1);
+
+    let mut builder = BlockBuilder::new(1 << 30).with_restart_interval(2);
+    for (key, value) in &entries {
+        assert!(builder.add(KeySlice::for_testing_from_slice_no_ts(key), value));
+    }
+    let block = builder.build();
+    assert!(block.data.len() > u16::MAX as usize * 10);
+    let block = Arc::new(Block::decode(&block.encode()));
+    let mut iter = BlockIterator::create_and_seek_to_first(block.clone());
+    for (key, value) in &entries {
+        assert_eq!(iter.key().key_ref(), key);
+        assert_eq!(iter.value(), value);
+        iter.next();
+    }
+    assert!(!iter.is_valid());
+    let iter = BlockIterator::create_and_seek_to_key(
+        block,
+        KeySlice::for_testing_from_slice_no_ts(&entries[3].0),
+    );
+    assert_eq!(iter.value(), entries[3].1);
+}
+
+#[test]
+fn test_sst_large_entries() {
+    let dir = tempdir().unwrap();
+    let mut builder = SsTableBuilder::new(4096);
+    let mut data = Vec::new();
+    for idx in 0..10 {
+        let key = large_key_of(idx, 100000);
+        let value = large_value_of(idx, 300000);
+        builder.add(KeySlice::for_testing_from_slice_no_ts(&key), &value);
+        data.push((Bytes::from(key), Bytes::from(value)));
+    }
+    builder.build_for_test(dir.path().join("1.sst")).unwrap();
+    let sst = Arc::new(
+        SsTable::open(
+            1,
+            None,
+            FileObject::open(&dir.path().join("1.sst")).unwrap(),
+        )
+        .unwrap(),
+    );
+    assert_eq!(sst.num_of_blocks(), 10);
+    assert_eq!(sst.first_key().key_ref(), data[0].0);
+    assert_eq!(sst.last_key().key_ref(), data[9].0);
+    check_iter_result_by_key(
+        &mut SsTableIterator::create_and_seek_to_first(sst).unwrap(),
+        data,
+    );
+}
+
+#[test]
+fn test_storage_large_entries_with_wal() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.enable_wal = true;
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    for idx in 0..3 {
+        storage
+            .put(&large_key_of(idx, 80000), &large_value_of(idx, 3 << 20))
+            .unwrap();
+    }
+    for idx in 0..3 {
+        assert_eq!(
+            storage.get(&large_key_of(idx, 80000)).unwrap(),
+            Some(Bytes::from(large_value_of(idx, 3 << 20)))
+        );
+    }
+    storage.close().unwrap();
+    drop(storage);
+
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for idx in 0..3 {
+        assert_eq!(
+            storage.get(&large_key_of(idx, 80000)).unwrap(),
+            Some(Bytes::from(large_value_of(idx, 3 << 20)))
+        );
+    }
+    storage.force_flush().unwrap();
+    for idx in 0..3 {
+        assert_eq!(
+            storage.get(&large_key_of(idx, 80000)).unwrap(),
+            Some(Bytes::from(large_value_of(idx, 3 << 20)))
+        );
+    }
+}
+
+#[test]
+fn test_storage_rejects_oversized_entries() {
+    let dir = tempdir().unwrap();
+    let storage = MiniLsm::open(
+        &dir,
+        LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction),
+    )
+    .unwrap();
+    let err = storage
+        .put(&vec![b'k'; MAX_KEY_SIZE + 1], b"value")
+        .unwrap_err();
+    assert!(err.to_string().contains("maximum key size"), "{}", err);
+    let err = storage
+        .put(b"key", &vec![b'v'; MAX_VALUE_SIZE + 1])
+        .unwrap_err();
+    assert!(err.to_string().contains("maximum value size"), "{}", err);
+    assert_eq!(storage.get(b"key").unwrap(), None);
+}
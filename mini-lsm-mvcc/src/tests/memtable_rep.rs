@@ -0,0 +1,191 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Bound;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    iterators::StorageIterator,
+    key::KeySlice,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    mem_table::{MemTable, MemTableRepType},
+    tests::harness::{check_lsm_iter_result_by_key, key_of, value_of},
+};
+
+fn check_memtable(rep: MemTableRepType) {
+    let memtable = MemTable::create_with_rep(0, rep);
+    assert!(memtable.is_empty());
+    // Insert out of order, with two versions of each key and an overwritten value.
+    for idx in (0..100).rev() {
+        let key = key_of(idx);
+        memtable
+            .put(KeySlice::from_slice(&key, 1), b"overwritten")
+            .unwrap();
+        memtable
+            .put(KeySlice::from_slice(&key, 2), &value_of(idx))
+            .unwrap();
+        memtable
+            .put(KeySlice::from_slice(&key, 1), &value_of(idx))
+            .unwrap();
+    }
+    assert!(!memtable.is_empty());
+    assert!(memtable.approximate_size() > 0);
+    assert!(memtable.memory_usage() >= memtable.approximate_size());
+    assert_eq!(memtable.max_ts(), 2);
+    assert_eq!(
+        memtable.get(KeySlice::from_slice(&key_of(7), 1)).unwrap(),
+        value_of(7)
+    );
+    assert!(memtable.get(KeySlice::from_slice(&key_of(7), 3)).is_none());
+    memtable.freeze();
+
+    let mut iter = memtable.scan(
+        Bound::Excluded(KeySlice::from_slice(&key_of(10), 2)),
+        Bound::Included(KeySlice::from_slice(&key_of(12), 2)),
+    );
+    for (idx, ts) in [(10, 1), (11, 2), (11, 1), (12, 2)] {
+        assert_eq!(iter.key(), KeySlice::from_slice(&key_of(idx), ts));
+        assert_eq!(iter.value(), value_of(idx));
+        iter.next().unwrap();
+    }
+    assert!(!iter.is_valid());
+
+    let mut iter = memtable.scan(Bound::Unbounded, Bound::Unbounded);
+    let mut count = 0;
+    while iter.is_valid() {
+        count += 1;
+        iter.next().unwrap();
+    }
+    assert_eq!(count, 200);
+}
+
+#[test]
+fn test_skiplist_memtable_rep() {
+    check_memtable(MemTableRepType::SkipList);
+}
+
+#[test]
+fn test_vector_memtable_rep() {
+    check_memtable(MemTableRepType::Vector);
+}
+
+#[test]
+fn test_vector_memtable_insert_after_scan() {
+    let memtable = MemTable::create_with_rep(0, MemTableRepType::Vector);
+    memtable
+        .put(KeySlice::from_slice(b"b", 1), b"value_b")
+        .unwrap();
+    let mut iter = memtable.scan(Bound::Unbounded, Bound::Unbounded);
+    memtable
+        .put(KeySlice::from_slice(b"a", 1), b"value_a")
+        .unwrap();
+    // The iterator keeps the entries it was created with.
+    assert_eq!(iter.key(), KeySlice::from_slice(b"b", 1));
+    iter.next().unwrap();
+    assert!(!iter.is_valid());
+
+    let iter = memtable.scan(Bound::Unbounded, Bound::Unbounded);
+    assert_eq!(iter.key(), KeySlice::from_slice(b"a", 1));
+    assert_eq!(
+        memtable.get(KeySlice::from_slice(b"b", 1)).unwrap(),
+        "value_b"
+    );
+}
+
+#[test]
+fn test_vector_memtable_overwrite_after_merge() {
+    let memtable = MemTable::create_with_rep(0, MemTableRepType::Vector);
+    for idx in (0..1000).rev() {
+        memtable
+            .put(KeySlice::from_slice(&key_of(idx), 1), b"overwritten")
+            .unwrap();
+    }
+    // Reads merge the entries inserted so far into the sorted ones.
+    assert_eq!(
+        memtable.get(KeySlice::from_slice(&key_of(0), 1)).unwrap(),
+        "overwritten"
+    );
+    for idx in (0..1000).step_by(10) {
+        memtable
+            .put(KeySlice::from_slice(&key_of(idx), 1), &value_of(idx))
+            .unwrap();
+    }
+    for frozen in [false, true] {
+        if frozen {
+            memtable.freeze();
+        }
+        assert_eq!(
+            memtable.get(KeySlice::from_slice(&key_of(20), 1)).unwrap(),
+            value_of(20)
+        );
+        let mut iter = memtable.scan(Bound::Unbounded, Bound::Unbounded);
+        for idx in 0..1000 {
+            assert_eq!(iter.key(), KeySlice::from_slice(&key_of(idx), 1));
+            if idx % 10 == 0 {
+                assert_eq!(iter.value(), value_of(idx));
+            } else {
+                assert_eq!(iter.value(), b"overwritten");
+            }
+            iter.next().unwrap();
+        }
+        assert!(!iter.is_valid());
+    }
+}
+
+#[test]
+fn test_vector_memtable_storage() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.enable_wal = true;
+    options.memtable_rep = MemTableRepType::Vector;
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    for idx in (0..1000).rev() {
+        storage.put(&key_of(idx), &value_of(idx)).unwrap();
+    }
+    storage.delete(&key_of(5)).unwrap();
+    storage
+        .inner
+        .force_freeze_memtable(&storage.inner.state_lock.lock())
+        .unwrap();
+    storage.put(&key_of(6), b"new_value").unwrap();
+    storage.close().unwrap();
+
+    // Recover the memtables from the WAL.
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    assert_eq!(storage.get(&key_of(5)).unwrap(), None);
+    assert_eq!(storage.get(&key_of(6)).unwrap().unwrap(), "new_value");
+    assert_eq!(storage.get(&key_of(7)).unwrap().unwrap(), value_of(7));
+    check_lsm_iter_result_by_key(
+        &mut storage
+            .scan(Bound::Included(&key_of(4)), Bound::Included(&key_of(7)))
+            .unwrap(),
+        vec![
+            (Bytes::from(key_of(4)), Bytes::from(value_of(4))),
+            (Bytes::from(key_of(6)), Bytes::from_static(b"new_value")),
+            (Bytes::from(key_of(7)), Bytes::from(value_of(7))),
+        ],
+    );
+
+    // Flush the memtables to SSTs.
+    storage.force_flush().unwrap();
+    while !storage.inner.state.read().imm_memtables.is_empty() {
+        storage.inner.force_flush_next_imm_memtable().unwrap();
+    }
+    assert!(!storage.inner.state.read().l0_sstables.is_empty());
+    assert_eq!(storage.get(&key_of(5)).unwrap(), None);
+    assert_eq!(storage.get(&key_of(999)).unwrap().unwrap(), value_of(999));
+}
@@ -0,0 +1,110 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Bound;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use super::harness::{key_of, value_of};
+use crate::{
+    compact::CompactionOptions,
+    iterators::StorageIterator,
+    key::KeySlice,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    table::{FileObject, SsTable, SsTableBuilder, SsTableIterator},
+};
+
+#[test]
+fn test_mmap_file_object() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    let mut builder = SsTableBuilder::new(128).with_mmap(true);
+    for idx in 0..100 {
+        builder.add(
+            KeySlice::for_testing_from_slice_no_ts(&key_of(idx)),
+            &value_of(idx),
+        );
+    }
+    let sst = builder.build_for_test(&path).unwrap();
+    assert!(sst.file.is_mmap());
+
+    let file = FileObject::open(&path).unwrap();
+    assert!(!file.is_mmap());
+    let size = file.size();
+    let data = file.read(0, size).unwrap();
+    let file = file.mmap().unwrap();
+    assert!(file.is_mmap());
+    assert_eq!(file.read_bytes(0, size).unwrap(), data);
+    assert_eq!(file.read(10, 20).unwrap(), data[10..30]);
+    assert!(file.read_bytes(size - 1, 2).is_err());
+    assert!(file.read_bytes(u64::MAX, 2).is_err());
+
+    let sst = SsTable::open_for_test(file).unwrap();
+    let mut iter = SsTableIterator::create_and_seek_to_first(sst.into()).unwrap();
+    for idx in 0..100 {
+        assert_eq!(iter.key().key_ref(), key_of(idx));
+        assert_eq!(iter.value(), value_of(idx));
+        iter.next().unwrap();
+    }
+    assert!(!iter.is_valid());
+}
+
+#[test]
+fn test_mmap_storage() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.mmap_sst = true;
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    for idx in 0..100 {
+        storage.put(&key_of(idx), &value_of(idx)).unwrap();
+    }
+    storage.force_flush().unwrap();
+    for idx in 0..100 {
+        storage.put(&key_of(idx), &value_of(idx + 1)).unwrap();
+    }
+    storage.force_flush().unwrap();
+    storage.close().unwrap();
+    drop(storage);
+
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    let old_ssts = {
+        let snapshot = storage.inner.state.read();
+        assert_eq!(snapshot.l0_sstables.len(), 2);
+        assert!(snapshot.sstables.values().all(|sst| sst.file.is_mmap()));
+        snapshot.l0_sstables.clone()
+    };
+    for idx in 0..100 {
+        assert_eq!(
+            storage.get(&key_of(idx)).unwrap(),
+            Some(Bytes::from(value_of(idx + 1)))
+        );
+    }
+
+    // An iterator keeps reading the blocks of SSTs removed by a compaction.
+    let mut iter = storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap();
+    storage.force_full_compaction().unwrap();
+    for &id in &old_ssts {
+        assert!(!storage.inner.path_of_sst(id).exists());
+        assert!(storage.inner.block_cache.get(&(id, 0)).is_none());
+    }
+    let snapshot = storage.inner.state.read().clone();
+    assert!(snapshot.sstables.values().all(|sst| sst.file.is_mmap()));
+    for idx in 0..100 {
+        assert_eq!(iter.key(), key_of(idx));
+        assert_eq!(iter.value(), value_of(idx + 1));
+        iter.next().unwrap();
+    }
+    assert!(!iter.is_valid());
+}
@@ -0,0 +1,158 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    iterators::StorageIterator,
+    key::{KeySlice, TS_RANGE_BEGIN},
+    lsm_storage::{BlockCache, LsmStorageOptions, MiniLsm},
+    table::{FileObject, SsTable, SsTableBuilder, SsTableIterator, index::IndexKind},
+};
+
+use super::harness::{check_iter_result_by_key, value_of};
+
+fn key_of(idx: usize) -> Vec<u8> {
+    format!("key_{:05}", idx * 2).into_bytes()
+}
+
+fn build_sst(path: &std::path::Path, index_partition_size: Option<usize>) -> SsTable {
+    let mut builder = SsTableBuilder::new(128).with_index_partition_size(index_partition_size);
+    for idx in 0..500 {
+        builder.add(
+            KeySlice::for_testing_from_slice_no_ts(&key_of(idx)),
+            &value_of(idx),
+        );
+    }
+    builder.build_for_test(path).unwrap()
+}
+
+#[test]
+fn test_partitioned_index_find_block() {
+    let dir = tempdir().unwrap();
+    let full = build_sst(&dir.path().join("1.sst"), None);
+    build_sst(&dir.path().join("2.sst"), Some(4));
+    let partitioned = SsTable::open(
+        2,
+        Some(Arc::new(BlockCache::new(1024))),
+        FileObject::open(&dir.path().join("2.sst")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        partitioned.index,
+        IndexKind::Partitioned {
+            blocks_per_partition: 4,
+            num_blocks: full.num_of_blocks(),
+        }
+    );
+    assert_eq!(partitioned.num_of_blocks(), full.num_of_blocks());
+    assert_eq!(
+        partitioned.block_meta.len(),
+        full.num_of_blocks().div_ceil(4)
+    );
+    assert_eq!(partitioned.first_key(), full.first_key());
+    assert_eq!(partitioned.last_key(), full.last_key());
+    for idx in 0..500 {
+        for key in [key_of(idx), format!("key_{:05}", idx * 2 + 1).into_bytes()] {
+            let key = KeySlice::for_testing_from_slice_no_ts(&key);
+            assert_eq!(
+                partitioned.find_block_idx(key).unwrap(),
+                full.find_block_idx(key).unwrap()
+            );
+        }
+    }
+    let before_first = KeySlice::for_testing_from_slice_no_ts(b"a");
+    assert_eq!(partitioned.find_block_idx(before_first).unwrap(), 0);
+    for block_idx in 0..full.num_of_blocks() {
+        assert_eq!(
+            partitioned.read_block_cached(block_idx).unwrap().data,
+            full.read_block(block_idx).unwrap().data
+        );
+    }
+}
+
+#[test]
+fn test_partitioned_index_iterators() {
+    let dir = tempdir().unwrap();
+    build_sst(&dir.path().join("1.sst"), Some(3));
+    let sst = Arc::new(
+        SsTable::open(
+            1,
+            None,
+            FileObject::open(&dir.path().join("1.sst")).unwrap(),
+        )
+        .unwrap(),
+    );
+    let data = (0..500)
+        .map(|idx| (Bytes::from(key_of(idx)), Bytes::from(value_of(idx))))
+        .collect::<Vec<_>>();
+    check_iter_result_by_key(
+        &mut SsTableIterator::create_and_seek_to_first(sst.clone()).unwrap(),
+        data.clone(),
+    );
+    check_iter_result_by_key(
+        &mut SsTableIterator::create_and_seek_to_key(
+            sst.clone(),
+            KeySlice::for_testing_from_slice_no_ts(&key_of(250)),
+        )
+        .unwrap(),
+        data[250..].to_vec(),
+    );
+    let mut iter = SsTableIterator::create_and_seek_to_last(sst).unwrap();
+    for idx in (0..500).rev() {
+        assert_eq!(iter.key().key_ref(), key_of(idx));
+        iter.prev().unwrap();
+    }
+    assert!(!iter.is_valid());
+}
+
+#[test]
+fn test_storage_with_partitioned_index() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.index_partition_size = Some(8);
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    for idx in 0..2000 {
+        storage.put(&key_of(idx), &value_of(idx)).unwrap();
+    }
+    storage.force_flush().unwrap();
+    storage.close().unwrap();
+    drop(storage);
+
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    let snapshot = storage.inner.state.read().clone();
+    let sst = &snapshot.sstables[&snapshot.l0_sstables[0]];
+    assert!(matches!(sst.index, IndexKind::Partitioned { .. }));
+    for idx in 0..2000 {
+        assert_eq!(
+            storage.get(&key_of(idx)).unwrap(),
+            Some(Bytes::from(value_of(idx)))
+        );
+        let missing = format!("key_{:05}", idx * 2 + 1).into_bytes();
+        let iter = SsTableIterator::create_and_seek_to_key(
+            sst.clone(),
+            KeySlice::from_slice(&missing, TS_RANGE_BEGIN),
+        )
+        .unwrap();
+        if idx + 1 < 2000 {
+            assert_eq!(iter.key().key_ref(), key_of(idx + 1));
+        } else {
+            assert!(!iter.is_valid());
+        }
+    }
+}
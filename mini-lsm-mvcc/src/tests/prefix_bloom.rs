@@ -0,0 +1,163 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    key::KeySlice,
+    lsm_storage::{LsmStorageOptions, MiniLsm, prefix_upper_bound},
+    table::{FileObject, SsTable, SsTableBuilder, prefix::PrefixExtractor},
+};
+
+use super::harness::check_lsm_iter_result_by_key;
+
+fn key_of(tenant: usize, idx: usize) -> Vec<u8> {
+    format!("tenant{:03}/key_{:05}", tenant, idx).into_bytes()
+}
+
+fn value_of(tenant: usize, idx: usize) -> Vec<u8> {
+    format!("value_{:03}_{:05}", tenant, idx).into_bytes()
+}
+
+#[test]
+fn test_prefix_extractor() {
+    let fixed = PrefixExtractor::FixedLength(4);
+    assert_eq!(fixed.extract(b"abcdef"), Some(&b"abcd"[..]));
+    assert_eq!(fixed.extract(b"abcd"), Some(&b"abcd"[..]));
+    assert_eq!(fixed.extract(b"abc"), None);
+    let delimiter = PrefixExtractor::Delimiter(b'/');
+    assert_eq!(delimiter.extract(b"ab/cd/ef"), Some(&b"ab/"[..]));
+    assert_eq!(delimiter.extract(b"ab/"), Some(&b"ab/"[..]));
+    assert_eq!(delimiter.extract(b"ab"), None);
+
+    assert_eq!(prefix_upper_bound(b"ab"), Some(b"ac".to_vec()));
+    assert_eq!(prefix_upper_bound(b"a\xff\xff"), Some(b"b".to_vec()));
+    assert_eq!(prefix_upper_bound(b"\xff"), None);
+    assert_eq!(prefix_upper_bound(b""), None);
+}
+
+#[test]
+fn test_sst_prefix_bloom() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    let mut builder =
+        SsTableBuilder::new(128).with_prefix_extractor(Some(PrefixExtractor::Delimiter(b'/')));
+    for tenant in 0..10 {
+        for idx in 0..20 {
+            builder.add(
+                KeySlice::for_testing_from_slice_no_ts(&key_of(tenant, idx)),
+                &value_of(tenant, idx),
+            );
+        }
+    }
+    builder.build_for_test(&path).unwrap();
+    let sst = SsTable::open(0, None, FileObject::open(&path).unwrap()).unwrap();
+    assert_eq!(
+        sst.prefix_extractor(),
+        Some(PrefixExtractor::Delimiter(b'/'))
+    );
+    for tenant in 0..10 {
+        assert!(
+            sst.may_contain_prefix(format!("tenant{:03}/", tenant).as_bytes())
+                .unwrap()
+        );
+        assert!(
+            sst.may_contain_prefix(format!("tenant{:03}/key_0001", tenant).as_bytes())
+                .unwrap()
+        );
+    }
+    let false_positives = (10..1000)
+        .filter(|tenant| {
+            sst.may_contain_prefix(format!("tenant{:03}/", tenant).as_bytes())
+                .unwrap()
+        })
+        .count();
+    assert!(false_positives < 50, "{} false positives", false_positives);
+    // Without the delimiter, the prefix may match any tenant.
+    assert!(sst.may_contain_prefix(b"tenant999").unwrap());
+
+    let path = dir.path().join("2.sst");
+    let mut builder = SsTableBuilder::new(128);
+    builder.add(
+        KeySlice::for_testing_from_slice_no_ts(&key_of(0, 0)),
+        &value_of(0, 0),
+    );
+    builder.build_for_test(&path).unwrap();
+    let sst = SsTable::open(0, None, FileObject::open(&path).unwrap()).unwrap();
+    assert_eq!(sst.prefix_extractor(), None);
+    assert!(sst.may_contain_prefix(b"tenant999/").unwrap());
+}
+
+#[test]
+fn test_storage_scan_prefix() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.prefix_extractor = Some(PrefixExtractor::FixedLength(9));
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    // Each L0 SST holds a single tenant.
+    for tenant in 0..10 {
+        for idx in 0..50 {
+            storage
+                .put(&key_of(tenant, idx), &value_of(tenant, idx))
+                .unwrap();
+        }
+        storage.force_flush().unwrap();
+    }
+    storage.put(&key_of(3, 50), &value_of(3, 50)).unwrap();
+    storage.delete(&key_of(3, 0)).unwrap();
+
+    let snapshot = storage.inner.state.read().clone();
+    assert_eq!(snapshot.l0_sstables.len(), 10);
+    let candidates = snapshot
+        .l0_sstables
+        .iter()
+        .filter(|id| {
+            snapshot.sstables[*id]
+                .may_contain_prefix(b"tenant003")
+                .unwrap()
+        })
+        .count();
+    assert!((1..5).contains(&candidates), "{} candidates", candidates);
+
+    check_lsm_iter_result_by_key(
+        &mut storage.scan_prefix(b"tenant003").unwrap(),
+        (1..=50)
+            .map(|idx| (Bytes::from(key_of(3, idx)), Bytes::from(value_of(3, idx))))
+            .collect(),
+    );
+    check_lsm_iter_result_by_key(
+        &mut storage.scan_prefix(b"tenant009/key_0004").unwrap(),
+        (40..50)
+            .map(|idx| (Bytes::from(key_of(9, idx)), Bytes::from(value_of(9, idx))))
+            .collect(),
+    );
+    check_lsm_iter_result_by_key(&mut storage.scan_prefix(b"tenant010").unwrap(), vec![]);
+    check_lsm_iter_result_by_key(
+        &mut storage.scan_prefix(b"tenant").unwrap(),
+        (0..10)
+            .flat_map(|tenant| {
+                (0..=50)
+                    .filter(move |&idx| (idx < 50 || tenant == 3) && (tenant, idx) != (3, 0))
+                    .map(move |idx| {
+                        (
+                            Bytes::from(key_of(tenant, idx)),
+                            Bytes::from(value_of(tenant, idx)),
+                        )
+                    })
+            })
+            .collect(),
+    );
+}
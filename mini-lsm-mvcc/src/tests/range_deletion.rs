@@ -0,0 +1,243 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Bound;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    iterators::StorageIterator,
+    lsm_storage::{LsmStorageOptions, MiniLsm, WriteBatchRecord},
+    range_tombstone::RangeTombstone,
+};
+
+use super::harness::{check_lsm_iter_result_by_key, construct_merge_iterator_over_storage};
+
+fn key_of(idx: usize) -> Vec<u8> {
+    format!("key_{:03}", idx).into_bytes()
+}
+
+fn value_of(idx: usize) -> Vec<u8> {
+    format!("value_{:03}", idx).into_bytes()
+}
+
+fn expected(range: impl Iterator<Item = usize>) -> Vec<(Bytes, Bytes)> {
+    range
+        .map(|idx| (Bytes::from(key_of(idx)), Bytes::from(value_of(idx))))
+        .collect()
+}
+
+#[test]
+fn test_range_tombstone() {
+    let tombstone = RangeTombstone::new(b"b", b"d", 10);
+    assert!(!tombstone.contains(b"a"));
+    assert!(tombstone.contains(b"b"));
+    assert!(tombstone.contains(b"c"));
+    assert!(!tombstone.contains(b"d"));
+    assert!(tombstone.covers(b"b", 9));
+    assert!(!tombstone.covers(b"b", 10));
+    assert!(tombstone.overlaps(Bound::Included(b"a"), Bound::Included(b"b")));
+    assert!(!tombstone.overlaps(Bound::Included(b"a"), Bound::Excluded(b"b")));
+    assert!(!tombstone.overlaps(Bound::Included(b"d"), Bound::Unbounded));
+    assert_eq!(
+        tombstone.clip(Some(b"c"), None),
+        Some(RangeTombstone::new(b"c", b"d", 10))
+    );
+    assert_eq!(tombstone.clip(None, Some(b"b")), None);
+
+    let mut buf = Vec::new();
+    tombstone.encode(&mut buf);
+    assert_eq!(buf.len(), tombstone.encoded_len());
+    assert_eq!(RangeTombstone::decode(&mut &buf[..]), tombstone);
+}
+
+#[test]
+fn test_delete_range_memtable() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for idx in 0..10 {
+        storage.put(&key_of(idx), &value_of(idx)).unwrap();
+    }
+    let snapshot = storage.new_txn().unwrap();
+    storage.delete_range(&key_of(3), &key_of(7)).unwrap();
+    assert_eq!(
+        storage.get(&key_of(2)).unwrap(),
+        Some(Bytes::from(value_of(2)))
+    );
+    assert_eq!(storage.get(&key_of(3)).unwrap(), None);
+    assert_eq!(storage.get(&key_of(6)).unwrap(), None);
+    assert_eq!(
+        storage.get(&key_of(7)).unwrap(),
+        Some(Bytes::from(value_of(7)))
+    );
+    check_lsm_iter_result_by_key(
+        &mut storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap(),
+        expected((0..3).chain(7..10)),
+    );
+    // A snapshot taken before the deletion still sees the keys.
+    check_lsm_iter_result_by_key(
+        &mut snapshot.scan(Bound::Unbounded, Bound::Unbounded).unwrap(),
+        expected(0..10),
+    );
+    // Keys written after the deletion are visible.
+    storage.put(&key_of(5), &value_of(5)).unwrap();
+    check_lsm_iter_result_by_key(
+        &mut storage
+            .scan(Bound::Included(&key_of(4)), Bound::Included(&key_of(7)))
+            .unwrap(),
+        expected([5, 7].into_iter()),
+    );
+
+    // Keys written in the same batch as the deletion are visible.
+    storage
+        .write_batch(&[
+            WriteBatchRecord::DelRange(key_of(0), key_of(10)),
+            WriteBatchRecord::Put(key_of(1), value_of(1)),
+        ])
+        .unwrap();
+    check_lsm_iter_result_by_key(
+        &mut storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap(),
+        expected(1..2),
+    );
+
+    assert!(storage.delete_range(&key_of(5), &key_of(5)).is_err());
+    assert!(storage.delete_range(&key_of(6), &key_of(5)).is_err());
+}
+
+#[test]
+fn test_delete_range_flush_and_recover() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.enable_wal = true;
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    for idx in 0..20 {
+        storage.put(&key_of(idx), &value_of(idx)).unwrap();
+    }
+    storage.force_flush().unwrap();
+    storage.delete_range(&key_of(5), &key_of(15)).unwrap();
+    storage.close().unwrap();
+    drop(storage);
+
+    // Recover the tombstone from the WAL.
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    check_lsm_iter_result_by_key(
+        &mut storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap(),
+        expected((0..5).chain(15..20)),
+    );
+    // Flush a memtable that holds only the tombstone.
+    storage.force_flush().unwrap();
+    {
+        let snapshot = storage.inner.state.read();
+        let sst = &snapshot.sstables[snapshot.l0_sstables.first().unwrap()];
+        assert_eq!(sst.range_tombstones().len(), 1);
+        assert_eq!(sst.first_key().key_ref(), key_of(5));
+        assert_eq!(sst.last_key().key_ref(), key_of(15));
+    }
+    storage.close().unwrap();
+    drop(storage);
+
+    // Recover the tombstone from the SST.
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    assert_eq!(storage.get(&key_of(10)).unwrap(), None);
+    check_lsm_iter_result_by_key(
+        &mut storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap(),
+        expected((0..5).chain(15..20)),
+    );
+}
+
+#[test]
+fn test_delete_range_compaction() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for idx in 0..100 {
+        storage.put(&key_of(idx), &value_of(idx)).unwrap();
+    }
+    storage.force_flush().unwrap();
+    storage.delete_range(&key_of(10), &key_of(90)).unwrap();
+    storage.put(&key_of(50), &value_of(50)).unwrap();
+    storage.force_flush().unwrap();
+    storage.force_full_compaction().unwrap();
+
+    check_lsm_iter_result_by_key(
+        &mut storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap(),
+        expected((0..10).chain([50]).chain(90..100)),
+    );
+    // Compacting to the bottom level drops the tombstone and the versions it covers.
+    let snapshot = storage.inner.state.read().clone();
+    assert!(snapshot.l0_sstables.is_empty());
+    for (_, ids) in &snapshot.levels {
+        for id in ids {
+            assert!(snapshot.sstables[id].range_tombstones().is_empty());
+        }
+    }
+    let mut iter = construct_merge_iterator_over_storage(&snapshot);
+    let mut keys = Vec::new();
+    while iter.is_valid() {
+        keys.push(iter.key().key_ref().to_vec());
+        iter.next().unwrap();
+    }
+    assert_eq!(
+        keys,
+        (0..10)
+            .chain([50])
+            .chain(90..100)
+            .map(key_of)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_txn_delete_range() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.serializable = true;
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for idx in 0..10 {
+        storage.put(&key_of(idx), &value_of(idx)).unwrap();
+    }
+    let txn = storage.new_txn().unwrap();
+    txn.put(&key_of(12), &value_of(12));
+    txn.delete_range(&key_of(2), &key_of(20));
+    txn.put(&key_of(5), &value_of(5));
+    assert_eq!(txn.get(&key_of(3)).unwrap(), None);
+    assert_eq!(txn.get(&key_of(12)).unwrap(), None);
+    assert_eq!(txn.get(&key_of(5)).unwrap(), Some(Bytes::from(value_of(5))));
+    check_lsm_iter_result_by_key(
+        &mut txn.scan(Bound::Unbounded, Bound::Unbounded).unwrap(),
+        expected([0, 1, 5].into_iter()),
+    );
+    // Other transactions do not see the deletion before it commits.
+    assert_eq!(
+        storage.get(&key_of(3)).unwrap(),
+        Some(Bytes::from(value_of(3)))
+    );
+    txn.commit().unwrap();
+    check_lsm_iter_result_by_key(
+        &mut storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap(),
+        expected([0, 1, 5].into_iter()),
+    );
+
+    // A transaction that read keys conflicts with a concurrent range deletion.
+    let txn1 = storage.new_txn().unwrap();
+    let txn2 = storage.new_txn().unwrap();
+    txn1.get(&key_of(0)).unwrap();
+    txn1.put(&key_of(30), &value_of(30));
+    txn2.delete_range(&key_of(0), &key_of(1));
+    txn2.commit().unwrap();
+    assert!(txn1.commit().is_err());
+}
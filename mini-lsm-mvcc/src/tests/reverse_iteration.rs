@@ -0,0 +1,166 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use tempfile::tempdir;
+
+use super::harness::value_of;
+use crate::{
+    block::{Block, BlockBuilder, BlockIterator},
+    iterators::StorageIterator,
+    key::KeySlice,
+    table::{SsTable, SsTableBuilder, SsTableIterator},
+};
+
+fn key_of(idx: usize) -> Vec<u8> {
+    format!("key_{:05}", idx * 2).into_bytes()
+}
+
+/// A key between `key_of(idx)` and `key_of(idx + 1)`.
+fn missing_key_of(idx: usize) -> Vec<u8> {
+    format!("key_{:05}", idx * 2 + 1).into_bytes()
+}
+
+fn generate_block(restart_interval: usize) -> Arc<Block> {
+    let mut builder = BlockBuilder::new(1 << 20).with_restart_interval(restart_interval);
+    for idx in 0..100 {
+        assert!(builder.add(
+            KeySlice::for_testing_from_slice_no_ts(&key_of(idx)),
+            &value_of(idx)
+        ));
+    }
+    Arc::new(builder.build())
+}
+
+fn generate_sst(dir: &tempfile::TempDir) -> Arc<SsTable> {
+    let mut builder = SsTableBuilder::new(128).with_restart_interval(2);
+    for idx in 0..100 {
+        builder.add(
+            KeySlice::for_testing_from_slice_no_ts(&key_of(idx)),
+            &value_of(idx),
+        );
+    }
+    Arc::new(builder.build_for_test(dir.path().join("1.sst")).unwrap())
+}
+
+#[test]
+fn test_block_reverse_iteration() {
+    for restart_interval in [1, 3, 16, 200] {
+        let block = generate_block(restart_interval);
+        let mut iter = BlockIterator::create_and_seek_to_last(block.clone());
+        for idx in (0..100).rev() {
+            assert!(iter.is_valid());
+            assert_eq!(iter.key().key_ref(), key_of(idx));
+            assert_eq!(iter.value(), value_of(idx));
+            iter.prev();
+        }
+        assert!(!iter.is_valid());
+
+        // Mix forward and backward steps.
+        let mut iter = BlockIterator::create_and_seek_to_first(block);
+        iter.next();
+        iter.next();
+        iter.prev();
+        assert_eq!(iter.key().key_ref(), key_of(1));
+        iter.next();
+        assert_eq!(iter.key().key_ref(), key_of(2));
+    }
+}
+
+#[test]
+fn test_block_seek_for_prev() {
+    for restart_interval in [1, 3, 16] {
+        let block = generate_block(restart_interval);
+        for idx in 0..100 {
+            let iter = BlockIterator::create_and_seek_for_prev(
+                block.clone(),
+                KeySlice::for_testing_from_slice_no_ts(&key_of(idx)),
+            );
+            assert_eq!(iter.key().key_ref(), key_of(idx));
+            let iter = BlockIterator::create_and_seek_for_prev(
+                block.clone(),
+                KeySlice::for_testing_from_slice_no_ts(&missing_key_of(idx)),
+            );
+            assert_eq!(iter.key().key_ref(), key_of(idx));
+        }
+        let iter = BlockIterator::create_and_seek_for_prev(
+            block.clone(),
+            KeySlice::for_testing_from_slice_no_ts(b"a"),
+        );
+        assert!(!iter.is_valid());
+        let iter = BlockIterator::create_and_seek_for_prev(
+            block,
+            KeySlice::for_testing_from_slice_no_ts(b"z"),
+        );
+        assert_eq!(iter.key().key_ref(), key_of(99));
+    }
+}
+
+#[test]
+fn test_sst_reverse_iteration() {
+    let dir = tempdir().unwrap();
+    let sst = generate_sst(&dir);
+    assert!(sst.num_of_blocks() > 5);
+    let mut iter = SsTableIterator::create_and_seek_to_last(sst.clone()).unwrap();
+    for idx in (0..100).rev() {
+        assert!(iter.is_valid());
+        assert_eq!(iter.key().key_ref(), key_of(idx));
+        assert_eq!(iter.value(), value_of(idx));
+        iter.prev().unwrap();
+    }
+    assert!(!iter.is_valid());
+
+    iter.seek_to_first().unwrap();
+    for _ in 0..50 {
+        iter.next().unwrap();
+    }
+    for idx in (0..50).rev() {
+        iter.prev().unwrap();
+        assert_eq!(iter.key().key_ref(), key_of(idx));
+    }
+}
+
+#[test]
+fn test_sst_seek_for_prev() {
+    let dir = tempdir().unwrap();
+    let sst = generate_sst(&dir);
+    for idx in 0..100 {
+        let iter = SsTableIterator::create_and_seek_for_prev(
+            sst.clone(),
+            KeySlice::for_testing_from_slice_no_ts(&key_of(idx)),
+        )
+        .unwrap();
+        assert_eq!(iter.key().key_ref(), key_of(idx));
+        let mut iter = SsTableIterator::create_and_seek_for_prev(
+            sst.clone(),
+            KeySlice::for_testing_from_slice_no_ts(&missing_key_of(idx)),
+        )
+        .unwrap();
+        assert_eq!(iter.key().key_ref(), key_of(idx));
+        iter.prev().unwrap();
+        if idx > 0 {
+            assert_eq!(iter.key().key_ref(), key_of(idx - 1));
+        } else {
+            assert!(!iter.is_valid());
+        }
+    }
+    let mut iter = SsTableIterator::create_and_seek_to_first(sst).unwrap();
+    iter.seek_for_prev(KeySlice::for_testing_from_slice_no_ts(b"a"))
+        .unwrap();
+    assert!(!iter.is_valid());
+    iter.seek_for_prev(KeySlice::for_testing_from_slice_no_ts(b"z"))
+        .unwrap();
+    assert_eq!(iter.key().key_ref(), key_of(99));
+}
@@ -0,0 +1,181 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use bytes::{Buf, BufMut, Bytes};
+use tempfile::tempdir;
+
+use super::harness::generate_sst_of;
+use crate::{
+    block::BlockLayout,
+    error::{CorruptedSection, CorruptionError},
+    iterators::StorageIterator,
+    key::KeyBytes,
+    table::{
+        BlockMeta, FileObject, SsTable, SsTableBuilder, SsTableIterator,
+        footer::{Footer, SST_FORMAT_VERSION, SST_MAGIC},
+        index::IndexKind,
+        properties::TableProperties,
+    },
+    varint::{get_varint, put_varint},
+};
+
+fn build_sst(path: &Path) {
+    generate_sst_of(SsTableBuilder::new(128), 0..100, 0, path, None);
+}
+
+fn open_err(path: &Path) -> anyhow::Error {
+    SsTable::open(0, None, FileObject::open(path).unwrap())
+        .err()
+        .unwrap()
+}
+
+#[test]
+fn test_sst_footer() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    build_sst(&path);
+    let data = std::fs::read(&path).unwrap();
+    assert_eq!(data[data.len() - 8..], SST_MAGIC.to_be_bytes());
+    let sst = SsTable::open(0, None, FileObject::open(&path).unwrap()).unwrap();
+    assert_eq!(sst.format_version(), SST_FORMAT_VERSION);
+    assert_eq!(
+        sst.block_meta_offset as u64,
+        Footer::decode(&data[data.len() - Footer::SIZE..], 0, 0)
+            .unwrap()
+            .meta_offset
+    );
+}
+
+#[test]
+fn test_sst_footer_rejects_invalid_files() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    std::fs::write(&path, b"too small").unwrap();
+    let err = open_err(&path);
+    assert!(err.to_string().contains("too small"), "{}", err);
+
+    std::fs::write(&path, vec![0x42; 4096]).unwrap();
+    let err = open_err(&path);
+    assert!(err.to_string().contains("bad magic number"), "{}", err);
+
+    // A file written by a newer build.
+    build_sst(&path);
+    let mut data = std::fs::read(&path).unwrap();
+    let version_offset = data.len() - 12;
+    data[version_offset..version_offset + 4]
+        .copy_from_slice(&(SST_FORMAT_VERSION + 1).to_be_bytes());
+    std::fs::write(&path, &data).unwrap();
+    let err = open_err(&path);
+    assert!(
+        err.to_string().contains(&format!(
+            "unsupported format version {}",
+            SST_FORMAT_VERSION + 1
+        )),
+        "{}",
+        err
+    );
+
+    build_sst(&path);
+    let mut data = std::fs::read(&path).unwrap();
+    let footer_offset = data.len() - Footer::SIZE;
+    data[footer_offset + 3] ^= 0xff;
+    std::fs::write(&path, &data).unwrap();
+    let err = open_err(&path);
+    let corruption = err.downcast_ref::<CorruptionError>().unwrap();
+    assert_eq!(corruption.section, CorruptedSection::Footer);
+    assert_eq!(corruption.offset, footer_offset as u64);
+}
+
+#[test]
+fn test_sst_64bit_offsets() {
+    let key = |key: &'static [u8]| KeyBytes::from_bytes_with_ts(Bytes::from_static(key), 1);
+    let block_meta = vec![
+        BlockMeta {
+            offset: 0,
+            first_key: key(b"a"),
+            last_key: key(b"b"),
+        },
+        BlockMeta {
+            offset: 5 << 32,
+            first_key: key(b"c"),
+            last_key: key(b"d"),
+        },
+    ];
+    let mut buf = Vec::new();
+    BlockMeta::encode_block_meta(
+        &block_meta,
+        BlockLayout::Variable,
+        IndexKind::Full,
+        None,
+        &[],
+        &TableProperties::default(),
+        &mut buf,
+    );
+    let decoded = BlockMeta::decode_block_meta(&buf, 0, 0, SST_FORMAT_VERSION).unwrap();
+    assert_eq!(decoded.block_meta, block_meta);
+}
+
+/// Rewrite an SST in format version 1, which stores 32-bit block offsets in the meta.
+fn downgrade_to_v1(data: &[u8]) -> Vec<u8> {
+    let footer_offset = data.len() - Footer::SIZE;
+    let footer = Footer::decode(&data[footer_offset..], 0, 0).unwrap();
+    let meta_offset = footer.meta_offset as usize;
+    let filter_offset = footer.filter_offset as usize;
+    let mut meta = &data[meta_offset..filter_offset - 4];
+    let mut output = data[..meta_offset].to_vec();
+    let num_blocks = meta.get_u32();
+    output.put_u32(num_blocks);
+    for _ in 0..num_blocks {
+        output.put_u32(meta.get_u64() as u32);
+        for _ in 0..2 {
+            let key_len = get_varint(&mut meta) as usize;
+            put_varint(&mut output, key_len as u64);
+            output.put_slice(&meta[..key_len + 8]);
+            meta.advance(key_len + 8);
+        }
+    }
+    output.put_slice(meta);
+    output.put_u32(crc32fast::hash(&output[meta_offset + 4..]));
+    let new_filter_offset = output.len();
+    output.put_slice(&data[filter_offset..footer_offset]);
+    Footer {
+        format_version: 1,
+        meta_offset: meta_offset as u64,
+        filter_offset: new_filter_offset as u64,
+        ..footer
+    }
+    .encode(&mut output);
+    output
+}
+
+#[test]
+fn test_sst_format_v1_compatibility() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    build_sst(&path);
+    let data = downgrade_to_v1(&std::fs::read(&path).unwrap());
+    std::fs::write(&path, &data).unwrap();
+    let sst = SsTable::open(0, None, FileObject::open(&path).unwrap()).unwrap();
+    assert_eq!(sst.format_version(), 1);
+    assert!(sst.num_of_blocks() > 1);
+    let mut iter = SsTableIterator::create_and_seek_to_first(Arc::new(sst)).unwrap();
+    for idx in 0..100 {
+        assert_eq!(iter.key().key_ref(), format!("key_{:05}", idx).as_bytes());
+        iter.next().unwrap();
+    }
+    assert!(!iter.is_valid());
+}
@@ -0,0 +1,114 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tempfile::tempdir;
+
+use crate::{
+    compact::{CompactionOptions, CompactionTask},
+    key::KeySlice,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    range_tombstone::RangeTombstone,
+    table::{FileObject, SsTable, SsTableBuilder},
+};
+
+#[test]
+fn test_sst_properties() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    let mut builder = SsTableBuilder::new(128);
+    for idx in 0..100 {
+        let key = format!("key_{:03}", idx);
+        builder.add(
+            KeySlice::for_testing_from_slice_with_ts(key.as_bytes(), 20),
+            b"value",
+        );
+        if idx % 10 == 0 {
+            // An older version deleted by a newer one.
+            builder.add(
+                KeySlice::for_testing_from_slice_with_ts(key.as_bytes(), 10),
+                b"",
+            );
+        }
+    }
+    builder.add_range_tombstone(RangeTombstone::new(b"key_050", b"key_060", 5));
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let sst = builder.build_for_test(&path).unwrap();
+    let properties = sst.properties();
+    assert_eq!(properties.num_entries, 110);
+    assert_eq!(properties.num_deletions, 10);
+    assert_eq!(properties.num_range_tombstones, 1);
+    assert_eq!(properties.num_distinct_keys, 100);
+    assert_eq!(properties.min_ts, 5);
+    assert_eq!(properties.max_ts, 20);
+    assert_eq!(sst.max_ts(), 20);
+    assert_eq!(properties.raw_key_size, 110 * 7);
+    assert_eq!(properties.raw_value_size, 100 * 5);
+    assert!(properties.creation_time >= now);
+    assert_eq!(properties.compaction_task, None);
+
+    let reopened = SsTable::open(0, None, FileObject::open(&path).unwrap()).unwrap();
+    assert_eq!(reopened.properties(), sst.properties());
+
+    let path = dir.path().join("2.sst");
+    let task = CompactionTask::ForceFullCompaction {
+        l0_sstables: vec![1, 2],
+        l1_sstables: vec![3],
+    };
+    let mut builder = SsTableBuilder::new(128).with_compaction_task(task.clone());
+    builder.add(KeySlice::for_testing_from_slice_with_ts(b"a", 1), b"1");
+    builder.build_for_test(&path).unwrap();
+    let sst = SsTable::open(0, None, FileObject::open(&path).unwrap()).unwrap();
+    assert_eq!(sst.properties().compaction_task, Some(task));
+}
+
+#[test]
+fn test_storage_sst_properties() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for idx in 0..10 {
+        storage
+            .put(format!("key_{}", idx).as_bytes(), b"value")
+            .unwrap();
+    }
+    storage.force_flush().unwrap();
+    storage.delete(b"key_0").unwrap();
+    storage.force_flush().unwrap();
+
+    let snapshot = storage.inner.state.read().clone();
+    let l0_sstables = snapshot.l0_sstables.clone();
+    for id in &l0_sstables {
+        assert_eq!(snapshot.sstables[id].properties().compaction_task, None);
+    }
+    let deletion = snapshot.sstables[&l0_sstables[0]].properties();
+    assert_eq!(deletion.num_entries, 1);
+    assert_eq!(deletion.num_deletions, 1);
+
+    storage.force_full_compaction().unwrap();
+    let snapshot = storage.inner.state.read().clone();
+    let task = CompactionTask::ForceFullCompaction {
+        l0_sstables,
+        l1_sstables: vec![],
+    };
+    for id in &snapshot.levels[0].1 {
+        let properties = snapshot.sstables[id].properties();
+        assert_eq!(properties.compaction_task.as_ref(), Some(&task));
+        assert_eq!(properties.num_deletions, 0);
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tempfile::tempdir;
+
+use super::harness::key_of;
+use crate::{
+    compact::CompactionOptions,
+    error::WriteStallError,
+    lsm_storage::{LsmStorageInner, LsmStorageOptions, MiniLsm},
+    write_buffer_manager::WriteBufferManager,
+    write_stall::WriteStallCause,
+};
+
+fn value_of(idx: usize) -> Vec<u8> {
+    format!("value_{:05}", idx).repeat(8).into_bytes()
+}
+
+fn options_with(manager: &Arc<WriteBufferManager>) -> LsmStorageOptions {
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    // Only the manager freezes and flushes memtables.
+    options.target_sst_size = 64 << 20;
+    options.num_memtable_limit = 1000;
+    options.write_buffer_manager = Some(manager.clone());
+    options
+}
+
+#[test]
+fn test_write_buffer_manager_flushes_shared_budget() {
+    let manager = Arc::new(WriteBufferManager::new(256 << 10, 64 << 20));
+    let (dir1, dir2) = (tempdir().unwrap(), tempdir().unwrap());
+    let storage1 = MiniLsm::open(&dir1, options_with(&manager)).unwrap();
+    let storage2 = MiniLsm::open(&dir2, options_with(&manager)).unwrap();
+    for idx in 0..5000 {
+        storage1.put(&key_of(idx), &value_of(idx)).unwrap();
+        storage2.put(&key_of(idx), &value_of(idx)).unwrap();
+    }
+
+    // The flush threads bring the usage back under the budget.
+    let start = Instant::now();
+    while manager.memory_usage() >= manager.buffer_size() {
+        assert!(start.elapsed() < Duration::from_secs(10));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    for storage in [&storage1, &storage2] {
+        assert!(!storage.inner.state.read().l0_sstables.is_empty());
+        assert_eq!(storage.get(&key_of(0)).unwrap().unwrap(), value_of(0));
+        assert_eq!(storage.get(&key_of(4999)).unwrap().unwrap(), value_of(4999));
+    }
+
+    // A closed storage no longer counts.
+    storage2.close().unwrap();
+    drop(storage2);
+    let usage = manager.memory_usage();
+    let state = storage1.inner.state.read();
+    let usage1 = state.memtable.memory_usage()
+        + state
+            .imm_memtables
+            .iter()
+            .map(|memtable| memtable.memory_usage())
+            .sum::<usize>();
+    assert_eq!(usage, usage1);
+}
+
+#[test]
+fn test_write_buffer_manager_stalls_writes() {
+    let manager = Arc::new(WriteBufferManager::new(64 << 10, 128 << 10));
+    let dir = tempdir().unwrap();
+    // Without a flush thread, only the test flushes memtables.
+    let storage = Arc::new(LsmStorageInner::open(&dir, options_with(&manager)).unwrap());
+    manager.register(&storage);
+
+    let writer = {
+        let storage = storage.clone();
+        std::thread::spawn(move || {
+            for idx in 0..5000 {
+                storage.put(&key_of(idx), &value_of(idx)).unwrap();
+            }
+        })
+    };
+    let start = Instant::now();
+    while manager.memory_usage() < manager.hard_limit() {
+        assert!(start.elapsed() < Duration::from_secs(10));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(!writer.is_finished());
+    // The memtables were frozen by the writer, but could not be flushed.
+    assert!(storage.state.read().imm_memtables.len() > 1);
+
+    while !writer.is_finished() {
+        assert!(start.elapsed() < Duration::from_secs(10));
+        storage.force_flush_next_imm_memtable().unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    writer.join().unwrap();
+    assert!(manager.memory_usage() < manager.hard_limit() + (64 << 10));
+    assert_eq!(storage.get(&key_of(4999)).unwrap().unwrap(), value_of(4999));
+}
+
+#[test]
+fn test_write_buffer_manager_stall_timeout() {
+    let manager = Arc::new(WriteBufferManager::new(64 << 10, 128 << 10));
+    let dir = tempdir().unwrap();
+    let mut options = options_with(&manager);
+    options.write_stall.stop_timeout = Duration::from_millis(100);
+    // Without a flush thread, nothing brings the usage back under the hard limit.
+    let storage = Arc::new(LsmStorageInner::open(&dir, options).unwrap());
+    manager.register(&storage);
+
+    let start = Instant::now();
+    let err = (0..100000)
+        .find_map(|idx| storage.put(&key_of(idx), &value_of(idx)).err())
+        .unwrap();
+    let err = err.downcast_ref::<WriteStallError>().unwrap();
+    assert_eq!(err.cause, WriteStallCause::WriteBufferManager);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    // Writes go through again once the memtables are flushed.
+    while !storage.state.read().imm_memtables.is_empty() {
+        storage.force_flush_next_imm_memtable().unwrap();
+    }
+    storage.put(&key_of(0), &value_of(0)).unwrap();
+}
@@ -0,0 +1,175 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tempfile::tempdir;
+
+use crate::{
+    compact::{CompactionOptions, SimpleLeveledCompactionOptions},
+    error::WriteStallError,
+    lsm_storage::{LsmStorageInner, LsmStorageOptions},
+    write_stall::{WriteStallCause, WriteStallOptions, WriteStallState},
+};
+
+fn freeze(storage: &LsmStorageInner) {
+    storage
+        .force_freeze_memtable(&storage.state_lock.lock())
+        .unwrap();
+}
+
+#[test]
+fn test_write_stall_state_triggers() {
+    let options = WriteStallOptions {
+        imm_memtable_slowdown_trigger: Some(2),
+        imm_memtable_stop_trigger: Some(4),
+        l0_slowdown_trigger: Some(8),
+        l0_stop_trigger: Some(12),
+        pending_compaction_bytes_slowdown_trigger: Some(1000),
+        pending_compaction_bytes_stop_trigger: Some(2000),
+        ..Default::default()
+    };
+    assert_eq!(options.state(1, 7, 999), WriteStallState::Normal);
+    assert_eq!(
+        options.state(2, 7, 999),
+        WriteStallState::Delayed(WriteStallCause::ImmMemtables)
+    );
+    assert_eq!(
+        options.state(1, 8, 999),
+        WriteStallState::Delayed(WriteStallCause::L0Files)
+    );
+    assert_eq!(
+        options.state(1, 7, 1000),
+        WriteStallState::Delayed(WriteStallCause::PendingCompactionBytes)
+    );
+    // Stop triggers take precedence over slowdown triggers.
+    assert_eq!(
+        options.state(2, 12, 1000),
+        WriteStallState::Stopped(WriteStallCause::L0Files)
+    );
+    assert_eq!(
+        options.state(4, 12, 2000),
+        WriteStallState::Stopped(WriteStallCause::ImmMemtables)
+    );
+    assert_eq!(
+        WriteStallOptions::default().state(100, 100, u64::MAX),
+        WriteStallState::Normal
+    );
+}
+
+#[test]
+fn test_write_stall_on_imm_memtables() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week1_test();
+    options.num_memtable_limit = 1000;
+    options.write_stall = WriteStallOptions {
+        imm_memtable_slowdown_trigger: Some(1),
+        imm_memtable_stop_trigger: Some(2),
+        stop_timeout: Duration::from_millis(100),
+        ..Default::default()
+    };
+    // Without a flush thread, only the test flushes memtables.
+    let storage = Arc::new(LsmStorageInner::open(&dir, options).unwrap());
+    storage.put(b"1", b"1").unwrap();
+    freeze(&storage);
+    assert_eq!(
+        storage.write_stall_state(),
+        WriteStallState::Delayed(WriteStallCause::ImmMemtables)
+    );
+    storage.put(b"2", b"2").unwrap();
+    freeze(&storage);
+    assert_eq!(
+        storage.write_stall_state(),
+        WriteStallState::Stopped(WriteStallCause::ImmMemtables)
+    );
+
+    // The write times out while writes are stopped.
+    let err = storage.put(b"3", b"3").unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<WriteStallError>().unwrap().cause,
+        WriteStallCause::ImmMemtables
+    );
+    assert_eq!(storage.get(b"3").unwrap(), None);
+
+    // A flush lets the stopped write through.
+    let writer = {
+        let storage = storage.clone();
+        std::thread::spawn(move || storage.put(b"4", b"4"))
+    };
+    std::thread::sleep(Duration::from_millis(20));
+    storage.force_flush_next_imm_memtable().unwrap();
+    writer.join().unwrap().unwrap();
+    assert_eq!(storage.get(b"4").unwrap().unwrap(), "4");
+    assert_eq!(
+        storage.write_stall_state(),
+        WriteStallState::Delayed(WriteStallCause::ImmMemtables)
+    );
+    storage.force_flush_next_imm_memtable().unwrap();
+    assert_eq!(storage.write_stall_state(), WriteStallState::Normal);
+}
+
+#[test]
+fn test_write_stall_on_l0_files() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week1_test();
+    options.write_stall = WriteStallOptions {
+        l0_stop_trigger: Some(2),
+        stop_timeout: Duration::from_millis(50),
+        ..Default::default()
+    };
+    let storage = Arc::new(LsmStorageInner::open(&dir, options).unwrap());
+    for key in [b"1", b"2"] {
+        storage.put(key, b"value").unwrap();
+        freeze(&storage);
+        storage.force_flush_next_imm_memtable().unwrap();
+    }
+    assert_eq!(
+        storage.write_stall_state(),
+        WriteStallState::Stopped(WriteStallCause::L0Files)
+    );
+    assert!(storage.put(b"3", b"value").is_err());
+}
+
+#[test]
+fn test_write_stall_on_pending_compaction_bytes() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::Simple(
+        SimpleLeveledCompactionOptions {
+            size_ratio_percent: 200,
+            level0_file_num_compaction_trigger: 2,
+            max_levels: 3,
+        },
+    ));
+    options.write_stall = WriteStallOptions {
+        pending_compaction_bytes_slowdown_trigger: Some(1),
+        ..Default::default()
+    };
+    // Without a compaction thread, the L0 SSTs stay until compacted by the test.
+    let storage = Arc::new(LsmStorageInner::open(&dir, options).unwrap());
+    storage.put(b"1", b"value").unwrap();
+    freeze(&storage);
+    storage.force_flush_next_imm_memtable().unwrap();
+    assert_eq!(storage.write_stall_state(), WriteStallState::Normal);
+    storage.put(b"2", b"value").unwrap();
+    freeze(&storage);
+    storage.force_flush_next_imm_memtable().unwrap();
+    assert_eq!(
+        storage.write_stall_state(),
+        WriteStallState::Delayed(WriteStallCause::PendingCompactionBytes)
+    );
+    // Delayed writes still go through.
+    storage.put(b"3", b"value").unwrap();
+    assert_eq!(storage.get(b"3").unwrap().unwrap(), "value");
+}
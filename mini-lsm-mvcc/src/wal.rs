@@ -20,10 +20,24 @@ use std::sync::Arc;
 
 use anyhow::{Context, Result, bail};
 use bytes::{Buf, BufMut, Bytes};
-use crossbeam_skiplist::SkipMap;
 use parking_lot::Mutex;
 
-use crate::key::{KeyBytes, KeySlice};
+use crate::error::{CorruptedFile, CorruptedSection, CorruptionError};
+use crate::key::KeySlice;
+use crate::mem_table::MemTableRep;
+use crate::range_tombstone::RangeTombstone;
+use crate::varint::{get_varint, put_varint};
+
+/// A put or a point deletion, stored as key, ts and value.
+const RECORD_POINT: u8 = 0;
+/// A range deletion, stored as start key, ts and end key.
+const RECORD_RANGE_TOMBSTONE: u8 = 1;
+
+fn varint_bytes(value: usize) -> Vec<u8> {
+    let mut buf = Vec::new();
+    put_varint(&mut buf, value as u64);
+    buf
+}
 
 pub struct Wal {
     file: Arc<Mutex<BufWriter<File>>>,
@@ -43,7 +57,15 @@ impl Wal {
         })
     }
 
-    pub fn recover(path: impl AsRef<Path>, skiplist: &SkipMap<KeyBytes, Bytes>) -> Result<Self> {
+    /// Replay the WAL of the memtable with the given id into `rep` and `range_tombstones`, up to
+    /// the first batch that is corrupted or incomplete. Returns the corruption that stopped the
+    /// replay, if any.
+    pub(crate) fn recover(
+        id: usize,
+        path: impl AsRef<Path>,
+        rep: &dyn MemTableRep,
+        range_tombstones: &mut Vec<RangeTombstone>,
+    ) -> Result<(Self, Option<CorruptionError>)> {
         let path = path.as_ref();
         let mut file = OpenOptions::new()
             .read(true)
@@ -53,58 +75,108 @@ impl Wal {
         let mut buf = Vec::new();
         file.read_to_end(&mut buf)?;
         let mut rbuf: &[u8] = buf.as_slice();
+        let mut corruption = None;
         while rbuf.has_remaining() {
-            let batch_size = rbuf.get_u32() as usize;
-            if rbuf.remaining() < batch_size {
-                bail!("incomplete WAL");
+            let batch_offset = (buf.len() - rbuf.remaining()) as u64;
+            // A crash while appending a batch leaves it incomplete at the end of the WAL.
+            if rbuf.remaining() < 4 || rbuf.remaining() < (&rbuf[..]).get_u32() as usize + 8 {
+                corruption = Some(CorruptionError::incomplete_wal_batch(id, batch_offset));
+                break;
             }
+            let batch_size = rbuf.get_u32() as usize;
             let mut batch_buf = &rbuf[..batch_size];
-            let mut kv_pairs = Vec::new();
+            let expected_checksum = (&rbuf[batch_size..]).get_u32();
+            // Verify the batch before decoding it, so that corrupted lengths are never followed.
+            if let Err(err) = CorruptionError::check(
+                batch_buf,
+                expected_checksum,
+                CorruptedFile::Wal(id),
+                CorruptedSection::WalBatch,
+                batch_offset,
+            ) {
+                corruption = Some(err);
+                break;
+            }
+            let mut records = Vec::new();
             let mut hasher = crc32fast::Hasher::new();
             // The checksum computed from the individual components should be the same as a direct checksum on the buffer.
             // Students' implementation only needs to do a single checksum on the buffer. We compute both for verification purpose.
-            let single_checksum = crc32fast::hash(batch_buf);
             while batch_buf.has_remaining() {
-                let key_len = batch_buf.get_u16() as usize;
-                hasher.write(&(key_len as u16).to_be_bytes());
+                let kind = batch_buf.get_u8();
+                hasher.write_u8(kind);
+                let key_len = get_varint(&mut batch_buf) as usize;
+                hasher.write(&varint_bytes(key_len));
                 let key = Bytes::copy_from_slice(&batch_buf[..key_len]);
                 hasher.write(&key);
                 batch_buf.advance(key_len);
                 let ts = batch_buf.get_u64();
                 hasher.write(&ts.to_be_bytes());
-                let value_len = batch_buf.get_u16() as usize;
-                hasher.write(&(value_len as u16).to_be_bytes());
+                let value_len = get_varint(&mut batch_buf) as usize;
+                hasher.write(&varint_bytes(value_len));
                 let value = Bytes::copy_from_slice(&batch_buf[..value_len]);
                 hasher.write(&value);
-                kv_pairs.push((key, ts, value));
+                records.push((kind, key, ts, value));
                 batch_buf.advance(value_len);
             }
-            rbuf.advance(batch_size);
-            let expected_checksum = rbuf.get_u32();
-            let component_checksum = hasher.finalize();
-            assert_eq!(component_checksum, single_checksum);
-            if single_checksum != expected_checksum {
-                bail!("checksum mismatch");
-            }
-            for (key, ts, value) in kv_pairs {
-                skiplist.insert(KeyBytes::from_bytes_with_ts(key, ts), value);
+            rbuf.advance(batch_size + 4);
+            assert_eq!(hasher.finalize(), expected_checksum);
+            for (kind, key, ts, value) in records {
+                match kind {
+                    RECORD_POINT => {
+                        rep.insert(KeySlice::from_slice(&key, ts), &value);
+                    }
+                    RECORD_RANGE_TOMBSTONE => range_tombstones.push(RangeTombstone {
+                        start: key,
+                        end: value,
+                        ts,
+                    }),
+                    kind => bail!("unknown WAL record kind {}", kind),
+                }
             }
         }
-        Ok(Self {
+        let wal = Self {
             file: Arc::new(Mutex::new(BufWriter::new(file))),
-        })
+        };
+        Ok((wal, corruption))
     }
 
     /// Implement this in week 3, day 5.
     pub fn put_batch(&self, data: &[(KeySlice, &[u8])]) -> Result<()> {
+        self.write_batch(data, &[])
+    }
+
+    /// Write key-value pairs and range tombstones as a single batch.
+    pub fn write_batch(
+        &self,
+        data: &[(KeySlice, &[u8])],
+        range_tombstones: &[RangeTombstone],
+    ) -> Result<()> {
         let mut file = self.file.lock();
         let mut buf = Vec::<u8>::new();
-        for (key, value) in data {
-            buf.put_u16(key.key_len() as u16);
-            buf.put_slice(key.key_ref());
-            buf.put_u64(key.ts());
-            buf.put_u16(value.len() as u16);
+        let mut put_record = |kind: u8, key: &[u8], ts: u64, value: &[u8]| {
+            buf.put_u8(kind);
+            put_varint(&mut buf, key.len() as u64);
+            buf.put_slice(key);
+            buf.put_u64(ts);
+            put_varint(&mut buf, value.len() as u64);
             buf.put_slice(value);
+        };
+        for (key, value) in data {
+            put_record(RECORD_POINT, key.key_ref(), key.ts(), value);
+        }
+        for tombstone in range_tombstones {
+            put_record(
+                RECORD_RANGE_TOMBSTONE,
+                &tombstone.start,
+                tombstone.ts,
+                &tombstone.end,
+            );
+        }
+        if buf.len() > u32::MAX as usize {
+            bail!(
+                "write batch of {} bytes is too large for the WAL",
+                buf.len()
+            );
         }
         // write batch_size header (u32)
         file.write_all(&(buf.len() as u32).to_be_bytes())?;
@@ -0,0 +1,214 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use parking_lot::{Condvar, Mutex};
+
+use crate::error::WriteStallError;
+use crate::lsm_storage::LsmStorageInner;
+use crate::write_stall::WriteStallCause;
+
+/// A memory budget for the memtables of all storage instances sharing it through
+/// `LsmStorageOptions::write_buffer_manager`.
+///
+/// When the active and immutable memtables of all instances take more than `buffer_size` bytes,
+/// writers freeze the largest active memtable and the flush threads flush their oldest immutable
+/// memtables. Once they take more than `hard_limit` bytes, writers stall until flushes bring the
+/// usage back under the limit, and fail with `WriteStallError` after
+/// `WriteStallOptions::stop_timeout`.
+///
+/// Writers track the usage with an estimate, and only compute it from the memtables of all
+/// storages once the estimate gets close to the budget, or after `buffer_size / 16` bytes were
+/// written since the last time.
+pub struct WriteBufferManager {
+    buffer_size: usize,
+    hard_limit: usize,
+    storages: Mutex<Vec<Weak<LsmStorageInner>>>,
+    /// The usage last computed from the memtables, plus the bytes written since then, minus the
+    /// memory of the memtables flushed since then.
+    estimated_usage: AtomicUsize,
+    /// Bytes written since the usage was last computed from the memtables.
+    unscanned_bytes: AtomicUsize,
+    /// Notified when a memtable is flushed, to wake up stalled writers.
+    flushed: Condvar,
+    flushed_lock: Mutex<()>,
+}
+
+impl fmt::Debug for WriteBufferManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteBufferManager")
+            .field("buffer_size", &self.buffer_size)
+            .field("hard_limit", &self.hard_limit)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WriteBufferManager {
+    pub fn new(buffer_size: usize, hard_limit: usize) -> Self {
+        assert!(
+            hard_limit >= buffer_size,
+            "the hard limit must be at least the buffer size"
+        );
+        Self {
+            buffer_size,
+            hard_limit,
+            storages: Mutex::new(Vec::new()),
+            estimated_usage: AtomicUsize::new(0),
+            unscanned_bytes: AtomicUsize::new(0),
+            flushed: Condvar::new(),
+            flushed_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    pub fn hard_limit(&self) -> usize {
+        self.hard_limit
+    }
+
+    /// Memory of the active and immutable memtables of all storages sharing the manager.
+    pub fn memory_usage(&self) -> usize {
+        let usage = self
+            .storages()
+            .iter()
+            .map(|storage| {
+                let (_, active, immutable) = Self::memtables_of(storage);
+                active + immutable
+            })
+            .sum();
+        self.reset_estimate(usage);
+        usage
+    }
+
+    /// Replace the estimated usage with `usage`, computed from the memtables.
+    fn reset_estimate(&self, usage: usize) {
+        self.unscanned_bytes.store(0, Ordering::Relaxed);
+        self.estimated_usage.store(usage, Ordering::Relaxed);
+    }
+
+    pub(crate) fn register(&self, storage: &Arc<LsmStorageInner>) {
+        self.storages.lock().push(Arc::downgrade(storage));
+    }
+
+    /// The registered storages that are still open.
+    fn storages(&self) -> Vec<Arc<LsmStorageInner>> {
+        let mut storages = self.storages.lock();
+        storages.retain(|storage| storage.strong_count() > 0);
+        storages.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    /// The id and memory of the active memtable of `storage`, and the memory of its immutable
+    /// memtables.
+    fn memtables_of(storage: &LsmStorageInner) -> (usize, usize, usize) {
+        let state = storage.state.read();
+        let immutable = state
+            .imm_memtables
+            .iter()
+            .map(|memtable| memtable.memory_usage())
+            .sum();
+        (
+            state.memtable.id(),
+            state.memtable.memory_usage(),
+            immutable,
+        )
+    }
+
+    /// Whether the flush threads should flush immutable memtables to free memory.
+    pub(crate) fn should_flush(&self) -> bool {
+        self.memory_usage() >= self.buffer_size
+    }
+
+    /// Account for `written` bytes added to a memtable, then freeze the largest active memtable
+    /// if the active memtables take most of the budget, or if the budget is exceeded and
+    /// freezing would free a large share of it. Otherwise, flushing the immutable memtables is
+    /// left to the flush threads.
+    pub(crate) fn maybe_freeze(&self, written: usize) -> Result<()> {
+        let estimate = self.estimated_usage.fetch_add(written, Ordering::Relaxed) + written;
+        let unscanned = self.unscanned_bytes.fetch_add(written, Ordering::Relaxed) + written;
+        // Freezing needs the memtables to take at least 7/8 of the budget.
+        if estimate < self.buffer_size / 8 * 7 && unscanned < self.buffer_size / 16 {
+            return Ok(());
+        }
+        self.freeze_largest()
+    }
+
+    fn freeze_largest(&self) -> Result<()> {
+        let (mut total_active, mut total) = (0, 0);
+        let mut largest = None;
+        for storage in self.storages() {
+            let (id, active, immutable) = Self::memtables_of(&storage);
+            total_active += active;
+            total += active + immutable;
+            if largest.as_ref().is_none_or(|(_, _, size)| active > *size) {
+                largest = Some((storage, id, active));
+            }
+        }
+        self.reset_estimate(total);
+        let should_freeze = total_active >= self.buffer_size / 8 * 7
+            || (total >= self.buffer_size && total_active >= self.buffer_size / 2);
+        if let Some((storage, id, _)) = largest.filter(|_| should_freeze) {
+            let state_lock = storage.state_lock.lock();
+            let memtable = storage.state.read().memtable.clone();
+            // Another writer may have frozen it already.
+            if memtable.id() == id && !memtable.is_empty() {
+                storage.force_freeze_memtable(&state_lock)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Block while the memtables take more than the hard limit, for up to `timeout`.
+    pub(crate) fn stall(&self, timeout: Duration) -> Result<()> {
+        if self.estimated_usage.load(Ordering::Relaxed) < self.hard_limit {
+            return Ok(());
+        }
+        let start = Instant::now();
+        while self.memory_usage() >= self.hard_limit {
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Err(WriteStallError {
+                    cause: WriteStallCause::WriteBufferManager,
+                    timeout,
+                }
+                .into());
+            }
+            self.freeze_largest()?;
+            let mut guard = self.flushed_lock.lock();
+            // Flushes are also checked periodically, as they may finish before the wait starts.
+            self.flushed.wait_for(
+                &mut guard,
+                (timeout - elapsed).min(Duration::from_millis(10)),
+            );
+        }
+        Ok(())
+    }
+
+    /// Account for a flushed memtable that held `freed` bytes, and wake up the writers stalled
+    /// on the memory limit.
+    pub(crate) fn notify_flushed(&self, freed: usize) {
+        let _ = self
+            .estimated_usage
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |usage| {
+                Some(usage.saturating_sub(freed))
+            });
+        self.flushed.notify_all();
+    }
+}
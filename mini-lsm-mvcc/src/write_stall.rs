@@ -0,0 +1,134 @@
+// Copyright (c) 2022-2025 Alex Chi Z
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::time::Duration;
+
+/// Triggers that slow down or stop writes when flushes and compactions fall behind. Each trigger
+/// is disabled when `None`.
+#[derive(Debug, Clone)]
+pub struct WriteStallOptions {
+    /// Number of immutable memtables at which writes are slowed down.
+    pub imm_memtable_slowdown_trigger: Option<usize>,
+    /// Number of immutable memtables at which writes are stopped.
+    pub imm_memtable_stop_trigger: Option<usize>,
+    /// Number of L0 SSTs (tiers with tiered compaction) at which writes are slowed down.
+    pub l0_slowdown_trigger: Option<usize>,
+    /// Number of L0 SSTs (tiers with tiered compaction) at which writes are stopped.
+    pub l0_stop_trigger: Option<usize>,
+    /// Estimated pending compaction bytes at which writes are slowed down.
+    pub pending_compaction_bytes_slowdown_trigger: Option<u64>,
+    /// Estimated pending compaction bytes at which writes are stopped.
+    pub pending_compaction_bytes_stop_trigger: Option<u64>,
+    /// How long each write is delayed while writes are slowed down.
+    pub slowdown_delay: Duration,
+    /// How long a write waits while writes are stopped before it fails with `WriteStallError`.
+    pub stop_timeout: Duration,
+}
+
+impl Default for WriteStallOptions {
+    fn default() -> Self {
+        Self {
+            imm_memtable_slowdown_trigger: None,
+            imm_memtable_stop_trigger: None,
+            l0_slowdown_trigger: None,
+            l0_stop_trigger: None,
+            pending_compaction_bytes_slowdown_trigger: None,
+            pending_compaction_bytes_stop_trigger: None,
+            slowdown_delay: Duration::from_millis(1),
+            stop_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Why writes are slowed down or stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteStallCause {
+    ImmMemtables,
+    L0Files,
+    PendingCompactionBytes,
+    WriteBufferManager,
+}
+
+/// Whether writes are currently slowed down or stopped, and why.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteStallState {
+    Normal,
+    Delayed(WriteStallCause),
+    Stopped(WriteStallCause),
+}
+
+impl fmt::Display for WriteStallCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteStallCause::ImmMemtables => write!(f, "too many immutable memtables"),
+            WriteStallCause::L0Files => write!(f, "too many L0 SSTs"),
+            WriteStallCause::PendingCompactionBytes => {
+                write!(f, "too many pending compaction bytes")
+            }
+            WriteStallCause::WriteBufferManager => {
+                write!(
+                    f,
+                    "memtables exceeding the write buffer manager's hard limit"
+                )
+            }
+        }
+    }
+}
+
+impl WriteStallOptions {
+    pub(crate) fn has_pending_compaction_bytes_trigger(&self) -> bool {
+        self.pending_compaction_bytes_slowdown_trigger.is_some()
+            || self.pending_compaction_bytes_stop_trigger.is_some()
+    }
+
+    /// Whether `num_imm_memtables` reaches a slowdown or stop trigger.
+    pub(crate) fn stalls_on_imm_memtables(&self, num_imm_memtables: usize) -> bool {
+        [
+            self.imm_memtable_slowdown_trigger,
+            self.imm_memtable_stop_trigger,
+        ]
+        .into_iter()
+        .flatten()
+        .any(|x| num_imm_memtables >= x)
+    }
+
+    /// The stall state for the given backlog. Stop triggers take precedence over slowdown
+    /// triggers.
+    pub(crate) fn state(
+        &self,
+        num_imm_memtables: usize,
+        num_l0_files: usize,
+        pending_compaction_bytes: u64,
+    ) -> WriteStallState {
+        let reached = |trigger: Option<usize>, value: usize| trigger.is_some_and(|x| value >= x);
+        let bytes_reached =
+            |trigger: Option<u64>| trigger.is_some_and(|x| pending_compaction_bytes >= x);
+        if reached(self.imm_memtable_stop_trigger, num_imm_memtables) {
+            WriteStallState::Stopped(WriteStallCause::ImmMemtables)
+        } else if reached(self.l0_stop_trigger, num_l0_files) {
+            WriteStallState::Stopped(WriteStallCause::L0Files)
+        } else if bytes_reached(self.pending_compaction_bytes_stop_trigger) {
+            WriteStallState::Stopped(WriteStallCause::PendingCompactionBytes)
+        } else if reached(self.imm_memtable_slowdown_trigger, num_imm_memtables) {
+            WriteStallState::Delayed(WriteStallCause::ImmMemtables)
+        } else if reached(self.l0_slowdown_trigger, num_l0_files) {
+            WriteStallState::Delayed(WriteStallCause::L0Files)
+        } else if bytes_reached(self.pending_compaction_bytes_slowdown_trigger) {
+            WriteStallState::Delayed(WriteStallCause::PendingCompactionBytes)
+        } else {
+            WriteStallState::Normal
+        }
+    }
+}
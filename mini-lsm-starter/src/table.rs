@@ -85,10 +85,10 @@ impl BlockMeta {
                 let bytes = buf.copy_to_bytes(last_key_len);
                 KeyBytes::from_bytes(bytes)
             };
-            block_metas.push(BlockMeta{
+            block_metas.push(BlockMeta {
                 offset,
                 first_key,
-                last_key
+                last_key,
             })
         }
         block_metas
@@ -184,10 +184,13 @@ impl SsTable {
 
     /// Read a block from the disk.
     pub fn read_block(&self, block_idx: usize) -> Result<Arc<Block>> {
-        assert!(block_idx < self.block_meta.len(), 
-            "block idx {} is greater than the total number", block_idx);
+        assert!(
+            block_idx < self.block_meta.len(),
+            "block idx {} is greater than the total number",
+            block_idx
+        );
 
-        let meta = &self.block_meta[block_idx];        
+        let meta = &self.block_meta[block_idx];
         let off = meta.offset as u64;
         let block_len_bytes = self.file.read(off, 2)?;
         let block_len = u16::from_be_bytes([block_len_bytes[0], block_len_bytes[1]]);
@@ -215,7 +218,7 @@ impl SsTable {
                 return idx;
             }
         }
-        metas.len()         // invalid index
+        metas.len() // invalid index
     }
 
     /// Get number of data blocks.
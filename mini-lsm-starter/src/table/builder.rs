@@ -15,14 +15,19 @@
 #![allow(unused_variables)] // TODO(you): remove this lint after implementing this mod
 #![allow(dead_code)] // TODO(you): remove this lint after implementing this mod
 
-use std::{fs::Metadata, path::Path};
 use std::sync::Arc;
+use std::{fs::Metadata, path::Path};
 
 use anyhow::Result;
 use bytes::Bytes;
 
 use super::{BlockMeta, SsTable};
-use crate::{block::BlockBuilder, key::{Key, KeyBytes, KeySlice}, lsm_storage::BlockCache, table::FileObject};
+use crate::{
+    block::BlockBuilder,
+    key::{Key, KeyBytes, KeySlice},
+    lsm_storage::BlockCache,
+    table::FileObject,
+};
 
 /// Builds an SSTable from key-value pairs.
 pub struct SsTableBuilder {
@@ -43,7 +48,7 @@ impl SsTableBuilder {
             last_key: Vec::new(),
             data: Vec::new(),
             meta: Vec::new(),
-            block_size
+            block_size,
         }
     }
 
@@ -53,14 +58,15 @@ impl SsTableBuilder {
     /// be helpful here)
     pub fn add(&mut self, key: KeySlice, value: &[u8]) {
         if !self.builder.add(key, value) {
-            let block_builder = std::mem::replace(&mut self.builder, BlockBuilder::new(self.block_size));
+            let block_builder =
+                std::mem::replace(&mut self.builder, BlockBuilder::new(self.block_size));
             let encoded_bytes = block_builder.build().encode();
 
             let off = self.data.len();
             self.meta.push(BlockMeta {
                 offset: off,
                 first_key: KeyBytes::from_bytes(Bytes::from(self.first_key.clone())),
-                last_key: KeyBytes::from_bytes(Bytes::from(self.last_key.clone()))
+                last_key: KeyBytes::from_bytes(Bytes::from(self.last_key.clone())),
             });
             self.data.extend_from_slice(&encoded_bytes);
             let _ = self.builder.add(key, value);
@@ -99,7 +105,7 @@ impl SsTableBuilder {
             this.meta.push(BlockMeta {
                 offset: off,
                 first_key: Key::from_bytes(Bytes::from(this.first_key)),
-                last_key: Key::from_bytes(Bytes::from(this.last_key))
+                last_key: Key::from_bytes(Bytes::from(this.last_key)),
             });
             this.data.extend_from_slice(&encoded_len.to_be_bytes());
             this.data.extend_from_slice(&encoded_bytes);
@@ -115,7 +121,7 @@ impl SsTableBuilder {
 
         // create file and wirte data
         let file = FileObject::create(path.as_ref(), this.data)?;
-        
+
         Ok(SsTable {
             file,
             block_meta: this.meta.clone(),
@@ -38,7 +38,7 @@ impl SsTableIterator {
         Ok(Self {
             table,
             blk_iter: iter,
-            blk_idx,            
+            blk_idx,
         })
     }
 
@@ -211,6 +211,33 @@ pub fn generate_sst(
     builder.build(id, block_cache, path.as_ref()).unwrap()
 }
 
+/// The key of the `idx`-th entry of a test, so that keys sort by `idx`.
+pub fn key_of(idx: usize) -> Vec<u8> {
+    format!("key_{:05}", idx).into_bytes()
+}
+
+/// The value of the `idx`-th entry of a test.
+pub fn value_of(idx: usize) -> Vec<u8> {
+    format!("value_{:05}", idx).into_bytes()
+}
+
+/// Add `key_of(idx)` and `value_of(idx)` for each index to `builder`, and build the SST.
+pub fn generate_sst_of(
+    mut builder: SsTableBuilder,
+    indices: impl IntoIterator<Item = usize>,
+    id: usize,
+    path: impl AsRef<Path>,
+    block_cache: Option<Arc<BlockCache>>,
+) -> SsTable {
+    for idx in indices {
+        builder.add(
+            KeySlice::for_testing_from_slice_no_ts(&key_of(idx)),
+            &value_of(idx),
+        );
+    }
+    builder.build(id, block_cache, path.as_ref()).unwrap()
+}
+
 pub fn generate_sst_with_ts(
     id: usize,
     path: impl AsRef<Path>,